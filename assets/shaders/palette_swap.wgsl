@@ -0,0 +1,22 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct PaletteSwap {
+    // Atlas uvs of the sprite's frame, min in xy and max in zw
+    frame: vec4<f32>,
+    row: u32,
+};
+
+@group(1) @binding(0) var<uniform> palette_swap: PaletteSwap;
+@group(1) @binding(1) var index_map: texture_2d<f32>;
+@group(1) @binding(2) var index_sampler: sampler;
+@group(1) @binding(3) var palettes: texture_2d<f32>;
+
+// The index map's red channel picks the column in the palette texture, the
+// palette row picks which set of colors to use
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let index = textureSample(index_map, index_sampler, mix(palette_swap.frame.xy, palette_swap.frame.zw, in.uv));
+    let column = i32(round(index.r * 255.0));
+    let color = textureLoad(palettes, vec2<i32>(column, i32(palette_swap.row)), 0);
+    return vec4<f32>(color.rgb, color.a * index.a);
+}
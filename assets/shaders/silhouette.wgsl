@@ -0,0 +1,30 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct Silhouette {
+    color: vec4<f32>,
+    // Atlas uvs of the sprite's frame, min in xy and max in zw
+    frame: vec4<f32>,
+    // World space rect of the foreground layer, bottom left in xy and size in zw
+    foreground: vec4<f32>,
+};
+
+@group(1) @binding(0) var<uniform> silhouette: Silhouette;
+@group(1) @binding(1) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(2) var sprite_sampler: sampler;
+@group(1) @binding(3) var foreground_texture: texture_2d<f32>;
+@group(1) @binding(4) var foreground_sampler: sampler;
+
+// Flat color wherever the sprite is covered by an opaque foreground pixel
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sprite_uv = mix(silhouette.frame.xy, silhouette.frame.zw, in.uv);
+    let sprite_alpha = textureSample(sprite_texture, sprite_sampler, sprite_uv).a;
+
+    var foreground_uv = (in.world_position.xy - silhouette.foreground.xy) / silhouette.foreground.zw;
+    foreground_uv.y = 1.0 - foreground_uv.y;
+    let inside = all(foreground_uv >= vec2<f32>(0.0)) && all(foreground_uv <= vec2<f32>(1.0));
+    let foreground_alpha = textureSample(foreground_texture, foreground_sampler, clamp(foreground_uv, vec2<f32>(0.0), vec2<f32>(1.0))).a * f32(inside);
+
+    let alpha = step(0.5, sprite_alpha) * step(0.5, foreground_alpha) * silhouette.color.a;
+    return vec4<f32>(silhouette.color.rgb, alpha);
+}
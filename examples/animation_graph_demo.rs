@@ -0,0 +1,82 @@
+// Drives the hero sprite through a tiny animation graph. Hold A or D to run,
+// let go to idle. Only the animation plugin and the animation_graph! macro are
+// used, the velocity is set straight from the keyboard.
+use bevy::prelude::*;
+use macros::animation_graph;
+use platformer::prelude::*;
+
+animation_graph!(
+    Hero,
+    {},
+    {vel: platformer::physics::body::Velocity, mut controller: AnimationController},
+    // Play the animation named after each state whenever the graph moves to it
+    Idle {
+        on_enter { controller.play("idle"); },
+        Run -> vel.0.x != 0.0
+    },
+    Run {
+        on_enter { controller.play("run"); },
+        Idle -> vel.0.x == 0.0
+    }
+);
+
+const IDLE_ROW: usize = 5;
+const RUN_ROW: usize = 1;
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_handle = asset_server.load("herochar_spritesheet.png");
+    let atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 8, 15, None, None);
+
+    let mut animation_definitions = vec![AnimationDefinition::default(); 15];
+    animation_definitions[IDLE_ROW] = AnimationDefinition {name: String::from("idle"), number_of_frames: 4, frame_time: 0.1, repeating: true, ..Default::default()};
+    animation_definitions[RUN_ROW] = AnimationDefinition {name: String::from("run"), number_of_frames: 6, frame_time: 0.07, repeating: true, ..Default::default()};
+
+    commands.spawn(Camera2dBundle::default());
+    commands.spawn(AnimatedSpriteBundle {
+        sprite_sheet: SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(atlas),
+            transform: Transform::from_scale(Vec3::splat(8.0)),
+            ..Default::default()
+        },
+        sprite_sheet_definitions: SpriteSheetDefinition {
+            animation_definitions,
+            rows: 15,
+            columns: 8,
+            ..Default::default()
+        },
+        animation_timer: AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+        current_row: Row(IDLE_ROW),
+        current_col: Col(0),
+        controller: AnimationController::new("idle"),
+    })
+    .insert(HeroAnimationUpdate::Idle)
+    .insert(Velocity::default());
+}
+
+fn keyboard_velocity(keys: Res<Input<KeyCode>>, mut query: Query<(&mut Velocity, &mut TextureAtlasSprite)>) {
+    for (mut velocity, mut sprite) in query.iter_mut() {
+        velocity.0.x = if keys.pressed(KeyCode::A) {
+            -1.0
+        } else if keys.pressed(KeyCode::D) {
+            1.0
+        } else {
+            0.0
+        };
+
+        if velocity.0.x != 0.0 {
+            sprite.flip_x = velocity.0.x < 0.0;
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins.set(ImagePlugin::default_nearest()), AnimationPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (keyboard_velocity, hero_animation_update).chain())
+        .run();
+}
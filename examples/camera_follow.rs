@@ -0,0 +1,63 @@
+// The camera plugin follows anything tagged with CameraTarget. Move the orb
+// around with WASD and the camera eases after it, the grid of squares is only
+// there so the movement is visible.
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+use platformer::prelude::*;
+
+const SPEED: f32 = 600.0;
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let mut camera = Camera2dBundle::default();
+    camera.transform.translation.z = 800.0;
+    commands.spawn(camera).insert(MainCamera);
+
+    for x in -10..=10 {
+        for y in -10..=10 {
+            commands.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(0.3, 0.3, 0.4),
+                    custom_size: Some(Vec2::splat(20.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(Vec3::new(x as f32 * 100.0, y as f32 * 100.0, 0.0)),
+                ..Default::default()
+            });
+        }
+    }
+
+    commands.spawn(SpriteBundle {
+        texture: asset_server.load("miscellaneous sprites/orb.png"),
+        transform: Transform {
+            translation: Vec3::new(0.0, 0.0, 500.0),
+            scale: Vec3::splat(4.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .insert(CameraTarget);
+}
+
+fn move_target(time: Res<Time>, keys: Res<Input<KeyCode>>, mut target_query: Query<&mut Transform, With<CameraTarget>>) {
+    let mut direction = Vec2::ZERO;
+    if keys.pressed(KeyCode::A) { direction.x -= 1.0; }
+    if keys.pressed(KeyCode::D) { direction.x += 1.0; }
+    if keys.pressed(KeyCode::S) { direction.y -= 1.0; }
+    if keys.pressed(KeyCode::W) { direction.y += 1.0; }
+
+    for mut transform in target_query.iter_mut() {
+        let movement = direction.normalize_or_zero() * SPEED * time.delta_seconds();
+        transform.translation += movement.extend(0.0);
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins.set(ImagePlugin::default_nearest()), EguiPlugin, CameraPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_target)
+        .run();
+}
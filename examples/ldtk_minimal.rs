@@ -0,0 +1,89 @@
+// Loads an LDtk project through the asset loader and draws the tiles of its
+// first level. Everything else (colliders, entities, backgrounds) is left to
+// the level plugin, this only shows how to get at the parsed project.
+use bevy::prelude::*;
+use platformer::{ldtk::ldtk_json::Project, prelude::*};
+
+const SCALE: f32 = 4.0;
+
+#[derive(Resource)]
+struct MinimalMap {
+    project: Handle<Project>,
+    spawned: bool,
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2dBundle::default());
+    commands.insert_resource(MinimalMap {
+        project: asset_server.load("maps/physics-testing.ldtk"),
+        spawned: false,
+    });
+}
+
+fn spawn_first_level(
+    mut commands: Commands,
+    mut map: ResMut<MinimalMap>,
+    asset_server: Res<AssetServer>,
+    projects: Res<Assets<Project>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    if map.spawned {
+        return;
+    }
+
+    let project = match projects.get(&map.project) {
+        Some(project) => project,
+        None => return,
+    };
+
+    let level = &project.levels[0];
+    info!("Loaded {} with {} level(s)", level.identifier, project.levels.len());
+
+    for layer in level.layer_instances.iter().flatten() {
+        let tileset = match layer.tileset_def_uid.and_then(|uid| project.defs.tilesets.iter().find(|tileset| tileset.uid == uid)) {
+            Some(tileset) => tileset,
+            None => continue,
+        };
+
+        let texture_handle = asset_server.load(format!("maps/{}", tileset.rel_path));
+        let atlas = texture_atlases.add(TextureAtlas::from_grid(
+            texture_handle,
+            Vec2::splat(tileset.tile_grid_size as f32),
+            (tileset.px_wid / tileset.tile_grid_size) as usize,
+            (tileset.px_hei / tileset.tile_grid_size) as usize,
+            None,
+            None,
+        ));
+
+        // LDtk is top left origin with y pointing down, so flip and center it
+        let half_level = Vec2::new(level.px_wid as f32, level.px_hei as f32) / 2.0;
+        let half_tile = layer.grid_size as f32 / 2.0;
+        for tile in layer.grid_tiles.iter().chain(layer.auto_layer_tiles.iter()) {
+            let position = Vec2::new(
+                tile.px[0] as f32 + half_tile - half_level.x,
+                -(tile.px[1] as f32 + half_tile) + half_level.y,
+            ) * SCALE;
+
+            commands.spawn(SpriteSheetBundle {
+                transform: Transform {
+                    translation: position.extend(0.0),
+                    scale: Vec3::splat(SCALE),
+                    ..Default::default()
+                },
+                sprite: TextureAtlasSprite::new(tile.t as usize),
+                texture_atlas: atlas.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    map.spawned = true;
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins.set(ImagePlugin::default_nearest()), LdtkLoaderPlugin::default()))
+        .add_systems(Startup, setup)
+        .add_systems(Update, spawn_first_level)
+        .run();
+}
@@ -0,0 +1,57 @@
+// Background layers scrolling at different rates while the camera target
+// drifts back and forth on its own. Layers further away from the target on the
+// z axis move slower, the egui window shows the factor computed for each one.
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+use platformer::prelude::*;
+
+const SCALE: f32 = 8.0;
+
+// Layers have to exist before parallax_start runs in the startup schedule
+fn spawn_layers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let mut camera = Camera2dBundle::default();
+    camera.transform.translation.z = 800.0;
+    commands.spawn(camera).insert(MainCamera);
+
+    let layers = [
+        ("tiles and background_foreground/bg_0.png", 100.0),
+        ("tiles and background_foreground/bg_1.png", 200.0),
+        ("tiles and background_foreground/bg_2.png", 300.0),
+        ("tiles and background_foreground/fg_1.png", 600.0),
+    ];
+
+    for (path, z) in layers.iter() {
+        commands.spawn(SpriteBundle {
+            texture: asset_server.load(*path),
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, *z),
+                scale: Vec3::splat(SCALE),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ParallaxLayer::default());
+    }
+
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(Vec3::new(0.0, 0.0, 500.0))),
+        CameraTarget
+    ));
+}
+
+fn drift_target(time: Res<Time>, mut target_query: Query<&mut Transform, With<CameraTarget>>) {
+    for mut transform in target_query.iter_mut() {
+        transform.translation.x = (time.elapsed_seconds() * 0.5).sin() * 600.0;
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins.set(ImagePlugin::default_nearest()), EguiPlugin, CameraPlugin))
+        .add_systems(PreStartup, spawn_layers)
+        .add_systems(Update, drift_target)
+        .run();
+}
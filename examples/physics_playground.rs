@@ -0,0 +1,89 @@
+// Drops a handful of actors onto a few solids using only the physics plugin.
+// Press space to throw everything back up, the debug plugin outlines actors in
+// green and solids in red. One of the solids patrols side to side and carries
+// whatever lands on it. Velocities are in units of 100 pixels.
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+use platformer::prelude::*;
+
+const PLATFORM_RANGE: f32 = 300.0;
+
+#[derive(Component)]
+struct Patrol;
+
+fn spawn_solid(commands: &mut Commands, position: Vec2, half_size: IVec2) {
+    commands.spawn(BodyBundle {
+        position: Position(position),
+        ..Default::default()
+    })
+    .insert(AABB {
+        position: IVec2::ZERO,
+        half_size,
+    });
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+
+    spawn_solid(&mut commands, Vec2::new(0.0, -300.0), IVec2::new(500, 16));
+    spawn_solid(&mut commands, Vec2::new(-200.0, -100.0), IVec2::new(100, 8));
+    spawn_solid(&mut commands, Vec2::new(250.0, 0.0), IVec2::new(80, 8));
+
+    commands.spawn(BodyBundle {
+        position: Position(Vec2::new(0.0, -180.0)),
+        velocity: Velocity(Vec2::new(1.5, 0.0)),
+        ..Default::default()
+    })
+    .insert(AABB {
+        position: IVec2::ZERO,
+        half_size: IVec2::new(60, 8),
+    })
+    .insert(Patrol);
+
+    for i in 0..8 {
+        commands.spawn(BodyBundle {
+            body_type: BodyType::Actor,
+            position: Position(Vec2::new(-350.0 + i as f32 * 100.0, 200.0 + i as f32 * 20.0)),
+            velocity: Velocity(Vec2::new(0.5 - i as f32 * 0.15, 0.0)),
+            ..Default::default()
+        })
+        .insert((Acceleration::default(), GravityScale(1.0), SpeedLimits { max_horizontal: 5.0, max_fall: 12.0 }))
+        .insert(AABB {
+            position: IVec2::ZERO,
+            half_size: IVec2::new(10 + i, 10 + i),
+        });
+    }
+}
+
+fn throw_actors(keys: Res<Input<KeyCode>>, mut body_query: Query<(&mut Velocity, &BodyType)>) {
+    if keys.just_pressed(KeyCode::Space) {
+        for (mut velocity, body_type) in body_query.iter_mut() {
+            if *body_type == BodyType::Actor {
+                velocity.0.y = 9.0;
+            }
+        }
+    }
+}
+
+fn patrol_platforms(mut platform_query: Query<(&Position, &mut Velocity), With<Patrol>>) {
+    for (position, mut velocity) in platform_query.iter_mut() {
+        if position.0.x.abs() > PLATFORM_RANGE && position.0.x.signum() == velocity.0.x.signum() {
+            velocity.0.x = -velocity.0.x;
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, EguiPlugin, PhysicsPlugin, DebugPhysicsPlugin))
+        .insert_resource(PhysicsConfig {
+            pixels_per_unit: 100.0,
+            max_step_pixels: 64,
+            gravity: Vec2::new(0.0, -15.0),
+            ..Default::default()
+        })
+        .add_systems(Startup, setup)
+        .add_systems(Update, throw_actors)
+        .add_systems(FixedUpdate, patrol_platforms.in_set(PhysicsStages::PreStep))
+        .run();
+}
@@ -1,29 +1,19 @@
-#![feature(proc_macro_diagnostic)]
-use std::panic;
 use proc_macro::TokenStream;
-use syn::{Expr, ExprBinary, ExprBlock, ExprType, Ident, Lit, Path, Token, Type, braced, parse::{Parse, ParseStream}, parse_macro_input, punctuated::Punctuated, spanned::Spanned};
-use quote::{format_ident, quote};
+use syn::{Attribute, Block, Expr, Ident, Token, Type, braced, parse::{Parse, ParseStream}, parse_macro_input, punctuated::Punctuated, token};
+use quote::{format_ident, quote, quote_spanned};
 
 #[derive(Clone)]
 struct Transition {
     next_state: Ident,
-    transition_check: ExprBinary
+    // Anything that evaluates to a bool
+    transition_check: Expr
 }
 
 impl Parse for Transition {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let next_state: Ident = input.parse()?;
         input.parse::<Token![->]>()?;
-        let transition_check_expr: Expr = input.parse()?;
-        let transition_check: ExprBinary = match transition_check_expr {
-            Expr::Binary(expr_bin) => {
-                expr_bin
-            },
-            _ => {
-                transition_check_expr.span().unwrap().error("Expected binary expr");
-                panic!("Expected a boolean expression");
-            }
-        };
+        let transition_check: Expr = input.parse()?;
 
         Ok(Transition {
             next_state: next_state,
@@ -32,10 +22,37 @@ impl Parse for Transition {
     }
 }
 
+// `name: Type` reads the resource or component, `mut name: Type` can change it
+#[derive(Clone)]
+struct Param {
+    mutable: bool,
+    name: Ident,
+    ty: Type
+}
+
+impl Parse for Param {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mutable = input.parse::<Option<Token![mut]>>()?.is_some();
+        let name: Ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty: Type = input.parse()?;
+
+        Ok(Param {
+            mutable,
+            name,
+            ty
+        })
+    }
+}
+
 #[derive(Clone)]
 struct State {
     name: Ident,
-    transitions: Vec<Transition>
+    transitions: Vec<Transition>,
+    on_enter: Option<Block>,
+    on_exit: Option<Block>,
+    // Sub-states, entering this state enters the first one
+    children: Vec<State>
 }
 
 impl Parse for State {
@@ -43,37 +60,78 @@ impl Parse for State {
         let name: Ident = input.parse()?;
         let content;
         let _ = braced!(content in input);
-        let transitions: Vec<Transition> = (Punctuated::<Transition, Token![,]>::parse_terminated(&content)?).into_iter().collect();
 
-        Ok(State{
+        let mut state = State {
             name: name,
-            transitions: transitions
-        })
+            transitions: Vec::new(),
+            on_enter: None,
+            on_exit: None,
+            children: Vec::new()
+        };
+        // Transitions, sub-states and `on_enter { .. }` / `on_exit { .. }`
+        // blocks in any order
+        while !content.is_empty() {
+            if content.peek(Ident) && content.peek2(token::Brace) {
+                let action: Ident = content.fork().parse()?;
+                match action.to_string().as_str() {
+                    "on_enter" | "on_exit" => {
+                        content.parse::<Ident>()?;
+                        let block = Some(content.parse::<Block>()?);
+                        if action == "on_enter" {
+                            state.on_enter = block;
+                        } else {
+                            state.on_exit = block;
+                        }
+                    },
+                    _ => state.children.push(content.parse()?),
+                }
+            } else {
+                state.transitions.push(content.parse()?);
+            }
+
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+
+        Ok(state)
     }
 }
 
 struct AnimationGraph {
+    // From `#[default_state(State)]` before the name
+    default_state: Option<Ident>,
     name: Ident,
-    resources: Vec<ExprType>,
-    params: Vec<ExprType>,
+    resources: Vec<Param>,
+    params: Vec<Param>,
     states: Vec<State>
 }
 
 impl Parse for AnimationGraph {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut default_state = None;
+        for attr in input.call(Attribute::parse_outer)? {
+            if attr.path.is_ident("default_state") {
+                default_state = Some(attr.parse_args::<Ident>()?);
+            } else {
+                return Err(syn::Error::new_spanned(attr.path, "Expected default_state"));
+            }
+        }
         let name: Ident = input.parse()?;
         input.parse::<Token![,]>()?;
         let resources_content;
         let _ = braced!(resources_content in input);
-        let resources: Vec<ExprType> = (Punctuated::<ExprType, Token![,]>::parse_terminated(&resources_content)?).into_iter().collect();
+        let resources: Vec<Param> = (Punctuated::<Param, Token![,]>::parse_terminated(&resources_content)?).into_iter().collect();
         input.parse::<Token![,]>()?;
         let params_content;
         let _ = braced!(params_content in input);
-        let params: Vec<ExprType> = (Punctuated::<ExprType, Token![,]>::parse_terminated(&params_content)?).into_iter().collect();
+        let params: Vec<Param> = (Punctuated::<Param, Token![,]>::parse_terminated(&params_content)?).into_iter().collect();
         input.parse::<Token![,]>()?;
         let states: Vec<State> = (Punctuated::<State, Token![,]>::parse_terminated(&input)?).into_iter().collect();
 
         Ok(AnimationGraph {
+            default_state,
             name,
             resources,
             params,
@@ -82,47 +140,162 @@ impl Parse for AnimationGraph {
     }
 }
 
+// A state flattened out of the tree, `Any` is the root
+struct Node {
+    name: Ident,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    transitions: Vec<Transition>,
+    on_enter: Option<Block>,
+    on_exit: Option<Block>
+}
+
+fn flatten(state: State, parent: Option<usize>, nodes: &mut Vec<Node>) -> usize {
+    let index = nodes.len();
+    nodes.push(Node {
+        name: state.name,
+        parent,
+        children: Vec::new(),
+        transitions: state.transitions,
+        on_enter: state.on_enter,
+        on_exit: state.on_exit
+    });
+    for child in state.children {
+        let child = flatten(child, Some(index), nodes);
+        nodes[index].children.push(child);
+    }
+    index
+}
+
+struct Graph {
+    nodes: Vec<Node>
+}
+
+impl Graph {
+    fn find(&self, name: &Ident) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == *name)
+    }
+
+    // The state the graph ends up in going to `index`
+    fn initial_leaf(&self, mut index: usize) -> usize {
+        while let Some(child) = self.nodes[index].children.first() {
+            index = *child;
+        }
+        index
+    }
+
+    // `index`, its parent and so on up to the root
+    fn ancestors(&self, index: usize) -> Vec<usize> {
+        let mut ancestors = vec![index];
+        while let Some(parent) = self.nodes[*ancestors.last().unwrap()].parent {
+            ancestors.push(parent);
+        }
+        ancestors
+    }
+
+    fn leaves_in(&self, index: usize) -> Vec<usize> {
+        if self.nodes[index].children.is_empty() {
+            return vec![index];
+        }
+        self.nodes[index].children.iter().flat_map(|child| self.leaves_in(*child)).collect()
+    }
+
+    // One `if` per transition out of `index`, for chaining with else in the
+    // order they're listed. Transitions from a state back into itself, or to
+    // a target an earlier one already goes to, get a deprecated const used in
+    // the system. It's the closest thing to a compile warning a stable proc
+    // macro has.
+    fn transition_ifs(&self, enum_ident: &Ident, index: usize, warnings: &mut Vec<proc_macro2::TokenStream>) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+        let from = &self.nodes[index].name;
+        let is_leaf = self.nodes[index].children.is_empty();
+        let mut targets: Vec<Ident> = Vec::new();
+        self.nodes[index].transitions.iter().map(|transition|{
+            let next_state = &transition.next_state;
+            let target = self.find(next_state)
+                .filter(|target| *target != 0)
+                .ok_or_else(|| syn::Error::new(next_state.span(), format!("No state named {}", next_state)))?;
+            let note = if is_leaf && next_state == from {
+                Some(format!("{} -> {} goes nowhere, the graph is already in {}", from, next_state, from))
+            } else if targets.contains(next_state) {
+                Some(format!("{} -> {} is listed twice, join the checks with || instead", from, next_state))
+            } else {
+                None
+            };
+            if let Some(note) = note {
+                let warning_ident = format_ident!("{}_to_{}_{}", from, next_state, warnings.len(), span = next_state.span());
+                warnings.push(quote_spanned! {next_state.span()=>
+                    #[deprecated(note = #note)]
+                    #[allow(non_upper_case_globals)]
+                    const #warning_ident: () = ();
+                    #[allow(clippy::let_unit_value)]
+                    let _ = #warning_ident;
+                });
+            }
+            targets.push(next_state.clone());
+
+            let leaf = &self.nodes[self.initial_leaf(target)].name;
+            let transition_check = &transition.transition_check;
+            Ok(quote! {
+                if #transition_check {
+                    next_state = Some(#enum_ident::#leaf);
+                }
+            })
+        }).collect()
+    }
+}
+
+// animation_graph!(Name, {resources}, {components}, State { Next -> condition, .. }, ..)
+// makes a `Name` module with a `NameAnimationUpdate` enum of the states and a
+// `name_animation_update` system moving between them, both also usable
+// straight from where the macro is. Starting with `#[default_state(State)]`
+// implements Default for the enum as that state. `state_time` can be used in
+// transitions and actions for the seconds spent in the current state, kept
+// in a `NameStateTime` component, and is 0 in on_enter. Resources are
+// `name: Type` or `mut name: Type` and become Res or ResMut params,
+// components are the same and get read, or changed with `mut`, on the entity
+// with the enum. Transitions are checked in the order they're listed and the
+// first one that passes is taken, so earlier ones have priority. A state can
+// also have `on_enter { .. }` and `on_exit { .. }` blocks that run when the
+// graph moves into or out of it, with the resources and components in scope.
+// States can hold sub-states, `Airborne { Jump { .. }, Fall { .. }, .. }`,
+// only the innermost ones end up in the enum. Going to a state with sub-states
+// goes to its first one, and its transitions are checked in all of them
+// before their own. Its on_enter and on_exit only run going between a state
+// inside it and one outside. An `Any { Next -> condition, .. }` block holds
+// transitions every state has, like dying, checked before everything else.
+// Types resolve from wherever the macro is used.
 #[proc_macro]
 pub fn animation_graph(input: TokenStream) -> TokenStream {
-    let AnimationGraph { 
+    let AnimationGraph {
+        default_state,
         name,
         resources,
-        params, 
-        states 
+        params,
+        states
     } = parse_macro_input!(input as AnimationGraph);
 
-    let state_idents: Vec<Ident> = states.clone().into_iter().map(|state| {
-        state.name
-    }).collect();
-
-    let param_types: Vec<Type> = params.clone().into_iter().map(|param| {
-        *param.ty
-    }).collect();
-
-    let param_names: Vec<Ident> = params.clone().into_iter().map(|param| {
-        let temp = if let Expr::Path(path) = *param.expr {
-            path.path.segments[0].ident.clone()
+    let param_types: Vec<proc_macro2::TokenStream> = params.clone().into_iter().map(|Param { mutable, ty, .. }| {
+        if mutable {
+            quote! { &mut #ty }
         } else {
-            (*param.expr).span().unwrap().error("Expected ident here");
-            panic!("Expected ident");
-        };
-
-        temp
+            quote! { &#ty }
+        }
     }).collect();
 
-    let resource_types: Vec<Type> = resources.clone().into_iter().map(|resource| {
-        *resource.ty
+    let param_names: Vec<proc_macro2::TokenStream> = params.clone().into_iter().map(|Param { mutable, name, .. }| {
+        if mutable {
+            quote! { mut #name }
+        } else {
+            quote! { #name }
+        }
     }).collect();
 
-    let resource_names: Vec<Ident> = resources.clone().into_iter().map(|resource| {
-        let temp = if let Expr::Path(path) = *resource.expr {
-            path.path.segments[0].ident.clone()
+    let resource_params: Vec<proc_macro2::TokenStream> = resources.into_iter().map(|Param { mutable, name, ty }| {
+        if mutable {
+            quote! { mut #name: bevy::ecs::system::ResMut<#ty> }
         } else {
-            (*resource.expr).span().unwrap().error("Expected ident here");
-            panic!("Expected ident");
-        };
-
-        temp
+            quote! { #name: bevy::ecs::system::Res<#ty> }
+        }
     }).collect();
 
     let enum_ident = format_ident!("{}AnimationUpdate", name);
@@ -130,60 +303,183 @@ pub fn animation_graph(input: TokenStream) -> TokenStream {
     let system_ident = format_ident!("{}_animation_update", lower_name_ident);
     let query_ident = format_ident!("{}_query", lower_name_ident);
     let enum_query_for_ident = format_ident!("{}_action", lower_name_ident);
+    let state_time_ident = format_ident!("{}StateTime", name);
 
     // let state_paths: Vec<Ident> = states.clone().into_iter().map(|state| {
     //     format_ident!("{}::{}::{}", name, enum_ident, state.name)
     // }).collect();
 
-    let states_match_statment: Vec<proc_macro2::TokenStream> = states.clone().into_iter().map(|state|{
-        let state_name = state.name;
-        let state_name_arm: proc_macro2::TokenStream = quote! {
-            #enum_ident::#state_name
-        }.into();
+    // `Any` is the root of the tree rather than a state, so its transitions
+    // are checked in every state, before any of the state's own
+    let (any_states, states): (Vec<State>, Vec<State>) = states.into_iter().partition(|state| state.name == "Any");
+    if let Some(state) = any_states.iter().find(|state| state.on_enter.is_some() || state.on_exit.is_some() || !state.children.is_empty()) {
+        return syn::Error::new(state.name.span(), "Any can only have transitions, the graph is never in it").to_compile_error().into();
+    }
+    let mut nodes = Vec::new();
+    flatten(State {
+        name: format_ident!("Any"),
+        transitions: any_states.into_iter().flat_map(|state| state.transitions).collect(),
+        on_enter: None,
+        on_exit: None,
+        children: states
+    }, None, &mut nodes);
+    let graph = Graph { nodes };
+    for (index, node) in graph.nodes.iter().enumerate() {
+        if index != 0 && graph.nodes[..index].iter().any(|other| other.name == node.name) {
+            return syn::Error::new(node.name.span(), format!("There's already a state named {}", node.name)).to_compile_error().into();
+        }
+    }
 
-        let transition_ifs: proc_macro2::TokenStream = state.transitions.into_iter().map(|transition|{
-            let next_state= transition.next_state;
-            let next_state_path: proc_macro2::TokenStream = quote! {
-                #enum_ident::#next_state
-            }.into();
-            let transition_check = transition.transition_check;
+    // Only states without sub-states make it into the enum
+    let leaves = graph.leaves_in(0);
+    let state_idents: Vec<Ident> = leaves.iter().map(|leaf| graph.nodes[*leaf].name.clone()).collect();
 
+    let default_impl = match default_state.map(|default_state| (graph.find(&default_state).filter(|index| *index != 0), default_state)) {
+        Some((None, default_state)) => {
+            return syn::Error::new(default_state.span(), "default_state has to be one of the graph's states").to_compile_error().into();
+        },
+        Some((Some(index), _)) => {
+            let default_state = &graph.nodes[graph.initial_leaf(index)].name;
             quote! {
-                if #transition_check {
-                    *#enum_query_for_ident = #next_state_path;
+                impl Default for #enum_ident {
+                    fn default() -> Self {
+                        #enum_ident::#default_state
+                    }
                 }
             }
-        }).collect();
+        },
+        None => quote! {},
+    };
+
+    let mut warnings: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut node_ifs: Vec<Vec<proc_macro2::TokenStream>> = Vec::new();
+    for index in 0..graph.nodes.len() {
+        match graph.transition_ifs(&enum_ident, index, &mut warnings) {
+            Ok(ifs) => node_ifs.push(ifs),
+            Err(err) => return err.to_compile_error().into(),
+        }
+    }
+
+    // Outermost first, so a parent's transitions win over its sub-states'
+    let states_match_statment: Vec<proc_macro2::TokenStream> = leaves.iter().map(|leaf| {
+        let state_name = &graph.nodes[*leaf].name;
+        let transition_ifs: Vec<&proc_macro2::TokenStream> = graph.ancestors(*leaf).into_iter().rev()
+            .flat_map(|index| node_ifs[index].iter())
+            .collect();
 
         quote! {
-            #state_name_arm => {
-                #transition_ifs
+            #enum_ident::#state_name => {
+                #(#transition_ifs)else*
             },
         }
     }).collect();
 
+    let transitions = quote! {
+        match *#enum_query_for_ident {
+            #(#states_match_statment)*
+        }
+    };
+
+    // A parent's blocks only run when going between a state inside it and one
+    // outside of it. Exits go from the state outwards, enters inwards to the
+    // state. States without the block do nothing.
+    let in_node = |index: usize, state: &proc_macro2::TokenStream| -> proc_macro2::TokenStream {
+        let leaves: Vec<&Ident> = graph.leaves_in(index).into_iter().map(|leaf| &graph.nodes[leaf].name).collect();
+        quote! { matches!(#state, #(#enum_ident::#leaves)|*) }
+    };
+    let exit_arms: Vec<proc_macro2::TokenStream> = leaves.iter().map(|leaf| {
+        let state_name = &graph.nodes[*leaf].name;
+        let blocks: Vec<proc_macro2::TokenStream> = graph.ancestors(*leaf).into_iter().filter_map(|index| {
+            let block = graph.nodes[index].on_exit.as_ref()?;
+            if index == *leaf {
+                return Some(quote! { #block });
+            }
+            let staying = in_node(index, &quote! { next_state });
+            Some(quote! { if !#staying #block })
+        }).collect();
+        quote! {
+            #enum_ident::#state_name => { #(#blocks)* },
+        }
+    }).collect();
+    let enter_arms: Vec<proc_macro2::TokenStream> = leaves.iter().map(|leaf| {
+        let state_name = &graph.nodes[*leaf].name;
+        let blocks: Vec<proc_macro2::TokenStream> = graph.ancestors(*leaf).into_iter().rev().filter_map(|index| {
+            let block = graph.nodes[index].on_enter.as_ref()?;
+            if index == *leaf {
+                return Some(quote! { #block });
+            }
+            let was_in = in_node(index, &quote! { previous_state });
+            Some(quote! { if !#was_in #block })
+        }).collect();
+        quote! {
+            #enum_ident::#state_name => { #(#blocks)* },
+        }
+    }).collect();
+
     let expanded = quote! {
+        #[allow(non_snake_case)]
         pub mod #name {
-            #[derive(Debug)]
+            // Types in the graph can be named like they are where the macro is used
+            #[allow(unused_imports)]
+            use super::*;
+
+            #[derive(bevy::prelude::Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
             pub enum #enum_ident {
                 #(#state_idents,)*
             }
 
+            #default_impl
+
+            // Seconds since the graph moved to the state it's in, added by
+            // the system to entities that don't have it yet
+            #[derive(bevy::prelude::Component, Debug, Default, Clone, Copy)]
+            pub struct #state_time_ident(pub f32);
+
+            #[allow(unused_variables, unused_mut)]
             pub fn #system_ident (
-                #(#resource_names: #resource_types,)*
-                mut #query_ident: bevy::ecs::system::Query<(&mut #enum_ident, #(&#param_types,)*)>
+                #(#resource_params,)*
+                animation_graph_time: bevy::ecs::system::Res<bevy::time::Time>,
+                mut animation_graph_commands: bevy::ecs::system::Commands,
+                mut #query_ident: bevy::ecs::system::Query<(bevy::ecs::entity::Entity, &mut #enum_ident, Option<&mut #state_time_ident>, #(#param_types,)*)>
             ) {
-                let mut i = 0;
-                for (mut #enum_query_for_ident, #(#param_names,)*) in #query_ident.iter_mut() {
-                    // println!("vel here {:?} {}", vel, i);
-                    i = i + 1;
-                    match *#enum_query_for_ident {
-                        #(#states_match_statment)*
+                #(#warnings)*
+                for (animation_graph_entity, mut #enum_query_for_ident, animation_graph_state_time, #(#param_names,)*) in #query_ident.iter_mut() {
+                    let state_time = match animation_graph_state_time.as_deref() {
+                        Some(state_time) => state_time.0 + animation_graph_time.delta_seconds(),
+                        None => 0.0,
+                    };
+
+                    let mut next_state: Option<#enum_ident> = None;
+                    #transitions
+
+                    let mut entered = false;
+                    if let Some(next_state) = next_state.filter(|next_state| *next_state != *#enum_query_for_ident) {
+                        match *#enum_query_for_ident {
+                            #(#exit_arms)*
+                        }
+                        let previous_state = *#enum_query_for_ident;
+                        *#enum_query_for_ident = next_state;
+                        entered = true;
+                        let state_time = 0.0_f32;
+                        match next_state {
+                            #(#enter_arms)*
+                        }
+                    }
+
+                    let state_time = if entered { 0.0 } else { state_time };
+                    match animation_graph_state_time {
+                        Some(mut animation_graph_state_time) => animation_graph_state_time.0 = state_time,
+                        None => {
+                            animation_graph_commands.entity(animation_graph_entity).insert(#state_time_ident(state_time));
+                        }
                     }
                 }
             }
         }
+
+        // So the call site can name them without going through the module
+        pub use self::#name::{#enum_ident, #state_time_ident, #system_ident};
     };
 
     return expanded.into();
-}
\ No newline at end of file
+}
@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+use serde::Deserialize;
+
+use crate::physics::{body::{BodyBundle, Position}, collision::{AABB, CollisionExclusions}, trigger::Sensor};
+
+use super::{Col, Row, SpriteSheetDefinition, sockets::AnimationSockets};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum FrameBoxKind {
+    // Hurts whatever it overlaps
+    Hitbox,
+    // Where the owner can be hurt on this frame
+    Hurtbox
+}
+
+// A box shown on one frame of an animation. `offset` and `half_size` are in
+// pixels from the body position with the sprite facing right, flipped sprites
+// mirror it. Walls and ceilings don't turn it. With a `socket` the offset is
+// from wherever that socket is instead, like a blade following the hand.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrameBox {
+    pub frame: usize,
+    pub kind: FrameBoxKind,
+    pub offset: (i32, i32),
+    pub half_size: (i32, i32),
+    #[serde(default)]
+    pub socket: Option<String>
+}
+
+// Where an attack is on a frame. Frames before the first hitbox are startup,
+// from the first to the last hitbox active and the rest recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackPhase {
+    Startup,
+    Active,
+    Recovery
+}
+
+// None for animations without hitboxes, which aren't attacks
+pub fn attack_phase(boxes: &[FrameBox], frame: usize) -> Option<AttackPhase> {
+    let mut hit_frames = boxes.iter().filter(|frame_box| frame_box.kind == FrameBoxKind::Hitbox).map(|frame_box| frame_box.frame);
+    let first = hit_frames.next()?;
+    let (first, last) = hit_frames.fold((first, first), |(first, last), frame| (first.min(frame), last.max(frame)));
+    Some(if frame < first {
+        AttackPhase::Startup
+    } else if frame <= last {
+        AttackPhase::Active
+    } else {
+        AttackPhase::Recovery
+    })
+}
+
+// Put on animated bodies that should get sensors for the boxes on their
+// frames. The sensors are its children, so they go when it does.
+#[derive(Component, Default, Debug)]
+pub struct FrameColliders(Vec<Entity>);
+
+// One of the sensors from FrameColliders. It excludes its owner so it never
+// triggers on whoever it belongs to.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FrameCollider {
+    pub owner: Entity,
+    pub kind: FrameBoxKind
+}
+
+// Sensors are reused between frames, only spawned or despawned when the
+// number of boxes changes
+pub fn sync_frame_colliders(
+    mut commands: Commands,
+    mut owner_query: Query<(Entity, &mut FrameColliders, &SpriteSheetDefinition, &Row, &Col, &Position, Option<&TextureAtlasSprite>, Option<&AnimationSockets>)>,
+    mut collider_query: Query<(&mut Position, &mut AABB, &mut FrameCollider), Without<FrameColliders>>
+) {
+    for (owner, mut colliders, sheet_def, row, col, position, sprite, sockets) in owner_query.iter_mut() {
+        let facing = if sprite.is_some_and(|sprite| sprite.flip_x) { -1 } else { 1 };
+        let boxes: Vec<&FrameBox> = sheet_def.animation_definitions.get(row.0)
+            .map(|animation| animation.boxes.iter().filter(|frame_box| frame_box.frame == col.0).collect())
+            .unwrap_or_default();
+
+        for (i, frame_box) in boxes.iter().enumerate() {
+            let socket = frame_box.socket.as_deref().and_then(|name| sockets.and_then(|sockets| sockets.get(name))).unwrap_or(Vec2::ZERO);
+            let box_position = Position(position.0 + socket + Vec2::new((frame_box.offset.0 * facing) as f32, frame_box.offset.1 as f32));
+            let collider = AABB {
+                position: IVec2::ZERO,
+                half_size: IVec2::new(frame_box.half_size.0, frame_box.half_size.1)
+            };
+            let frame_collider = FrameCollider {
+                owner,
+                kind: frame_box.kind
+            };
+
+            match colliders.0.get(i).and_then(|entity| collider_query.get_mut(*entity).ok()) {
+                Some((mut existing_position, mut existing_collider, mut existing)) => {
+                    *existing_position = box_position;
+                    *existing_collider = collider;
+                    *existing = frame_collider;
+                },
+                // One more box than last frame, or its sensor was despawned by something else
+                None => {
+                    let entity = commands.spawn((
+                        BodyBundle {
+                            position: box_position,
+                            ..Default::default()
+                        },
+                        collider,
+                        Sensor,
+                        CollisionExclusions::with(owner),
+                        frame_collider
+                    )).set_parent(owner).id();
+                    if i < colliders.0.len() {
+                        colliders.0[i] = entity;
+                    } else {
+                        colliders.0.push(entity);
+                    }
+                }
+            }
+        }
+
+        if colliders.0.len() > boxes.len() {
+            for entity in colliders.0.drain(boxes.len()..) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn frame_box_color(kind: FrameBoxKind) -> Color {
+    match kind {
+        FrameBoxKind::Hitbox => Color::RED,
+        FrameBoxKind::Hurtbox => Color::LIME_GREEN
+    }
+}
+
+// Boxes on the current frame, on top of the physics debug colliders
+fn draw_frame_boxes(
+    mut gizmos: Gizmos,
+    collider_query: Query<(&Position, &AABB, &FrameCollider)>
+) {
+    for (position, aabb, collider) in collider_query.iter() {
+        gizmos.rect_2d(position.0 + aabb.position.as_vec2(), 0.0, (aabb.half_size * 2).as_vec2(), frame_box_color(collider.kind));
+    }
+}
+
+// Startup, active and recovery frames of every attack, with the frame being
+// played marked
+fn debug_attack_frames(
+    mut egui_ctx: EguiContexts,
+    owner_query: Query<(Entity, &SpriteSheetDefinition, &Row, &Col, Option<&Name>), With<FrameColliders>>
+) {
+    egui::Window::new("Attack Frames").show(egui_ctx.ctx_mut(), |ui| {
+        for (entity, sheet_def, row, col, name) in owner_query.iter() {
+            ui.label(name.map_or_else(|| format!("{:?}", entity), |name| name.to_string()));
+            egui::Grid::new(("attack_frames", entity)).show(ui, |ui| {
+                for (index, animation) in sheet_def.animation_definitions.iter().enumerate() {
+                    if attack_phase(&animation.boxes, 0).is_none() {
+                        continue;
+                    }
+                    ui.label(&animation.name);
+                    ui.horizontal(|ui| {
+                        for frame in 0..animation.number_of_frames {
+                            let (label, color) = match attack_phase(&animation.boxes, frame) {
+                                Some(AttackPhase::Startup) => ("S", egui::Color32::LIGHT_BLUE),
+                                Some(AttackPhase::Active) => ("A", egui::Color32::RED),
+                                _ => ("R", egui::Color32::GRAY),
+                            };
+                            let text = egui::RichText::new(label).monospace().color(color);
+                            let text = if index == row.0 && frame == col.0 { text.strong().underline() } else { text };
+                            ui.label(text);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        }
+    });
+}
+
+pub struct FrameBoxDebugPlugin;
+
+impl Plugin for FrameBoxDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (draw_frame_boxes, debug_attack_frames));
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+use super::{Col, Row, SpriteSheetDefinition};
+
+// A sheet drawn over an animated sprite, like a weapon or armor, spawned as a
+// child of it. It shows the same frame of the animation with the same name on
+// its own sheet, so an overlay only needs rows for what it draws over and is
+// hidden during the rest.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct AnimationLayer;
+
+// Transforms are relative to the sprite it goes over, a small z keeps it on top
+#[derive(Bundle, Default)]
+pub struct AnimationLayerBundle {
+    pub sprite_sheet: SpriteSheetBundle,
+    pub sprite_sheet_definitions: SpriteSheetDefinition,
+    pub layer: AnimationLayer
+}
+
+pub fn sync_animation_layers(
+    base_query: Query<(&SpriteSheetDefinition, &Row, &Col, &TextureAtlasSprite), Without<AnimationLayer>>,
+    mut layer_query: Query<(&Parent, &SpriteSheetDefinition, &mut TextureAtlasSprite, &mut Visibility), With<AnimationLayer>>
+) {
+    for (parent, layer_def, mut sprite, mut visibility) in layer_query.iter_mut() {
+        let (base_def, row, col, base_sprite) = match base_query.get(parent.get()) {
+            Ok(base) => base,
+            Err(_) => continue,
+        };
+
+        let layer_row = base_def.animation_definitions.get(row.0).and_then(|animation| layer_def.animation_row(&animation.name));
+        let layer_row = match layer_row {
+            Some(layer_row) => layer_row,
+            None => {
+                *visibility = Visibility::Hidden;
+                continue;
+            }
+        };
+
+        // Short overlay rows hold their last frame
+        let frames = layer_def.animation_definitions[layer_row].number_of_frames.max(1);
+        *visibility = Visibility::Inherited;
+        sprite.index = col.0.min(frames - 1) + layer_def.columns * layer_row;
+        sprite.flip_x = base_sprite.flip_x;
+        sprite.flip_y = base_sprite.flip_y;
+        sprite.anchor = base_sprite.anchor;
+    }
+}
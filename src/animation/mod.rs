@@ -1,51 +1,452 @@
-use bevy::{core::{Time, Timer}, prelude::{AppBuilder, Bundle, IntoSystem, Plugin, Query, Res, SpriteSheetBundle}, sprite::TextureAtlasSprite};
+use bevy::{asset::{Asset, AssetApp, AssetEvent, AssetId, AssetLoader, Assets, AsyncReadExt, Handle, LoadContext, io::Reader}, math::{Quat, Vec2, Vec3}, prelude::{App, Bundle, Component, Deref, DerefMut, DetectChanges, Entity, Event, EventReader, EventWriter, Image, IntoSystemConfigs, Plugin, Query, Ref, Res, ResMut, Resource, SpriteSheetBundle, Time, Timer, TimerMode, Transform, Update, Without}, reflect::TypePath, sprite::{Anchor, TextureAtlas, TextureAtlasSprite}, utils::{BoxedFuture, HashMap}};
 
-#[derive(Default, Clone)]
+use serde::Deserialize;
+
+use crate::physics::{activation::Inactive, body::RootMotion};
+
+use self::{frame_boxes::{FrameBox, sync_frame_colliders}, layers::sync_animation_layers, palette::PalettePlugin, sockets::{FrameSocket, update_sockets}};
+
+pub mod frame_boxes;
+pub mod layers;
+pub mod palette;
+pub mod sockets;
+
+#[derive(Default, Clone, Debug, Deserialize)]
 pub struct AnimationDefinition {
     pub name: String,
     pub number_of_frames: usize,
     pub frame_time: f32,
-    pub repeating: bool
+    pub repeating: bool,
+    // Named events sent as AnimationEvent when the animation reaches a frame,
+    // for footsteps, hitboxes and the like
+    #[serde(default)]
+    pub events: Vec<(usize, String)>,
+    // Hitboxes and hurtboxes on its frames, see FrameColliders
+    #[serde(default)]
+    pub boxes: Vec<FrameBox>,
+    // Pixels the art is drawn away from the body facing right, for frames
+    // drawn off centre in the sheet like a lunge. The collider stays put.
+    #[serde(default)]
+    pub offset: (f32, f32),
+    // Pixels the body moves when the animation reaches a frame, facing right,
+    // for rolls and lunges that should match the art. Only bodies with
+    // RootMotion get moved.
+    #[serde(default)]
+    pub root_motion: Vec<(usize, (f32, f32))>,
+    // Named attachment points, see AnimationSockets
+    #[serde(default)]
+    pub sockets: Vec<FrameSocket>
 }
 
-#[derive(Default)]
+impl AnimationDefinition {
+    // Fresh timer for playing this animation from the start
+    pub fn timer(&self) -> AnimationTimer {
+        let mode = if self.repeating { TimerMode::Repeating } else { TimerMode::Once };
+        AnimationTimer(Timer::from_seconds(self.frame_time, mode))
+    }
+}
+
+// Sent when an animation reaches a frame that has events on it
+#[derive(Event, Debug, Clone)]
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String
+}
+
+// Sent once a non repeating animation has shown its last frame for its full
+// frame time. `row` is the animation that finished.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub row: usize
+}
+
+// What happens between two animations instead of swapping straight over.
+// `Hold` keeps showing the frame the old one was on for that many seconds,
+// `Clip` plays another animation once first. Clips should not repeat, the
+// next animation starts when they finish.
+#[derive(Clone, Debug, Deserialize)]
+pub enum TransitionKind {
+    Hold(f32),
+    Clip(String)
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationTransition {
+    pub from: String,
+    pub to: String,
+    pub kind: TransitionKind
+}
+
+// Transitions by the names of the animations they go between, any pair not
+// in here swaps instantly
+#[derive(Default, Clone, Debug, Deserialize)]
+#[serde(transparent)]
+pub struct TransitionTable(pub Vec<AnimationTransition>);
+
+impl TransitionTable {
+    pub fn find(&self, from: &str, to: &str) -> Option<&TransitionKind> {
+        self.0.iter().find(|transition| transition.from == from && transition.to == to).map(|transition| &transition.kind)
+    }
+}
+
+// Also an asset, written as RON in `*.anim.ron` files so frame timings can be
+// changed without a recompile. Animations are the sheet's rows from the top.
+#[derive(Component, Asset, TypePath, Default, Clone, Debug)]
 pub struct SpriteSheetDefinition {
     pub animation_definitions: Vec<AnimationDefinition>,
     pub rows: usize,
-    pub columns: usize
-} 
+    pub columns: usize,
+    pub transitions: TransitionTable
+}
+
+impl SpriteSheetDefinition {
+    pub fn animation_row(&self, name: &str) -> Option<usize> {
+        self.animation_definitions.iter().position(|animation| animation.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+struct SpriteSheetDefinitionFile {
+    rows: usize,
+    columns: usize,
+    animations: Vec<AnimationDefinition>,
+    #[serde(default)]
+    transitions: TransitionTable
+}
 
 #[derive(Default)]
+pub struct SpriteSheetDefinitionLoader;
+
+impl AssetLoader for SpriteSheetDefinitionLoader {
+    type Asset = SpriteSheetDefinition;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let file = ron::de::from_bytes::<SpriteSheetDefinitionFile>(&bytes)?;
+            Ok(SpriteSheetDefinition {
+                animation_definitions: file.animations,
+                rows: file.rows,
+                columns: file.columns,
+                transitions: file.transitions
+            })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+#[derive(Component, Default)]
 pub struct Row(pub usize);
 
-#[derive(Default)]
+#[derive(Component, Default)]
 pub struct Col(pub usize);
 
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct AnimationTimer(pub Timer);
+
+// How fast this sprite animates compared to normal, 0 holds it on its current frame
+#[derive(Component, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct PlaybackSpeed(pub f32);
+
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        PlaybackSpeed(1.0)
+    }
+}
+
+// Speed for every animated sprite on top of their own PlaybackSpeed, for slow
+// motion. `paused` freezes them all, for the pause menu.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct AnimationTimeScale {
+    pub scale: f32,
+    pub paused: bool
+}
+
+impl Default for AnimationTimeScale {
+    fn default() -> Self {
+        AnimationTimeScale {
+            scale: 1.0,
+            paused: false
+        }
+    }
+}
+
+// Picks animations by name. Whatever is asked for last in a frame gets played
+// from its first frame before the sprite animates, going through the sheet's
+// transition to it if there is one.
+#[derive(Component, Default, Debug)]
+pub struct AnimationController {
+    current: Option<String>,
+    requested: Option<String>,
+    // Waiting on the transition to finish before playing
+    queued: Option<String>,
+    hold: Option<Timer>
+}
+
+impl AnimationController {
+    // Starts out asking for `name`
+    pub fn new(name: &str) -> Self {
+        AnimationController {
+            requested: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // Asking for the clip that is already playing, or already being
+    // transitioned to, carries on with it instead of restarting
+    pub fn play(&mut self, name: &str) {
+        let playing = self.queued.as_deref().or(self.current.as_deref());
+        if playing == Some(name) {
+            self.requested = None;
+        } else if self.requested.as_deref() != Some(name) {
+            self.requested = Some(name.to_string());
+        }
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    // For things that put frames up by hand, like rewind. `name` counts as
+    // playing and anything requested or queued is dropped.
+    pub fn set_current(&mut self, name: &str) {
+        if self.current.as_deref() != Some(name) {
+            self.current = Some(name.to_string());
+        }
+        self.requested = None;
+        self.queued = None;
+        self.hold = None;
+    }
+
+    // Holding the last animation's frame before the next one
+    pub fn is_holding(&self) -> bool {
+        self.hold.is_some()
+    }
+
+    pub fn in_transition(&self) -> bool {
+        self.queued.is_some()
+    }
+}
+
 #[derive(Bundle, Default)]
 pub struct AnimatedSpriteBundle {
-    #[bundle]
     pub sprite_sheet: SpriteSheetBundle,
     pub sprite_sheet_definitions: SpriteSheetDefinition,
     pub current_row: Row,
     pub current_col: Col,
-    pub animation_timer: Timer,
+    pub animation_timer: AnimationTimer,
+    pub controller: AnimationController,
+}
+
+// Cell size sprite sheet atlases were cut with, by atlas. Atlases in here get
+// cut again when their image changes on disk so frames can be edited live.
+#[derive(Resource, Default)]
+pub struct SpriteSheetGrids(pub HashMap<AssetId<TextureAtlas>, Vec2>);
+
+impl SpriteSheetGrids {
+    // Cuts `texture` into `columns` by `rows` cells and remembers the cell size for reloading
+    pub fn from_grid(&mut self, texture_atlases: &mut Assets<TextureAtlas>, texture: Handle<Image>, tile_size: Vec2, columns: usize, rows: usize) -> Handle<TextureAtlas> {
+        let handle = texture_atlases.add(TextureAtlas::from_grid(texture, tile_size, columns, rows, None, None));
+        self.0.insert(handle.id(), tile_size);
+        handle
+    }
+}
+
+// Recuts atlases whose image was modified and points sprites using them at the
+// same row and column in the new layout. Timers and the current frame carry on.
+fn reload_sprite_sheets(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    images: Res<Assets<Image>>,
+    grids: Res<SpriteSheetGrids>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprite_query: Query<(&Handle<TextureAtlas>, &mut TextureAtlasSprite, &mut SpriteSheetDefinition, &Row, &Col)>
+) {
+    for event in image_events.read() {
+        let image_id = match event {
+            AssetEvent::Modified { id } => *id,
+            _ => continue,
+        };
+
+        for (atlas_id, tile_size) in grids.0.iter() {
+            let (atlas, image) = match (texture_atlases.get_mut(*atlas_id), images.get(image_id)) {
+                (Some(atlas), Some(image)) if atlas.texture.id() == image_id => (atlas, image),
+                _ => continue,
+            };
+
+            let cells = (image.size_f32() / *tile_size).floor();
+            let (columns, rows) = (cells.x as usize, cells.y as usize);
+            *atlas = TextureAtlas::from_grid(atlas.texture.clone(), *tile_size, columns, rows, None, None);
+            if atlas.is_empty() {
+                continue;
+            }
+
+            for (handle, mut sprite, mut sheet_def, row, col) in sprite_query.iter_mut() {
+                if handle.id() != *atlas_id {
+                    continue;
+                }
+                sheet_def.columns = columns;
+                sheet_def.rows = rows;
+                sprite.index = (col.0 + columns * row.0).min(atlas.len() - 1);
+            }
+        }
+    }
+}
+
+// Plays `name` from its first frame if the sheet has it
+fn start_animation(controller: &mut AnimationController, name: String, sheet_def: &SpriteSheetDefinition, timer: &mut AnimationTimer, sprite: &mut TextureAtlasSprite, row: &mut Row, col: &mut Col) {
+    let index = match sheet_def.animation_row(&name) {
+        Some(index) => index,
+        None => return,
+    };
+
+    *timer = sheet_def.animation_definitions[index].timer();
+    row.0 = index;
+    col.0 = 0;
+    sprite.index = sheet_def.columns * index;
+    controller.current = Some(name);
+}
+
+// Sheets without the requested animation keep playing whatever they were
+pub fn play_requested_animations(
+    time: Res<Time>,
+    mut query: Query<(&mut AnimationController, &SpriteSheetDefinition, &mut AnimationTimer, &mut TextureAtlasSprite, &mut Row, &mut Col)>
+) {
+    for (mut controller, sheet_def, mut timer, mut sprite, mut row, mut col) in query.iter_mut() {
+        let controller = controller.as_mut();
+        if let Some(name) = controller.requested.take() {
+            // Asking for something else part way through a transition drops it
+            controller.queued = None;
+            controller.hold = None;
+
+            if sheet_def.animation_row(&name).is_none() {
+                continue;
+            }
+            let transition = controller.current.as_deref().and_then(|current| sheet_def.transitions.find(current, &name)).cloned();
+            match transition {
+                Some(TransitionKind::Hold(seconds)) if seconds > 0.0 => {
+                    controller.hold = Some(Timer::from_seconds(seconds, TimerMode::Once));
+                    controller.queued = Some(name);
+                },
+                Some(TransitionKind::Clip(clip)) if sheet_def.animation_row(&clip).is_some() => {
+                    start_animation(controller, clip, sheet_def, &mut timer, &mut sprite, &mut row, &mut col);
+                    controller.queued = Some(name);
+                },
+                _ => {
+                    start_animation(controller, name, sheet_def, &mut timer, &mut sprite, &mut row, &mut col);
+                }
+            }
+            continue;
+        }
+
+        let transition_done = match controller.hold.as_mut() {
+            Some(hold) => hold.tick(time.delta()).finished(),
+            // The clip has shown its last frame for its full frame time
+            None => timer.finished() && sheet_def.animation_definitions.get(row.0).is_none_or(|animation| col.0 + 1 >= animation.number_of_frames)
+        };
+        if transition_done {
+            if let Some(name) = controller.queued.take() {
+                controller.hold = None;
+                start_animation(controller, name, sheet_def, &mut timer, &mut sprite, &mut row, &mut col);
+            }
+        }
+    }
 }
 
 fn animate_sprite_system(
     time: Res<Time>,
-    mut query: Query<(&mut Timer, &mut TextureAtlasSprite, &SpriteSheetDefinition, &Row, &mut Col)>,
+    time_scale: Res<AnimationTimeScale>,
+    mut animation_events: EventWriter<AnimationEvent>,
+    mut finished_events: EventWriter<AnimationFinished>,
+    mut query: Query<(Entity, &mut AnimationTimer, &mut TextureAtlasSprite, &SpriteSheetDefinition, Ref<Row>, &mut Col, Option<&PlaybackSpeed>, Option<&AnimationController>, Option<&mut RootMotion>, Option<&Transform>), Without<Inactive>>,
 ) {
-    for (mut timer, mut sprite, sheet_def, row, mut col) in query.iter_mut() {
-        timer.tick(time.delta());
-        if timer.finished() {
-            col.0 += 1;
-            if row.0 < sheet_def.rows {
-                let columns = sheet_def.animation_definitions[row.0].number_of_frames;
-                if col.0 >= columns{
-                    col.0 = 0;
+    let global_scale = if time_scale.paused { 0.0 } else { time_scale.scale };
+    for (entity, mut timer, mut sprite, sheet_def, row, mut col, speed, controller, root_motion, transform) in query.iter_mut() {
+        // Switching animations counts as reaching whatever frame it starts on
+        let mut reached = if row.is_changed() { Some(col.0) } else { None };
+        if controller.is_some_and(AnimationController::is_holding) {
+            continue;
+        }
+
+        let scale = (global_scale * speed.map_or(1.0, |speed| speed.0)).max(0.0);
+        timer.tick(time.delta().mul_f32(scale));
+        if timer.just_finished() {
+            let animation = sheet_def.animation_definitions.get(row.0);
+            let last_frame = animation.is_some_and(|animation| col.0 + 1 >= animation.number_of_frames);
+            let repeating = animation.is_none_or(|animation| animation.repeating);
+
+            if !last_frame {
+                col.0 += 1;
+                // Non repeating animations run on a one shot timer, wind it up for the next frame
+                if !repeating {
+                    timer.reset();
                 }
+            } else if repeating {
+                col.0 = 0;
+            } else {
+                // Stays on the last frame, the timer is left finished so this only happens once
+                finished_events.send(AnimationFinished {
+                    entity,
+                    row: row.0
+                });
+                continue;
+            }
+            sprite.index = col.0 + sheet_def.columns * row.0;
+            reached = Some(col.0);
+        }
+
+        let animation = match (reached, sheet_def.animation_definitions.get(row.0)) {
+            (Some(_), Some(animation)) => animation,
+            _ => continue,
+        };
+        for (_, name) in animation.events.iter().filter(|(frame, _)| Some(*frame) == reached) {
+            animation_events.send(AnimationEvent {
+                entity,
+                name: name.clone()
+            });
+        }
+
+        // Mirrored with the sprite and turned with it onto walls and ceilings
+        if let Some(mut root_motion) = root_motion {
+            let facing = if sprite.flip_x { -1.0 } else { 1.0 };
+            let rotation = transform.map_or(Quat::IDENTITY, |transform| transform.rotation);
+            for (_, (x, y)) in animation.root_motion.iter().filter(|(frame, _)| Some(*frame) == reached) {
+                root_motion.0 += (rotation * Vec3::new(x * facing, *y, 0.0)).truncate();
             }
-            sprite.index = (col.0 + sheet_def.columns * row.0) as u32;
+        }
+    }
+}
+
+// Shifts the sprite with its anchor, the transform follows the body position.
+// Sheets are drawn centred apart from these offsets.
+fn apply_animation_offsets(
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut query: Query<(&SpriteSheetDefinition, &Row, &Handle<TextureAtlas>, &mut TextureAtlasSprite)>
+) {
+    for (sheet_def, row, atlas, mut sprite) in query.iter_mut() {
+        let offset = sheet_def.animation_definitions.get(row.0).map_or(Vec2::ZERO, |animation| Vec2::new(animation.offset.0, animation.offset.1));
+        let size = sprite.custom_size.or_else(|| texture_atlases.get(atlas).and_then(|atlas| atlas.textures.get(sprite.index)).map(|rect| rect.size()));
+        let anchor = match size {
+            Some(size) if offset != Vec2::ZERO && size.cmpgt(Vec2::ZERO).all() => {
+                // Flipping mirrors the art but not the anchor
+                let facing = if sprite.flip_x { -1.0 } else { 1.0 };
+                Anchor::Custom(-Vec2::new(offset.x * facing, offset.y) / size)
+            },
+            _ => Anchor::Center
+        };
+        if sprite.anchor.as_vec() != anchor.as_vec() {
+            sprite.anchor = anchor;
         }
     }
 }
@@ -53,7 +454,14 @@ fn animate_sprite_system(
 pub struct AnimationPlugin;
 
 impl Plugin for AnimationPlugin {
-    fn build(&self, app: &mut AppBuilder) {
-        app.add_system(animate_sprite_system.system());
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PalettePlugin)
+            .init_asset::<SpriteSheetDefinition>()
+            .init_asset_loader::<SpriteSheetDefinitionLoader>()
+            .init_resource::<SpriteSheetGrids>()
+            .init_resource::<AnimationTimeScale>()
+            .add_event::<AnimationEvent>()
+            .add_event::<AnimationFinished>()
+            .add_systems(Update, (reload_sprite_sheets, play_requested_animations, animate_sprite_system, update_sockets, sync_frame_colliders, apply_animation_offsets, sync_animation_layers).chain());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,155 @@
+use bevy::{
+    prelude::*,
+    reflect::TypePath,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+    transform::TransformSystem,
+};
+
+// Recolors an animated sprite from a palette instead of its own texture, so
+// skins and enemy variants can share one spritesheet. `index_map` is laid out
+// exactly like the spritesheet with every pixel's red channel holding a column
+// of `palettes`, and each row of `palettes` is one set of colors.
+#[derive(Component, Debug, Clone)]
+pub struct PaletteSwap {
+    pub index_map: Handle<Image>,
+    pub palettes: Handle<Image>
+}
+
+// Which row of the palettes texture to draw with, 0 being the first
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub usize);
+
+// See assets/shaders/palette_swap.wgsl
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct PaletteMaterial {
+    #[uniform(0)]
+    pub frame: Vec4,
+    #[uniform(0)]
+    pub row: u32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub index_map: Handle<Image>,
+    #[texture(3)]
+    pub palettes: Handle<Image>
+}
+
+impl Material2d for PaletteMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/palette_swap.wgsl".into()
+    }
+}
+
+// Child of the palette swapped sprite, drawn in its place
+#[derive(Component)]
+struct PaletteOverlay;
+
+#[derive(Resource)]
+struct PaletteMesh(Mesh2dHandle);
+
+fn setup_palette_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>
+) {
+    // Unit sized, overlays get scaled up to the frame size
+    commands.insert_resource(PaletteMesh(meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE))).into()));
+}
+
+fn spawn_palette_overlays(
+    mut commands: Commands,
+    mesh: Res<PaletteMesh>,
+    mut materials: ResMut<Assets<PaletteMaterial>>,
+    mut sprite_query: Query<(Entity, &PaletteSwap, &mut TextureAtlasSprite), Added<PaletteSwap>>
+) {
+    for (entity, palette_swap, mut sprite) in sprite_query.iter_mut() {
+        // The sprite keeps animating and flipping as normal, it just isn't seen
+        sprite.color.set_a(0.0);
+
+        let overlay = commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.0.clone(),
+                material: materials.add(PaletteMaterial {
+                    frame: Vec4::ZERO,
+                    row: 0,
+                    index_map: palette_swap.index_map.clone(),
+                    palettes: palette_swap.palettes.clone()
+                }),
+                ..Default::default()
+            },
+            PaletteOverlay
+        )).id();
+        commands.entity(entity).add_child(overlay);
+    }
+}
+
+fn remove_palette_overlays(
+    mut commands: Commands,
+    mut removed: RemovedComponents<PaletteSwap>,
+    mut sprite_query: Query<(&mut TextureAtlasSprite, &Children)>,
+    overlay_query: Query<(), With<PaletteOverlay>>
+) {
+    for entity in removed.read() {
+        if let Ok((mut sprite, children)) = sprite_query.get_mut(entity) {
+            sprite.color.set_a(1.0);
+            for child in children.iter().copied().filter(|child| overlay_query.contains(*child)) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn update_palette_overlays(
+    images: Res<Assets<Image>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut materials: ResMut<Assets<PaletteMaterial>>,
+    sprite_query: Query<(&TextureAtlasSprite, &Handle<TextureAtlas>, &PaletteSwap, Option<&Palette>)>,
+    mut overlay_query: Query<(&Parent, &Handle<PaletteMaterial>, &mut Transform, &mut Visibility), With<PaletteOverlay>>
+) {
+    for (parent, material_handle, mut transform, mut visibility) in overlay_query.iter_mut() {
+        let (sprite, atlas_handle, palette_swap, palette) = match sprite_query.get(parent.get()) {
+            Ok(sprite) => sprite,
+            Err(_) => continue,
+        };
+
+        let frame = texture_atlases.get(atlas_handle).and_then(|atlas| atlas.textures.get(sprite.index).map(|rect| (atlas.size, *rect)));
+        let palettes = images.get(&palette_swap.palettes);
+        let ((atlas_size, rect), palettes, material) = match (frame, palettes, materials.get_mut(material_handle)) {
+            (Some(frame), Some(palettes), Some(material)) => (frame, palettes, material),
+            _ => {
+                *visibility = Visibility::Hidden;
+                continue;
+            }
+        };
+        *visibility = Visibility::Inherited;
+
+        // Just in front of the invisible sprite, the parent's scale still
+        // applies. Shifted with the sprite's anchor like the sprite itself.
+        transform.translation = (-sprite.anchor.as_vec() * rect.size()).extend(0.001);
+        transform.scale = rect.size().extend(1.0);
+
+        let (mut min, mut max) = (rect.min / atlas_size, rect.max / atlas_size);
+        if sprite.flip_x {
+            std::mem::swap(&mut min.x, &mut max.x);
+        }
+        if sprite.flip_y {
+            std::mem::swap(&mut min.y, &mut max.y);
+        }
+
+        // Out of range palettes fall back to the last one rather than reading off the texture
+        let rows = palettes.size().y.max(1);
+        let row = (palette.copied().unwrap_or_default().0 as u32).min(rows - 1);
+
+        material.frame = Vec4::new(min.x, min.y, max.x, max.y);
+        material.row = row;
+    }
+}
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<PaletteMaterial>::default())
+            .add_systems(Startup, setup_palette_mesh)
+            .add_systems(PostUpdate, (spawn_palette_overlays, remove_palette_overlays, update_palette_overlays).chain().before(TransformSystem::TransformPropagate));
+    }
+}
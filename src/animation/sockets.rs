@@ -0,0 +1,47 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use super::{Col, Row, SpriteSheetDefinition};
+
+// Where a named point like a hand or muzzle is on one frame of an animation,
+// in pixels from the body position with the sprite facing right. A socket
+// stays where it was last placed until a later frame moves it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrameSocket {
+    pub frame: usize,
+    pub name: String,
+    pub offset: (f32, f32)
+}
+
+// Put on animated bodies to have their sockets worked out as they animate.
+// Offsets are from the body position, already mirrored with the sprite and
+// turned with it onto walls and ceilings.
+#[derive(Component, Default, Debug)]
+pub struct AnimationSockets(pub HashMap<String, Vec2>);
+
+impl AnimationSockets {
+    pub fn get(&self, name: &str) -> Option<Vec2> {
+        self.0.get(name).copied()
+    }
+}
+
+pub fn update_sockets(
+    mut query: Query<(&SpriteSheetDefinition, &Row, &Col, &TextureAtlasSprite, Option<&Transform>, &mut AnimationSockets)>
+) {
+    for (sheet_def, row, col, sprite, transform, mut sockets) in query.iter_mut() {
+        let facing = if sprite.flip_x { -1.0 } else { 1.0 };
+        let rotation = transform.map_or(Quat::IDENTITY, |transform| transform.rotation);
+        let mut placed: HashMap<String, Vec2> = HashMap::new();
+        if let Some(animation) = sheet_def.animation_definitions.get(row.0) {
+            // Later frames win, so each socket ends up wherever it was last placed
+            for socket in animation.sockets.iter().filter(|socket| socket.frame <= col.0) {
+                let (x, y) = socket.offset;
+                placed.insert(socket.name.clone(), (rotation * Vec3::new(x * facing, y, 0.0)).truncate());
+            }
+        }
+
+        if sockets.0 != placed {
+            sockets.0 = placed;
+        }
+    }
+}
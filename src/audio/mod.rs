@@ -0,0 +1,277 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{EguiContexts, egui};
+
+use crate::{control::{ControlLockReason, PlayerControlLock}, explosion::ExplosionHit};
+
+pub mod zones;
+
+use self::zones::update_audio_zones;
+
+// Every sound plays on one of these, the settings volume of its bus and
+// whatever ducking is on it scale it on top of master
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+    // Loops from audio zones, layered under the music
+    Ambient
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 3] = [AudioBus::Music, AudioBus::Sfx, AudioBus::Ambient];
+}
+
+// Volumes from the settings, 0 to 1
+#[derive(Resource, Debug, Clone)]
+pub struct AudioVolumes {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ambient: f32
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        AudioVolumes {
+            master: 1.0,
+            music: 0.8,
+            sfx: 1.0,
+            ambient: 0.6
+        }
+    }
+}
+
+impl AudioVolumes {
+    pub fn bus(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ambient => self.ambient
+        }
+    }
+
+    pub fn bus_mut(&mut self, bus: AudioBus) -> &mut f32 {
+        match bus {
+            AudioBus::Music => &mut self.music,
+            AudioBus::Sfx => &mut self.sfx,
+            AudioBus::Ambient => &mut self.ambient
+        }
+    }
+}
+
+// Takes `bus` down to `volume` of its level. `hold` is how many seconds it
+// stays down after whatever set it off, dialog keeps it down for as long as
+// the dialog lock is held on top of that.
+#[derive(Debug, Clone, Copy)]
+pub struct DuckRule {
+    pub bus: AudioBus,
+    pub volume: f32,
+    pub hold: f32
+}
+
+// When the mixer ducks. Ducks fade in and out over `fade` seconds.
+#[derive(Resource, Debug, Clone)]
+pub struct Ducking {
+    pub dialog: DuckRule,
+    pub big_hit: DuckRule,
+    // Explosion hits doing at least this much damage count as big
+    pub big_hit_damage: u32,
+    pub fade: f32,
+    dialog_remaining: f32,
+    big_hit_remaining: f32
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Ducking {
+            dialog: DuckRule { bus: AudioBus::Music, volume: 0.35, hold: 0.25 },
+            big_hit: DuckRule { bus: AudioBus::Music, volume: 0.5, hold: 0.6 },
+            big_hit_damage: 2,
+            fade: 0.2,
+            dialog_remaining: 0.0,
+            big_hit_remaining: 0.0
+        }
+    }
+}
+
+impl Ducking {
+    pub fn big_hit(&mut self) {
+        self.big_hit_remaining = self.big_hit.hold;
+    }
+
+    // What each bus is ducked to right now, 1 when it isn't
+    fn targets(&self) -> HashMap<AudioBus, f32> {
+        let mut targets: HashMap<AudioBus, f32> = AudioBus::ALL.iter().map(|bus| (*bus, 1.0)).collect();
+        for (rule, remaining) in [(self.dialog, self.dialog_remaining), (self.big_hit, self.big_hit_remaining)] {
+            if remaining > 0.0 {
+                let target = targets.entry(rule.bus).or_insert(1.0);
+                *target = target.min(rule.volume);
+            }
+        }
+        targets
+    }
+}
+
+// What each bus comes out at this frame, volumes and ducking together
+#[derive(Resource, Debug, Clone, Default)]
+pub struct AudioMixer {
+    ducked: HashMap<AudioBus, f32>,
+    volumes: AudioVolumes
+}
+
+impl AudioMixer {
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        self.volumes.master * self.volumes.bus(bus) * self.ducked.get(&bus).copied().unwrap_or(1.0)
+    }
+}
+
+// Send to play a sound, every sound goes through here so it lands on a bus.
+// `volume` is on top of the bus.
+#[derive(Event, Debug, Clone)]
+pub struct PlaySound {
+    pub path: String,
+    pub bus: AudioBus,
+    pub volume: f32
+}
+
+impl PlaySound {
+    pub fn sfx(path: impl Into<String>) -> Self {
+        PlaySound {
+            path: path.into(),
+            bus: AudioBus::Sfx,
+            volume: 1.0
+        }
+    }
+}
+
+// On anything playing audio, its sink follows the mixer while it plays
+#[derive(Component, Debug, Clone, Copy)]
+pub struct MixedSound {
+    pub bus: AudioBus,
+    pub volume: f32
+}
+
+fn duck_for_big_hits(
+    mut ducking: ResMut<Ducking>,
+    mut hit_events: EventReader<ExplosionHit>
+) {
+    let big_hit_damage = ducking.big_hit_damage;
+    if hit_events.read().any(|hit| hit.damage >= big_hit_damage) {
+        ducking.big_hit();
+    }
+}
+
+fn update_mixer(
+    time: Res<Time<Real>>,
+    volumes: Res<AudioVolumes>,
+    control_lock: Option<Res<PlayerControlLock>>,
+    mut ducking: ResMut<Ducking>,
+    mut mixer: ResMut<AudioMixer>
+) {
+    let delta = time.delta_seconds();
+    if control_lock.is_some_and(|lock| lock.is_locked_by(ControlLockReason::Dialog)) {
+        ducking.dialog_remaining = ducking.dialog.hold;
+    } else {
+        ducking.dialog_remaining -= delta;
+    }
+    ducking.big_hit_remaining -= delta;
+
+    // Real time so ducks still let go while the game is paused
+    let step = if ducking.fade > 0.0 { delta / ducking.fade } else { 1.0 };
+    let targets = ducking.targets();
+    let mixer = mixer.as_mut();
+    for bus in AudioBus::ALL {
+        let target = targets[&bus];
+        let ducked = mixer.ducked.entry(bus).or_insert(1.0);
+        *ducked += (target - *ducked).clamp(-step, step);
+    }
+    if mixer.volumes.master != volumes.master || AudioBus::ALL.iter().any(|bus| mixer.volumes.bus(*bus) != volumes.bus(*bus)) {
+        mixer.volumes = volumes.clone();
+    }
+}
+
+#[cfg(feature = "audio")]
+fn play_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mixer: Res<AudioMixer>,
+    mut sound_events: EventReader<PlaySound>
+) {
+    use bevy::audio::Volume;
+
+    for sound in sound_events.read() {
+        commands.spawn((
+            AudioBundle {
+                source: asset_server.load(sound.path.clone()),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(mixer.volume(sound.bus) * sound.volume))
+            },
+            MixedSound {
+                bus: sound.bus,
+                volume: sound.volume
+            }
+        ));
+    }
+}
+
+// Without the audio feature sounds are only logged
+#[cfg(not(feature = "audio"))]
+fn play_sounds(mut sound_events: EventReader<PlaySound>) {
+    for sound in sound_events.read() {
+        trace!("Not playing {} on {:?}, audio is off", sound.path, sound.bus);
+    }
+}
+
+#[cfg(feature = "audio")]
+fn apply_mixer(
+    mixer: Res<AudioMixer>,
+    sink_query: Query<(&MixedSound, &AudioSink)>
+) {
+    use bevy::audio::AudioSinkPlayback;
+
+    for (sound, sink) in sink_query.iter() {
+        let volume = mixer.volume(sound.bus) * sound.volume;
+        if sink.volume() != volume {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+fn debug_audio(
+    mut egui_ctx: EguiContexts,
+    mut volumes: ResMut<AudioVolumes>,
+    mixer: Res<AudioMixer>
+) {
+    egui::Window::new("Audio").show(egui_ctx.ctx_mut(), |ui| {
+        ui.add(egui::Slider::new(&mut volumes.master, 0.0..=1.0).text("Master"));
+        for bus in AudioBus::ALL {
+            ui.add(egui::Slider::new(volumes.bus_mut(bus), 0.0..=1.0).text(format!("{:?} ({:.2})", bus, mixer.volume(bus))));
+        }
+        if cfg!(not(feature = "audio")) {
+            ui.label("Built without the audio feature, nothing plays");
+        }
+    });
+}
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioVolumes>()
+            .init_resource::<Ducking>()
+            .init_resource::<AudioMixer>()
+            .add_event::<PlaySound>()
+            .add_event::<ExplosionHit>()
+            .add_systems(Update, (duck_for_big_hits, update_mixer, update_audio_zones, play_sounds).chain());
+
+        #[cfg(feature = "audio")]
+        app.add_systems(Update, (zones::start_zone_loops, apply_mixer).after(play_sounds));
+    }
+}
+
+pub struct AudioDebugPlugin;
+
+impl Plugin for AudioDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, debug_audio);
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::player::PlayerInput;
+
+use super::{AudioBus, MixedSound};
+
+// Spawned for LDtk "AudioZone" entities. `sound` loops on the ambient bus at
+// `volume` while the player is inside `bounds`, fading out over the last
+// `falloff` pixels towards the edges. Going from one zone into another
+// crossfades between their loops over `crossfade` seconds.
+#[derive(Component, Debug, Clone)]
+pub struct AudioZone {
+    pub sound: String,
+    pub volume: f32,
+    pub falloff: f32,
+    pub crossfade: f32,
+    pub bounds: Rect
+}
+
+impl AudioZone {
+    // 1 well inside the zone, down to 0 at its edges and outside of it
+    pub fn weight(&self, point: Vec2) -> f32 {
+        if !self.bounds.contains(point) {
+            return 0.0;
+        }
+        let to_edge = (point - self.bounds.min).min(self.bounds.max - point).min_element();
+        if self.falloff > 0.0 { (to_edge / self.falloff).min(1.0) } else { 1.0 }
+    }
+}
+
+// How loud a zone's loop is right now, before the zone and bus volumes
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct AudioZoneLevel(pub f32);
+
+pub fn update_audio_zones(
+    time: Res<Time>,
+    player_query: Query<&Transform, With<PlayerInput>>,
+    mut zone_query: Query<(&AudioZone, &mut AudioZoneLevel, &mut MixedSound)>
+) {
+    let player = player_query.iter().next().map(|transform| transform.translation.truncate());
+    for (zone, mut level, mut sound) in zone_query.iter_mut() {
+        let target = player.map_or(0.0, |player| zone.weight(player));
+        let step = if zone.crossfade > 0.0 { time.delta_seconds() / zone.crossfade } else { 1.0 };
+        let current = level.0 + (target - level.0).clamp(-step, step);
+        if current != level.0 {
+            level.0 = current;
+            sound.volume = zone.volume * current;
+        }
+    }
+}
+
+// Loops start silent and stay playing, update_audio_zones fades them
+#[cfg(feature = "audio")]
+pub fn start_zone_loops(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    zone_query: Query<(Entity, &AudioZone), Added<AudioZone>>
+) {
+    use bevy::audio::Volume;
+
+    for (entity, zone) in zone_query.iter() {
+        commands.entity(entity).insert(AudioBundle {
+            source: asset_server.load(zone.sound.clone()),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.0))
+        });
+    }
+}
+
+pub fn spawn_audio_zone(commands: &mut Commands, zone: AudioZone) {
+    commands.spawn((
+        zone,
+        AudioZoneLevel::default(),
+        MixedSound {
+            bus: AudioBus::Ambient,
+            volume: 0.0
+        }
+    ));
+}
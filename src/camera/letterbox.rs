@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::control::PlayerControlLock;
+
+#[derive(Resource)]
+pub struct LetterboxSettings {
+    // Height of each bar as a fraction of the screen height when fully shown
+    pub bar_height: f32,
+    // How much of the full bar height gets covered per second
+    pub speed: f32,
+    pub color: Color
+}
+
+impl Default for LetterboxSettings {
+    fn default() -> Self {
+        LetterboxSettings {
+            bar_height: 0.12,
+            speed: 3.0,
+            color: Color::BLACK
+        }
+    }
+}
+
+// 0.0 is fully hidden, 1.0 is fully shown
+#[derive(Resource, Default)]
+pub struct Letterbox {
+    pub amount: f32
+}
+
+#[derive(Component)]
+pub struct LetterboxBar;
+
+pub fn setup_letterbox(
+    mut commands: Commands,
+    settings: Res<LetterboxSettings>
+) {
+    for top in [true, false] {
+        let (top, bottom) = if top {
+            (Val::Px(0.0), Val::Auto)
+        } else {
+            (Val::Auto, Val::Px(0.0))
+        };
+
+        commands.spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top,
+                bottom,
+                left: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                height: Val::Percent(0.0),
+                ..Default::default()
+            },
+            background_color: settings.color.into(),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        })
+        .insert(LetterboxBar);
+    }
+}
+
+pub fn animate_letterbox(
+    time: Res<Time>,
+    control_lock: Res<PlayerControlLock>,
+    settings: Res<LetterboxSettings>,
+    mut letterbox: ResMut<Letterbox>,
+    mut bar_query: Query<(&mut Style, &mut Visibility), With<LetterboxBar>>
+) {
+    let target = if control_lock.is_cinematic() { 1.0 } else { 0.0 };
+    let step = settings.speed * time.delta_seconds();
+    letterbox.amount = if letterbox.amount < target {
+        (letterbox.amount + step).min(target)
+    } else {
+        (letterbox.amount - step).max(target)
+    };
+
+    for (mut style, mut visibility) in bar_query.iter_mut() {
+        style.height = Val::Percent(letterbox.amount * settings.bar_height * 100.0);
+        *visibility = if letterbox.amount > 0.0 { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
@@ -1,14 +1,52 @@
-use bevy::{math::{Vec2, Vec3, Vec3Swizzles}, prelude::{IntoSystem, Plugin, Query, Transform, With, Without}};
+use bevy::{math::{Vec2, Vec3, Vec3Swizzles}, prelude::{App, Camera, Component, GlobalTransform, IntoSystemConfigs, Plugin, Query, Res, Resource, Startup, Transform, Update, With, Without}};
 use fastapprox::fast::ln;
 
-use self::parallax::{move_parallax, parallax_start};
+use crate::control::PlayerControlLock;
 
+use self::{letterbox::{Letterbox, LetterboxSettings, animate_letterbox, setup_letterbox}, parallax::{move_parallax, parallax_start}, pixel_perfect::GameView, shake::{CameraShake, settle_camera_shake, shake_camera}};
+
+pub mod letterbox;
 pub mod parallax;
+pub mod pixel_perfect;
+pub mod shake;
 
+#[derive(Component)]
 pub struct MainCamera;
+#[derive(Component)]
 pub struct CameraTarget;
 
+// When set the camera heads to this point instead of following the targets
+#[derive(Resource, Default, Debug)]
+pub struct CameraFocus(pub Option<Vec2>);
+
+// Size in logical pixels of the viewport the camera renders to. For the main
+// camera that is the game image, see GameView for where it is in the window.
+pub fn screen_size(camera: &Camera) -> Option<Vec2> {
+    camera.logical_viewport_size()
+}
+
+// Screen position in logical pixels, origin in the top left of the viewport.
+// Positions outside of the view are not clamped so callers can tell where
+// off-screen things are relative to the view.
+pub fn world_to_screen(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    world_position: Vec3,
+) -> Option<Vec2> {
+    camera.world_to_viewport(camera_transform, world_position)
+}
+
+// Inverse of world_to_screen
+pub fn screen_to_world(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    screen_position: Vec2,
+) -> Option<Vec2> {
+    camera.viewport_to_world_2d(camera_transform, screen_position)
+}
+
 fn move_camera(
+    camera_focus: Res<CameraFocus>,
     target_query: Query<&Transform, With<CameraTarget>>,
     mut camera_query: Query<&mut Transform, (With<MainCamera>, Without<CameraTarget>)>,
 ) {
@@ -20,6 +58,10 @@ fn move_camera(
     }
     centorid /= n;
 
+    if let Some(focus) = camera_focus.0 {
+        centorid = focus;
+    }
+
     for mut transform in camera_query.iter_mut() {
         let distance = centorid.distance(transform.translation.xy());
         let z = transform.translation.z;
@@ -30,7 +72,7 @@ fn move_camera(
         // let c = -0.8f32;
         // let mut t = (k / (1.0f32 + exp(a+(distance * b)))) + c;
 
-        let g = 0.0004f32;
+        let g = 0.0016f32;
         let l = -1.0f32;
         let t = ln(g * distance - l);
 
@@ -48,9 +90,16 @@ fn move_camera(
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
-    fn build(&self, app: &mut bevy::prelude::AppBuilder) {
-        app.add_startup_system(parallax_start.system());
-        app.add_system(move_parallax.system());
-        app.add_system(move_camera.system());
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerControlLock>()
+            .init_resource::<CameraFocus>()
+            .init_resource::<GameView>()
+            .init_resource::<LetterboxSettings>()
+            .init_resource::<Letterbox>()
+            .init_resource::<CameraShake>();
+        app.add_systems(Startup, setup_letterbox);
+        app.add_systems(Startup, parallax_start);
+        app.add_systems(Update, (move_parallax, (settle_camera_shake, move_camera, shake_camera).chain()));
+        app.add_systems(Update, animate_letterbox);
     }
-}
\ No newline at end of file
+}
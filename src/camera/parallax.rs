@@ -1,9 +1,9 @@
 use bevy::{math::Vec3Swizzles, prelude::*, render::camera::OrthographicProjection};
-use bevy_egui::{EguiContext, egui};
+use bevy_egui::{EguiContexts, egui};
 
 use super::{CameraTarget, MainCamera};
 
-#[derive(Default)]
+#[derive(Component, Default)]
 pub struct ParallaxLayer {
     pub start_position: Vec3,
     pub parallax_factor: f32
@@ -18,53 +18,57 @@ pub fn parallax_start (
 }
 
 pub fn move_parallax(
-    mut egui_ctx: ResMut<EguiContext>,
-    mut queries: QuerySet<(
+    mut egui_ctx: EguiContexts,
+    mut queries: ParamSet<(
         Query<&Transform, With<CameraTarget>>,
         Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
-        Query<(&mut Transform, &mut ParallaxLayer)>
+        Query<(&mut Transform, &mut ParallaxLayer, &InheritedVisibility)>
     )>
 ) {
-    if let Ok(target_result) = queries.q0().single() {
-        if let Ok(camera_result) = queries.q1().single() {
-            let target_position = target_result.translation;
-            let camera_position = camera_result.0.translation;
-            let near = camera_result.1.near;
-            let far = camera_result.1.far;
-            bevy_egui::egui::Window::new("Background").scroll(true).show(egui_ctx.ctx(), |ui| {
-                ui.label(format!("Near: {}", near));
-                ui.label(format!("Far: {}", far));
-                let mut i = 0;
-                egui::Grid::new(format!("BG {}", i)).show(ui, |ui|{
-                    for (mut layer_transform, mut layer) in queries.q2_mut().iter_mut() {
-                        let travel = camera_position.xy() - layer.start_position.xy();
-                        let distance_from_subject = layer_transform.translation.z - target_position.z;
-                        let clipping_plane = if distance_from_subject <= 0.0 {
-                            camera_position.z + far
-                        } else {
-                            camera_position.z + near
-                        };
+    let target_position = match queries.p0().get_single() {
+        Ok(target_result) => target_result.translation,
+        Err(_) => return,
+    };
 
-                        let parallax_factor = distance_from_subject.abs() / clipping_plane;
-                
-                        ui.label(format!("Parallax Factor: {}", parallax_factor));
-                        // ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer.parallax_factor));
-                        ui.end_row();
-                        ui.label("Position: ");
-                        ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.x));
-                        ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.y));
-                        ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.z));
-                        ui.end_row();
+    let (camera_position, near, far) = match queries.p1().get_single() {
+        Ok((camera_transform, projection)) => (camera_transform.translation, projection.near, projection.far),
+        Err(_) => return,
+    };
 
-                        // let new_pos = layer.start_position.xy() + travel * parallax_factor;
-                        let new_pos = Vec2::new(layer.start_position.x + travel.x * parallax_factor, layer.start_position.y);
-                        layer_transform.translation.x = new_pos.x;
-                        // layer_transform.translation.y = new_pos.y;
-                        i += 1;
-                    }
-                });
-            });
+    egui::Window::new("Background").vscroll(true).show(egui_ctx.ctx_mut(), |ui| {
+        ui.label(format!("Near: {}", near));
+        ui.label(format!("Far: {}", far));
+        egui::Grid::new("BG").show(ui, |ui|{
+            for (mut layer_transform, layer, visibility) in queries.p2().iter_mut() {
+                // Layers that were hidden on purpose don't need to follow the camera
+                if !visibility.get() {
+                    continue;
+                }
 
-        }
-    }
-}
\ No newline at end of file
+                let travel = camera_position.xy() - layer.start_position.xy();
+                let distance_from_subject = layer_transform.translation.z - target_position.z;
+                let clipping_plane = if distance_from_subject <= 0.0 {
+                    camera_position.z + far
+                } else {
+                    camera_position.z + near
+                };
+
+                let parallax_factor = distance_from_subject.abs() / clipping_plane;
+
+                ui.label(format!("Parallax Factor: {}", parallax_factor));
+                // ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer.parallax_factor));
+                ui.end_row();
+                ui.label("Position: ");
+                ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.x));
+                ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.y));
+                ui.add_sized([60.0, 20.0], egui::DragValue::new(&mut layer_transform.translation.z));
+                ui.end_row();
+
+                // let new_pos = layer.start_position.xy() + travel * parallax_factor;
+                let new_pos = Vec2::new(layer.start_position.x + travel.x * parallax_factor, layer.start_position.y);
+                layer_transform.translation.x = new_pos.x;
+                // layer_transform.translation.y = new_pos.y;
+            }
+        });
+    });
+}
@@ -0,0 +1,164 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{camera::RenderTarget, render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages}, view::RenderLayers},
+    window::PrimaryWindow,
+};
+
+use super::MainCamera;
+
+// The game is drawn 1:1 in art pixels into an image this size, which is then
+// blown up by the biggest whole number that fits the window. Whatever is left
+// over around it stays black.
+#[derive(Resource, Debug, Clone)]
+pub struct PixelPerfectSettings {
+    pub resolution: UVec2
+}
+
+impl Default for PixelPerfectSettings {
+    fn default() -> Self {
+        PixelPerfectSettings {
+            resolution: UVec2::new(480, 270)
+        }
+    }
+}
+
+// Where the game image sits in the window, in logical pixels. UI placed over
+// things in the world goes through this to line up with them. Without the
+// pixel perfect plugin the main camera draws straight to the window and this
+// stays at no offset and a scale of 1.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct GameView {
+    pub offset: Vec2,
+    pub scale: f32
+}
+
+impl Default for GameView {
+    fn default() -> Self {
+        GameView {
+            offset: Vec2::ZERO,
+            scale: 1.0
+        }
+    }
+}
+
+impl GameView {
+    // Main camera viewport position to window position
+    pub fn to_window(&self, viewport_position: Vec2) -> Vec2 {
+        self.offset + viewport_position * self.scale
+    }
+}
+
+#[derive(Resource)]
+pub struct GameRenderTarget(pub Handle<Image>);
+
+// Draws the upscaled game image to the window, along with the UI
+#[derive(Component)]
+pub struct UpscaleCamera;
+
+#[derive(Component)]
+struct UpscaleSprite;
+
+// Kept off the layer everything else is on so the main camera doesn't see it
+const UPSCALE_LAYER: u8 = 1;
+
+fn setup_render_target(
+    mut commands: Commands,
+    settings: Res<PixelPerfectSettings>,
+    mut images: ResMut<Assets<Image>>
+) {
+    let size = Extent3d {
+        width: settings.resolution.x,
+        height: settings.resolution.y,
+        ..Default::default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("game render target"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..Default::default()
+    };
+    image.resize(size);
+    let handle = images.add(image);
+
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // After the main camera has drawn into the image
+                order: 1,
+                ..Default::default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::BLACK)
+            },
+            ..Default::default()
+        },
+        RenderLayers::layer(UPSCALE_LAYER),
+        UpscaleCamera
+    ));
+    commands.spawn((
+        SpriteBundle {
+            texture: handle.clone(),
+            ..Default::default()
+        },
+        RenderLayers::layer(UPSCALE_LAYER),
+        UpscaleSprite
+    ));
+    commands.insert_resource(GameRenderTarget(handle));
+}
+
+// The main camera gets spawned with the level, point it at the image once it shows up
+fn target_main_camera(
+    mut commands: Commands,
+    render_target: Res<GameRenderTarget>,
+    mut camera_query: Query<(Entity, &mut Camera), Added<MainCamera>>
+) {
+    for (entity, mut camera) in camera_query.iter_mut() {
+        camera.target = RenderTarget::Image(render_target.0.clone());
+        commands.entity(entity).insert(UiCameraConfig { show_ui: false });
+    }
+}
+
+fn fit_to_window(
+    settings: Res<PixelPerfectSettings>,
+    mut view: ResMut<GameView>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut sprite_query: Query<&mut Transform, With<UpscaleSprite>>
+) {
+    let window = match window_query.get_single() {
+        Ok(window) => window,
+        Err(_) => return,
+    };
+
+    // Whole physical pixels per art pixel, so every art pixel is the same size on screen
+    let resolution = settings.resolution.as_vec2();
+    let physical_size = Vec2::new(window.physical_width() as f32, window.physical_height() as f32);
+    let factor = (physical_size / resolution).min_element().floor().max(1.0);
+    let scale = factor / window.scale_factor() as f32;
+    let offset = (Vec2::new(window.width(), window.height()) - resolution * scale) / 2.0;
+
+    if view.scale != scale || view.offset != offset {
+        *view = GameView { offset, scale };
+    }
+    for mut transform in sprite_query.iter_mut() {
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+pub struct PixelPerfectPlugin;
+
+impl Plugin for PixelPerfectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PixelPerfectSettings>()
+            .init_resource::<GameView>()
+            .add_systems(Startup, setup_render_target)
+            .add_systems(Update, (target_main_camera, fit_to_window));
+    }
+}
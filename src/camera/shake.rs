@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use super::MainCamera;
+
+// Add trauma to shake the main camera, it wears off by `decay` a second. How
+// far the view moves goes with the square of trauma, so small knocks barely
+// show and big ones stack up. `max_offset` is in pixels at full trauma.
+#[derive(Resource, Debug)]
+pub struct CameraShake {
+    pub trauma: f32,
+    pub decay: f32,
+    pub max_offset: f32,
+    // How far the camera was moved last frame
+    offset: Vec2
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake {
+            trauma: 0.0,
+            decay: 1.5,
+            max_offset: 6.0,
+            offset: Vec2::ZERO
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn add(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+}
+
+// Takes last frame's shake back off so move_camera follows from where the
+// camera really is
+pub fn settle_camera_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>
+) {
+    if shake.offset == Vec2::ZERO {
+        return;
+    }
+    for mut transform in camera_query.iter_mut() {
+        transform.translation -= shake.offset.extend(0.0);
+    }
+    shake.offset = Vec2::ZERO;
+}
+
+pub fn shake_camera(
+    time: Res<Time>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>
+) {
+    if shake.trauma <= 0.0 {
+        return;
+    }
+
+    // A few sines out of step with each other wobble about like noise without a random source
+    let t = time.elapsed_seconds();
+    let wobble = Vec2::new((t * 37.0).sin() * (t * 23.0).cos(), (t * 29.0).sin() * (t * 41.0).cos());
+    let offset = (wobble * shake.trauma * shake.trauma * shake.max_offset).round();
+    for mut transform in camera_query.iter_mut() {
+        transform.translation += offset.extend(0.0);
+    }
+    shake.offset = offset;
+    shake.trauma = (shake.trauma - shake.decay * time.delta_seconds()).max(0.0);
+}
@@ -0,0 +1,60 @@
+use bevy::{ecs::system::SystemParam, input::gamepad::{Gamepad, GamepadAxis, GamepadAxisType, GamepadButton}, prelude::*};
+
+use crate::player::PlayerInput;
+
+use super::{device::InputSmoothing, glyphs::InputAction};
+
+// Where gameplay reads the player's actions from, so whatever the prompts
+// show actually works. Keys come from the player's bindings and buttons from
+// InputAction::gamepad_button on their gamepad. The left stick counts as the
+// d-pad once it's past InputSmoothing's deadzone.
+#[derive(SystemParam)]
+pub struct PlayerActions<'w> {
+    keys: Res<'w, Input<KeyCode>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    gamepads: Res<'w, Gamepads>,
+    smoothing: Option<Res<'w, InputSmoothing>>
+}
+
+impl PlayerActions<'_> {
+    fn gamepad(&self, input: &PlayerInput) -> Option<Gamepad> {
+        self.gamepads.iter().nth(input.gamepad)
+    }
+
+    fn button(&self, input: &PlayerInput, action: InputAction) -> Option<GamepadButton> {
+        self.gamepad(input).map(|gamepad| GamepadButton::new(gamepad, action.gamepad_button()))
+    }
+
+    fn stick(&self, input: &PlayerInput, action: InputAction) -> bool {
+        let gamepad = match self.gamepad(input) {
+            Some(gamepad) => gamepad,
+            None => return false,
+        };
+        let deadzone = self.smoothing.as_ref().map_or(InputSmoothing::default().stick_deadzone, |smoothing| smoothing.stick_deadzone);
+        let axis = |axis_type: GamepadAxisType| self.axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.0);
+        match action {
+            InputAction::Left => axis(GamepadAxisType::LeftStickX) <= -deadzone,
+            InputAction::Right => axis(GamepadAxisType::LeftStickX) >= deadzone,
+            InputAction::Crouch => axis(GamepadAxisType::LeftStickY) <= -deadzone,
+            _ => false
+        }
+    }
+
+    pub fn pressed(&self, input: &PlayerInput, action: InputAction) -> bool {
+        self.keys.pressed(action.binding(input))
+            || self.button(input, action).is_some_and(|button| self.buttons.pressed(button))
+            || self.stick(input, action)
+    }
+
+    // The stick has no presses of its own, only the d-pad does
+    pub fn just_pressed(&self, input: &PlayerInput, action: InputAction) -> bool {
+        self.keys.just_pressed(action.binding(input))
+            || self.button(input, action).is_some_and(|button| self.buttons.just_pressed(button))
+    }
+
+    pub fn just_released(&self, input: &PlayerInput, action: InputAction) -> bool {
+        self.keys.just_released(action.binding(input))
+            || self.button(input, action).is_some_and(|button| self.buttons.just_released(button))
+    }
+}
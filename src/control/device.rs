@@ -0,0 +1,90 @@
+use bevy::{input::gamepad::{Gamepad, GamepadAxis, GamepadButton}, prelude::*};
+
+// What the player last touched, prompts and input smoothing follow it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputDevice {
+    #[default]
+    Keyboard,
+    Gamepad(Gamepad)
+}
+
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActiveInputDevice(pub InputDevice);
+
+// Sent whenever ActiveInputDevice switches, for UI that has to swap prompts
+#[derive(Event, Debug, Clone, Copy)]
+pub struct InputDeviceChanged {
+    pub previous: InputDevice,
+    pub current: InputDevice
+}
+
+// Share of the gap to the target walking speed closed each step for each
+// device type, 1 is instant. Sticks can afford a little smoothing where keys
+// feel sluggish with any. `stick_deadzone` is how far a stick has to move to
+// count as using the gamepad.
+#[derive(Resource, Debug, Clone)]
+pub struct InputSmoothing {
+    pub keyboard: f32,
+    pub gamepad: f32,
+    pub stick_deadzone: f32
+}
+
+impl Default for InputSmoothing {
+    fn default() -> Self {
+        InputSmoothing {
+            keyboard: 1.0,
+            gamepad: 1.0,
+            stick_deadzone: 0.5
+        }
+    }
+}
+
+impl InputSmoothing {
+    pub fn for_device(&self, device: InputDevice) -> f32 {
+        match device {
+            InputDevice::Keyboard => self.keyboard,
+            InputDevice::Gamepad(_) => self.gamepad
+        }
+    }
+}
+
+// Gamepad events only arrive with the `gamepad` feature, which `native` turns on
+pub(super) fn detect_input_device(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    smoothing: Res<InputSmoothing>,
+    mut active: ResMut<ActiveInputDevice>,
+    mut changed_events: EventWriter<InputDeviceChanged>
+) {
+    let stick_moved = |gamepad: Gamepad| {
+        axes.devices()
+            .filter(|axis| axis.gamepad == gamepad)
+            .any(|axis| axes.get(*axis).is_some_and(|value| value.abs() >= smoothing.stick_deadzone))
+    };
+
+    let used = if keys.get_just_pressed().next().is_some() {
+        Some(InputDevice::Keyboard)
+    } else if let Some(button) = buttons.get_just_pressed().next() {
+        Some(InputDevice::Gamepad(button.gamepad))
+    } else {
+        gamepads.iter().find(|gamepad| stick_moved(*gamepad)).map(InputDevice::Gamepad)
+    };
+
+    if let Some(current) = used.filter(|current| *current != active.0) {
+        changed_events.send(InputDeviceChanged { previous: active.0, current });
+        active.0 = current;
+    }
+}
+
+pub struct InputDevicePlugin;
+
+impl Plugin for InputDevicePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveInputDevice>()
+            .init_resource::<InputSmoothing>()
+            .add_event::<InputDeviceChanged>()
+            .add_systems(PreUpdate, detect_input_device.after(bevy::input::InputSystem));
+    }
+}
@@ -0,0 +1,178 @@
+use bevy::{input::gamepad::GamepadButtonType, prelude::*, utils::HashMap};
+
+use crate::player::PlayerInput;
+
+use super::device::{ActiveInputDevice, InputDevice};
+
+// Something the player can be told to press, looked up in their bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    Left,
+    Right,
+    Jump,
+    Crouch,
+    Rewind,
+    Throw,
+    Carry
+}
+
+impl InputAction {
+    pub fn binding(&self, input: &PlayerInput) -> KeyCode {
+        match self {
+            InputAction::Left => input.left,
+            InputAction::Right => input.right,
+            InputAction::Jump => input.jump,
+            InputAction::Crouch => input.crouch,
+            InputAction::Rewind => input.rewind,
+            InputAction::Throw => input.throw,
+            InputAction::Carry => input.carry
+        }
+    }
+
+    // Gamepads can't be rebound yet so every pad uses these
+    pub fn gamepad_button(&self) -> GamepadButtonType {
+        match self {
+            InputAction::Left => GamepadButtonType::DPadLeft,
+            InputAction::Right => GamepadButtonType::DPadRight,
+            InputAction::Jump => GamepadButtonType::South,
+            InputAction::Crouch => GamepadButtonType::DPadDown,
+            InputAction::Rewind => GamepadButtonType::LeftTrigger,
+            InputAction::Throw => GamepadButtonType::West,
+            InputAction::Carry => GamepadButtonType::East
+        }
+    }
+}
+
+// Icon sprites for bound keys and gamepad buttons, by index into `atlas`.
+// Which set is used follows ActiveInputDevice. Anything without an icon gets
+// its name written out instead.
+#[derive(Resource, Default)]
+pub struct InputGlyphs {
+    pub atlas: Option<Handle<TextureAtlas>>,
+    pub icons: HashMap<KeyCode, usize>,
+    pub gamepad_icons: HashMap<GamepadButtonType, usize>
+}
+
+impl InputGlyphs {
+    // Name and icon of whatever `action` is bound to on `device`
+    pub fn glyph(&self, action: InputAction, bindings: &PlayerInput, device: InputDevice) -> (String, Option<usize>) {
+        match device {
+            InputDevice::Keyboard => {
+                let key = action.binding(bindings);
+                (key_name(key), self.icons.get(&key).copied())
+            }
+            InputDevice::Gamepad(_) => {
+                let button = action.gamepad_button();
+                (button_name(button), self.gamepad_icons.get(&button).copied())
+            }
+        }
+    }
+}
+
+// Shows whatever `action` is bound to right now. On a text entity it fills in
+// `section`, so signs can be written as "Press " + glyph + " to jump". On an
+// atlas image it shows the key's icon, hidden when the key has none.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct InputGlyph {
+    pub action: InputAction,
+    pub section: usize
+}
+
+impl InputGlyph {
+    pub fn new(action: InputAction) -> Self {
+        InputGlyph { action, section: 0 }
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    let name = match key {
+        KeyCode::Return => "Enter",
+        KeyCode::Back => "Backspace",
+        KeyCode::Up => "Up Arrow",
+        KeyCode::Down => "Down Arrow",
+        KeyCode::Left => "Left Arrow",
+        KeyCode::Right => "Right Arrow",
+        _ => name.strip_prefix("Key").filter(|digit| digit.len() == 1).unwrap_or(&name)
+    };
+    name.to_uppercase()
+}
+
+pub fn button_name(button: GamepadButtonType) -> String {
+    let name = match button {
+        GamepadButtonType::South => "A",
+        GamepadButtonType::East => "B",
+        GamepadButtonType::West => "X",
+        GamepadButtonType::North => "Y",
+        GamepadButtonType::DPadUp => "D-Pad Up",
+        GamepadButtonType::DPadDown => "D-Pad Down",
+        GamepadButtonType::DPadLeft => "D-Pad Left",
+        GamepadButtonType::DPadRight => "D-Pad Right",
+        GamepadButtonType::LeftTrigger => "LB",
+        GamepadButtonType::RightTrigger => "RB",
+        GamepadButtonType::LeftTrigger2 => "LT",
+        GamepadButtonType::RightTrigger2 => "RT",
+        GamepadButtonType::Start => "Start",
+        GamepadButtonType::Select => "Select",
+        _ => return format!("{:?}", button).to_uppercase()
+    };
+    name.to_uppercase()
+}
+
+// Bindings of the player in the level, or the defaults before one has spawned
+fn current_bindings(player_query: &Query<&PlayerInput>) -> PlayerInput {
+    player_query.iter().next().copied().unwrap_or_default()
+}
+
+fn update_text_glyphs(
+    glyphs: Res<InputGlyphs>,
+    device: Res<ActiveInputDevice>,
+    player_query: Query<&PlayerInput>,
+    mut glyph_query: Query<(&InputGlyph, &mut Text)>
+) {
+    let bindings = current_bindings(&player_query);
+    for (glyph, mut text) in glyph_query.iter_mut() {
+        let (name, _) = glyphs.glyph(glyph.action, &bindings, device.0);
+        // Only touch the text when it changes so it isn't laid out again every frame
+        if text.sections.get(glyph.section).is_some_and(|section| section.value != name) {
+            text.sections[glyph.section].value = name;
+        }
+    }
+}
+
+fn update_icon_glyphs(
+    glyphs: Res<InputGlyphs>,
+    device: Res<ActiveInputDevice>,
+    player_query: Query<&PlayerInput>,
+    mut glyph_query: Query<(&InputGlyph, &mut Handle<TextureAtlas>, &mut UiTextureAtlasImage, &mut Visibility), Without<Text>>
+) {
+    let bindings = current_bindings(&player_query);
+    for (glyph, mut atlas, mut image, mut visibility) in glyph_query.iter_mut() {
+        let (_, icon) = glyphs.glyph(glyph.action, &bindings, device.0);
+        let shown = match glyphs.atlas.as_ref().zip(icon) {
+            Some((icon_atlas, index)) => {
+                if *atlas != *icon_atlas {
+                    *atlas = icon_atlas.clone();
+                }
+                if image.index != index {
+                    image.index = index;
+                }
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden
+        };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+pub struct InputGlyphPlugin;
+
+impl Plugin for InputGlyphPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputGlyphs>()
+            .init_resource::<ActiveInputDevice>()
+            .add_systems(Update, (update_text_glyphs, update_icon_glyphs));
+    }
+}
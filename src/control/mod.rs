@@ -0,0 +1,76 @@
+use bevy::{prelude::Resource, utils::HashMap};
+
+pub mod actions;
+pub mod device;
+pub mod glyphs;
+pub mod touch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControlLockReason {
+    Dialog,
+    Cutscene,
+    Death,
+    LevelTransition,
+    // The error screen is up
+    Error
+}
+
+impl ControlLockReason {
+    // Cinematic locks get the letterbox bars
+    pub fn is_cinematic(&self) -> bool {
+        matches!(self, ControlLockReason::Cutscene)
+    }
+}
+
+// Handed out by PlayerControlLock::acquire. It can't be copied so every lock
+// gets released exactly once, by whoever took it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ControlLockToken {
+    id: u64,
+    reason: ControlLockReason
+}
+
+impl ControlLockToken {
+    pub fn reason(&self) -> ControlLockReason {
+        self.reason
+    }
+}
+
+// Central place for anything that needs to take control away from the player.
+// Every system that wants the player frozen takes its own token, input only
+// comes back once every token is released so overlapping locks (a death during
+// a dialog, a cutscene kicking off a level transition) can't leave input stuck.
+#[derive(Resource, Default, Debug)]
+pub struct PlayerControlLock {
+    next_id: u64,
+    locks: HashMap<u64, ControlLockReason>
+}
+
+impl PlayerControlLock {
+    pub fn acquire(&mut self, reason: ControlLockReason) -> ControlLockToken {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.locks.insert(id, reason);
+        ControlLockToken { id, reason }
+    }
+
+    pub fn release(&mut self, token: ControlLockToken) {
+        self.locks.remove(&token.id);
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !self.locks.is_empty()
+    }
+
+    pub fn is_locked_by(&self, reason: ControlLockReason) -> bool {
+        self.count(reason) > 0
+    }
+
+    pub fn count(&self, reason: ControlLockReason) -> usize {
+        self.locks.values().filter(|lock_reason| **lock_reason == reason).count()
+    }
+
+    pub fn is_cinematic(&self) -> bool {
+        self.locks.values().any(|reason| reason.is_cinematic())
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::player::PlayerInput;
+
+use super::{device::detect_input_device, glyphs::InputAction};
+
+// On screen D-pad and jump button for phones and tablets. They stay hidden
+// until the first touch so desktop players never see them. `scale` multiplies
+// the button sizes and gaps, `opacity` is how see through the buttons are
+// while not held.
+#[derive(Resource, Debug, Clone)]
+pub struct TouchControlSettings {
+    pub enabled: bool,
+    pub scale: f32,
+    pub opacity: f32
+}
+
+impl Default for TouchControlSettings {
+    fn default() -> Self {
+        TouchControlSettings {
+            enabled: true,
+            scale: 1.0,
+            opacity: 0.35
+        }
+    }
+}
+
+// Actions currently held down through the on screen buttons, and whether any
+// touch has been seen yet
+#[derive(Resource, Default, Debug)]
+pub struct TouchControls {
+    pub held: HashSet<InputAction>,
+    pub shown: bool
+}
+
+#[derive(Component)]
+struct TouchControlsRoot;
+
+#[derive(Component, Debug, Clone, Copy)]
+struct TouchButton(InputAction);
+
+// Where a button sits in the layout and whether it is on the right hand side
+#[derive(Component, Debug, Clone, Copy)]
+struct TouchCell(Vec2, bool);
+
+// Logical pixels at a scale of 1
+const BUTTON_SIZE: f32 = 64.0;
+const MARGIN: f32 = 24.0;
+
+fn spawn_touch_controls(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..Default::default()
+            },
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(10),
+            ..Default::default()
+        },
+        TouchControlsRoot
+    )).with_children(|parent| {
+        // Grid cell is in button sizes from the bottom left of the D-pad, or
+        // the bottom right for the action buttons
+        let buttons = [
+            (InputAction::Left, "<", false, Vec2::new(0.0, 1.0)),
+            (InputAction::Right, ">", false, Vec2::new(2.0, 1.0)),
+            (InputAction::Crouch, "v", false, Vec2::new(1.0, 0.0)),
+            (InputAction::Jump, "^", true, Vec2::new(0.0, 0.5))
+        ];
+        for (action, label, right_side, cell) in buttons.iter().copied() {
+            // Size and offsets get filled in by update_touch_buttons
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                TouchButton(action),
+                TouchCell(cell, right_side)
+            )).with_children(|button| {
+                button.spawn(TextBundle::from_section(label, TextStyle {
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                }));
+            });
+        }
+    });
+}
+
+// Each touch holds down whichever button it is over, so the D-pad and jump
+// can be pressed together. Held actions press the first player's keys, which
+// keeps everything reading Input<KeyCode> working without knowing about touch.
+fn read_touch_controls(
+    touches: Res<Touches>,
+    settings: Res<TouchControlSettings>,
+    mut controls: ResMut<TouchControls>,
+    mut keys: ResMut<Input<KeyCode>>,
+    button_query: Query<(&TouchButton, &Node, &GlobalTransform, &InheritedVisibility)>
+) {
+    controls.shown |= touches.iter().next().is_some();
+
+    let mut held = HashSet::default();
+    if settings.enabled {
+        for touch in touches.iter() {
+            let over = button_query.iter().find(|(_, node, transform, visibility)| {
+                let half_size = node.size() / 2.0;
+                let offset = touch.position() - transform.translation().truncate();
+                visibility.get() && offset.abs().cmple(half_size).all()
+            });
+            if let Some((TouchButton(action), ..)) = over {
+                held.insert(*action);
+            }
+        }
+    }
+
+    let bindings = PlayerInput::for_local_player(0);
+    for action in controls.held.difference(&held) {
+        keys.release(action.binding(&bindings));
+    }
+    for action in held.difference(&controls.held) {
+        keys.press(action.binding(&bindings));
+    }
+    controls.held = held;
+}
+
+fn update_touch_buttons(
+    settings: Res<TouchControlSettings>,
+    controls: Res<TouchControls>,
+    mut root_query: Query<&mut Visibility, With<TouchControlsRoot>>,
+    mut button_query: Query<(&TouchButton, &TouchCell, &mut Style, &mut BackgroundColor)>
+) {
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if settings.enabled && controls.shown { Visibility::Inherited } else { Visibility::Hidden };
+    }
+
+    let size = BUTTON_SIZE * settings.scale;
+    let margin = MARGIN * settings.scale;
+    for (TouchButton(action), TouchCell(cell, right_side), mut style, mut color) in button_query.iter_mut() {
+        // The jump button is bigger since it gets hit the most
+        let button_size = if *right_side { size * 1.5 } else { size };
+        let offset = Val::Px(margin + cell.x * size);
+        if *right_side {
+            style.right = offset;
+        } else {
+            style.left = offset;
+        }
+        style.bottom = Val::Px(margin + cell.y * size);
+        style.width = Val::Px(button_size);
+        style.height = Val::Px(button_size);
+
+        let alpha = if controls.held.contains(action) { (settings.opacity * 2.0).min(1.0) } else { settings.opacity };
+        color.0 = Color::rgba(1.0, 1.0, 1.0, alpha);
+    }
+}
+
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControlSettings>()
+            .init_resource::<TouchControls>()
+            .add_systems(Startup, spawn_touch_controls)
+            // After the device check so the pressed keys don't count as the keyboard
+            .add_systems(PreUpdate, read_touch_controls.after(bevy::input::InputSystem).after(detect_input_device))
+            .add_systems(Update, update_touch_buttons);
+    }
+}
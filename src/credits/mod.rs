@@ -0,0 +1,218 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{camera::{CameraFocus, MainCamera}, control::{ControlLockReason, ControlLockToken, PlayerControlLock}, level::{AppState, GameCompleted}, preload::GameAssets};
+
+const CREDITS_PATH: &str = "credits/credits.credits.ron";
+
+// The credits roll, written as RON in `*.credits.ron` files. Text scrolls up
+// at `scroll_speed` logical pixels a second. With `pan_speed` set the level
+// stays visible behind the text and the camera drifts sideways through it so
+// the parallax layers move, otherwise the screen goes black.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Credits {
+    pub scroll_speed: f32,
+    #[serde(default)]
+    pub pan_speed: Option<f32>,
+    pub sections: Vec<CreditsSection>
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreditsSection {
+    pub title: String,
+    #[serde(default)]
+    pub names: Vec<String>
+}
+
+#[derive(Default)]
+pub struct CreditsAssetLoader;
+
+impl AssetLoader for CreditsAssetLoader {
+    type Asset = Credits;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let credits = ron::de::from_bytes::<Credits>(&bytes)?;
+            Ok(credits)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["credits.ron"]
+    }
+}
+
+// Root of the credits UI, everything under it goes when the credits end
+#[derive(Component)]
+struct CreditsRoot;
+
+// The column of text that scrolls up the screen
+#[derive(Component)]
+struct CreditsScroll {
+    offset: f32,
+    scroll_speed: f32,
+    pan_speed: Option<f32>
+}
+
+#[derive(Resource)]
+struct CreditsControlLock(Option<ControlLockToken>);
+
+fn start_credits(
+    mut game_completed: EventReader<GameCompleted>,
+    mut state: ResMut<NextState<AppState>>
+) {
+    if game_completed.read().last().is_some() {
+        state.set(AppState::Credits);
+    }
+}
+
+fn spawn_credits(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    credits_assets: Res<Assets<Credits>>,
+    mut control_lock: ResMut<PlayerControlLock>,
+    mut state: ResMut<NextState<AppState>>
+) {
+    let credits = match credits_assets.get(game_assets.credits(CREDITS_PATH)) {
+        Some(credits) => credits,
+        None => {
+            warn!("No credits to roll");
+            state.set(AppState::InGame);
+            return;
+        }
+    };
+
+    commands.insert_resource(CreditsControlLock(Some(control_lock.acquire(ControlLockReason::Cutscene))));
+
+    let backdrop = if credits.pan_speed.is_some() { Color::rgba(0.0, 0.0, 0.0, 0.5) } else { Color::BLACK };
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                overflow: Overflow::clip(),
+                ..Default::default()
+            },
+            background_color: backdrop.into(),
+            // Over the rest of the UI, letterbox bars included
+            z_index: ZIndex::Global(20),
+            ..Default::default()
+        },
+        CreditsRoot
+    )).with_children(|root| {
+        root.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    // Starts just under the bottom of the screen
+                    top: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            CreditsScroll {
+                offset: 0.0,
+                scroll_speed: credits.scroll_speed,
+                pan_speed: credits.pan_speed
+            }
+        )).with_children(|column| {
+            for section in credits.sections.iter() {
+                column.spawn(TextBundle::from_section(section.title.clone(), TextStyle {
+                    font_size: 48.0,
+                    color: Color::GOLD,
+                    ..Default::default()
+                }).with_style(Style {
+                    margin: UiRect::top(Val::Px(48.0)),
+                    ..Default::default()
+                }));
+                for name in section.names.iter() {
+                    column.spawn(TextBundle::from_section(name.clone(), TextStyle {
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    }));
+                }
+            }
+        });
+    });
+}
+
+// Ends the credits once the text has scrolled off the top or on any key press.
+// Back in game is as far as it goes until there is a title screen.
+fn scroll_credits(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut camera_focus: ResMut<CameraFocus>,
+    mut state: ResMut<NextState<AppState>>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    root_query: Query<&Node, With<CreditsRoot>>,
+    mut scroll_query: Query<(&mut CreditsScroll, &mut Style, &Node)>
+) {
+    // Nodes have no size until layout has run once
+    let screen_height = root_query.get_single().map_or(0.0, |node| node.size().y);
+    if screen_height == 0.0 {
+        return;
+    }
+
+    for (mut scroll, mut style, node) in scroll_query.iter_mut() {
+        scroll.offset += scroll.scroll_speed * time.delta_seconds();
+        style.top = Val::Px(screen_height - scroll.offset);
+
+        if let (Some(pan_speed), Ok(camera)) = (scroll.pan_speed, camera_query.get_single()) {
+            let focus = camera_focus.0.unwrap_or(camera.translation.truncate());
+            camera_focus.0 = Some(focus + Vec2::new(pan_speed * time.delta_seconds(), 0.0));
+        }
+
+        let scrolled_off = scroll.offset > screen_height + node.size().y;
+        if scrolled_off || keys.get_just_pressed().next().is_some() {
+            state.set(AppState::InGame);
+        }
+    }
+}
+
+fn despawn_credits(
+    mut commands: Commands,
+    mut camera_focus: ResMut<CameraFocus>,
+    mut control_lock: ResMut<PlayerControlLock>,
+    lock: Option<ResMut<CreditsControlLock>>,
+    root_query: Query<Entity, With<CreditsRoot>>
+) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(token) = lock.and_then(|mut lock| lock.0.take()) {
+        control_lock.release(token);
+    }
+    camera_focus.0 = None;
+}
+
+pub struct CreditsPlugin;
+
+impl Plugin for CreditsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Credits>()
+            .init_asset_loader::<CreditsAssetLoader>()
+            .add_systems(Update, start_credits.run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(AppState::Credits), spawn_credits)
+            .add_systems(Update, scroll_credits.run_if(in_state(AppState::Credits)))
+            .add_systems(OnExit(AppState::Credits), despawn_credits);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{control::{ControlLockReason, ControlLockToken, PlayerControlLock}, level::AppState};
+
+// Where an error came from, shown with it so reports say what to look at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorSource {
+    // Path in the asset folder
+    Asset(String),
+    // LDtk level identifier
+    Level(String),
+    // LDtk level and layer identifiers
+    Layer(String, String),
+    Entity(String)
+}
+
+impl fmt::Display for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorSource::Asset(path) => write!(f, "Asset {}", path),
+            ErrorSource::Level(level) => write!(f, "Level {}", level),
+            ErrorSource::Layer(level, layer) => write!(f, "Layer {} in level {}", layer, level),
+            ErrorSource::Entity(entity) => write!(f, "Entity {}", entity),
+        }
+    }
+}
+
+// Send instead of panicking when something is broken but the game can keep
+// going around it. Native builds stop on the error screen, which pauses
+// virtual time and locks player control until it's left, the web build only
+// logs it to the console so the page doesn't lock up.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct GameError {
+    pub source: ErrorSource,
+    pub message: String
+}
+
+impl GameError {
+    pub fn new(source: ErrorSource, message: impl Into<String>) -> Self {
+        GameError {
+            source,
+            message: message.into()
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+// Every error so far, each only once even if it keeps getting sent
+#[derive(Resource, Debug, Default)]
+pub struct GameErrors {
+    pub errors: Vec<GameError>,
+    // Where continuing from the error screen goes back to
+    previous_state: Option<AppState>,
+    // Held while the error screen is up
+    control_token: Option<ControlLockToken>
+}
+
+// Root of the error screen
+#[derive(Component)]
+struct ErrorScreenRoot;
+
+#[derive(Component)]
+struct ErrorList;
+
+// Panics still end the game, this writes what happened next to the game so
+// it can be sent along with a report. The web build gets the browser console
+// from Bevy's own hook instead.
+#[cfg(not(target_arch = "wasm32"))]
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = format!("{}\n\n{}", info, std::backtrace::Backtrace::force_capture());
+        if let Err(err) = std::fs::write("crash.log", report) {
+            eprintln!("Couldn't write crash.log: {}", err);
+        }
+        default_hook(info);
+    }));
+}
+
+fn record_errors(
+    mut error_events: EventReader<GameError>,
+    mut errors: ResMut<GameErrors>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>
+) {
+    for event in error_events.read() {
+        if errors.errors.contains(event) {
+            continue;
+        }
+        error!("{}", event);
+        errors.errors.push(event.clone());
+
+        if cfg!(not(target_arch = "wasm32")) && *state.get() != AppState::Error {
+            errors.previous_state = Some(state.get().clone());
+            next_state.set(AppState::Error);
+        }
+    }
+}
+
+// Nothing in FixedUpdate runs while virtual time is paused, so physics and
+// the half spawned level stay put behind the error screen
+fn pause_game(
+    mut time: ResMut<Time<Virtual>>,
+    mut errors: ResMut<GameErrors>,
+    mut control_lock: ResMut<PlayerControlLock>
+) {
+    time.pause();
+    if errors.control_token.is_none() {
+        errors.control_token = Some(control_lock.acquire(ControlLockReason::Error));
+    }
+}
+
+fn resume_game(
+    mut time: ResMut<Time<Virtual>>,
+    mut errors: ResMut<GameErrors>,
+    mut control_lock: ResMut<PlayerControlLock>
+) {
+    time.unpause();
+    if let Some(token) = errors.control_token.take() {
+        control_lock.release(token);
+    }
+}
+
+fn spawn_error_screen(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(16.0),
+                ..Default::default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+            // Over everything, credits included
+            z_index: ZIndex::Global(30),
+            ..Default::default()
+        },
+        ErrorScreenRoot
+    )).with_children(|root| {
+        root.spawn(TextBundle::from_section("Something went wrong", TextStyle {
+            font_size: 48.0,
+            color: Color::ORANGE_RED,
+            ..Default::default()
+        }));
+        root.spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ErrorList
+        ));
+        root.spawn(TextBundle::from_section("Enter to carry on, Escape to quit. The errors are in the log as well.", TextStyle {
+            font_size: 24.0,
+            color: Color::GRAY,
+            ..Default::default()
+        }));
+    });
+}
+
+// Errors can keep coming in while the screen is up
+fn update_error_list(
+    mut commands: Commands,
+    errors: Res<GameErrors>,
+    list_query: Query<Entity, With<ErrorList>>
+) {
+    if !errors.is_changed() {
+        return;
+    }
+    for list in list_query.iter() {
+        commands.entity(list).despawn_descendants().with_children(|list| {
+            for error in errors.errors.iter() {
+                list.spawn(TextBundle::from_section(error.to_string(), TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                }));
+            }
+        });
+    }
+}
+
+fn leave_error_screen(
+    keys: Res<Input<KeyCode>>,
+    errors: Res<GameErrors>,
+    mut exit_events: EventWriter<AppExit>,
+    mut next_state: ResMut<NextState<AppState>>
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        exit_events.send(AppExit);
+    } else if keys.just_pressed(KeyCode::Return) {
+        next_state.set(errors.previous_state.clone().unwrap_or_default());
+    }
+}
+
+fn despawn_error_screen(
+    mut commands: Commands,
+    root_query: Query<Entity, With<ErrorScreenRoot>>
+) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct ErrorPlugin;
+
+impl Plugin for ErrorPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(not(target_arch = "wasm32"))]
+        install_panic_hook();
+
+        app.add_event::<GameError>()
+            .init_resource::<GameErrors>()
+            .init_resource::<PlayerControlLock>()
+            .add_systems(Last, record_errors)
+            .add_systems(OnEnter(AppState::Error), (pause_game, spawn_error_screen))
+            .add_systems(Update, (update_error_list, leave_error_screen).run_if(in_state(AppState::Error)))
+            .add_systems(OnExit(AppState::Error), (resume_game, despawn_error_screen));
+    }
+}
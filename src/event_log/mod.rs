@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+
+use bevy::{core::FrameCount, prelude::*};
+use bevy_egui::{EguiContexts, egui};
+
+use crate::{animation::{AnimationEvent, AnimationFinished}, control::device::InputDeviceChanged, explosion::ExplosionHit, level::{AppState, GameCompleted}, physics::{solid::Squished, trigger::{TriggerEnter, TriggerExit}}, player::player_animation::PlayerAnimationUpdate};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogCategory {
+    Damage,
+    Trigger,
+    Level,
+    State,
+    Animation,
+    Input
+}
+
+impl LogCategory {
+    pub const ALL: [LogCategory; 6] = [LogCategory::Damage, LogCategory::Trigger, LogCategory::Level, LogCategory::State, LogCategory::Animation, LogCategory::Input];
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    // Seconds since startup and the frame it was logged on, events
+    // sent during fixed steps show up on the frame they were read
+    pub time: f32,
+    pub frame: u32,
+    pub category: LogCategory,
+    pub message: String
+}
+
+// The last `capacity` gameplay events, oldest first. Meant for working out
+// which order systems saw things happen in, so nothing here is read back by
+// gameplay.
+#[derive(Resource, Debug)]
+pub struct EventLog {
+    pub entries: VecDeque<LogEntry>,
+    pub capacity: usize,
+    pub paused: bool,
+    // Categories shown in the panel, everything is still recorded
+    pub shown: Vec<LogCategory>,
+    // Only entries containing this are shown, empty shows all
+    pub filter: String
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog {
+            entries: VecDeque::new(),
+            capacity: 500,
+            paused: false,
+            shown: LogCategory::ALL.to_vec(),
+            filter: String::new()
+        }
+    }
+}
+
+impl EventLog {
+    pub fn push(&mut self, time: &Time, frame: &FrameCount, category: LogCategory, message: String) {
+        if self.paused {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            time: time.elapsed_seconds(),
+            frame: frame.0,
+            category,
+            message
+        });
+    }
+}
+
+// Events the log knows how to describe
+pub trait LogEvent: Event {
+    const CATEGORY: LogCategory;
+    fn describe(&self) -> String;
+}
+
+impl LogEvent for ExplosionHit {
+    const CATEGORY: LogCategory = LogCategory::Damage;
+    fn describe(&self) -> String {
+        format!("{:?} hit by an explosion for {}", self.entity, self.damage)
+    }
+}
+
+impl LogEvent for Squished {
+    const CATEGORY: LogCategory = LogCategory::Damage;
+    fn describe(&self) -> String {
+        format!("{:?} squished", self.0)
+    }
+}
+
+impl LogEvent for TriggerEnter {
+    const CATEGORY: LogCategory = LogCategory::Trigger;
+    fn describe(&self) -> String {
+        format!("{:?} entered sensor {:?}", self.entity, self.sensor)
+    }
+}
+
+impl LogEvent for TriggerExit {
+    const CATEGORY: LogCategory = LogCategory::Trigger;
+    fn describe(&self) -> String {
+        format!("{:?} left sensor {:?}", self.entity, self.sensor)
+    }
+}
+
+impl LogEvent for GameCompleted {
+    const CATEGORY: LogCategory = LogCategory::Level;
+    fn describe(&self) -> String {
+        "Game completed".to_string()
+    }
+}
+
+impl LogEvent for AnimationEvent {
+    const CATEGORY: LogCategory = LogCategory::Animation;
+    fn describe(&self) -> String {
+        format!("{:?} animation event \"{}\"", self.entity, self.name)
+    }
+}
+
+impl LogEvent for AnimationFinished {
+    const CATEGORY: LogCategory = LogCategory::Animation;
+    fn describe(&self) -> String {
+        format!("{:?} finished animation row {}", self.entity, self.row)
+    }
+}
+
+impl LogEvent for InputDeviceChanged {
+    const CATEGORY: LogCategory = LogCategory::Input;
+    fn describe(&self) -> String {
+        format!("Input device {:?} -> {:?}", self.previous, self.current)
+    }
+}
+
+fn log_events<E: LogEvent>(
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    mut log: ResMut<EventLog>,
+    mut events: EventReader<E>
+) {
+    for event in events.read() {
+        log.push(&time, &frame, E::CATEGORY, event.describe());
+    }
+}
+
+// Entering InGame is when the level gets spawned
+fn log_app_state(
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    mut log: ResMut<EventLog>,
+    state: Res<State<AppState>>
+) {
+    if state.is_changed() {
+        let category = if *state.get() == AppState::InGame { LogCategory::Level } else { LogCategory::State };
+        log.push(&time, &frame, category, format!("App state {:?}", state.get()));
+    }
+}
+
+fn log_player_animation_state(
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    mut log: ResMut<EventLog>,
+    player_query: Query<(Entity, &PlayerAnimationUpdate), Changed<PlayerAnimationUpdate>>
+) {
+    for (entity, state) in player_query.iter() {
+        log.push(&time, &frame, LogCategory::State, format!("{:?} animation graph {:?}", entity, state));
+    }
+}
+
+fn debug_event_log(
+    mut egui_ctx: EguiContexts,
+    mut log: ResMut<EventLog>
+) {
+    egui::Window::new("Event log").show(egui_ctx.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut log.paused, "Paused");
+            if ui.button("Clear").clicked() {
+                log.entries.clear();
+            }
+            ui.label("Filter");
+            ui.text_edit_singleline(&mut log.filter);
+        });
+        ui.horizontal_wrapped(|ui| {
+            for category in LogCategory::ALL {
+                let mut shown = log.shown.contains(&category);
+                if ui.checkbox(&mut shown, format!("{:?}", category)).changed() {
+                    log.shown.retain(|shown_category| *shown_category != category);
+                    if shown {
+                        log.shown.push(category);
+                    }
+                }
+            }
+        });
+
+        ui.separator();
+
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            let filter = log.filter.to_lowercase();
+            for entry in log.entries.iter() {
+                if !log.shown.contains(&entry.category) || !entry.message.to_lowercase().contains(&filter) {
+                    continue;
+                }
+                ui.monospace(format!("{:>8.3} #{:<6} {:<9} {}", entry.time, entry.frame, format!("{:?}", entry.category), entry.message));
+            }
+        });
+    });
+}
+
+// `app.log_event::<E>()` adds another event to the log
+pub trait LogEventAppExt {
+    fn log_event<E: LogEvent>(&mut self) -> &mut Self;
+}
+
+impl LogEventAppExt for App {
+    // Skipped when whatever sends the event isn't added
+    fn log_event<E: LogEvent>(&mut self) -> &mut Self {
+        self.add_systems(Update, log_events::<E>.run_if(resource_exists::<Events<E>>()))
+    }
+}
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>()
+            .log_event::<ExplosionHit>()
+            .log_event::<Squished>()
+            .log_event::<TriggerEnter>()
+            .log_event::<TriggerExit>()
+            .log_event::<GameCompleted>()
+            .log_event::<AnimationEvent>()
+            .log_event::<AnimationFinished>()
+            .log_event::<InputDeviceChanged>()
+            .add_systems(Update, (
+                log_app_state.run_if(resource_exists::<State<AppState>>()),
+                log_player_animation_state,
+                debug_event_log
+            ));
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+use crate::{animation::{AnimatedSpriteBundle, AnimationController, AnimationDefinition, Col, Row, SpriteSheetDefinition, SpriteSheetGrids}, audio::PlaySound, camera::shake::CameraShake, physics::{PhysicsStages, activation::WakeBody, body::Velocity, world::{PhysicsQuery, QueryMask}}, player::Health, preload::GameAssets, spawn_effect::{DespawnEffect, SpawnEffect}};
+
+const EXPLOSION_TEXTURE: &str = "miscellaneous sprites/explosion_anim_strip_10.png";
+const EXPLOSION_SOUND: &str = "sounds/explosion.wav";
+const EXPLOSION_FRAMES: usize = 10;
+const EXPLOSION_FRAME_TIME: f32 = 0.05;
+
+// Send to blow something up, barrels, bombs and boss attacks all go through
+// here. `position` and `radius` are in pixels, `impulse` is the speed in
+// physics units added to bodies at the centre and `damage` the health lost
+// there. Both fall off to nothing at the edge of the radius. `shake` is the
+// trauma added to CameraShake.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Explosion {
+    pub position: Vec2,
+    pub radius: f32,
+    pub impulse: f32,
+    pub damage: u32,
+    pub shake: f32
+}
+
+#[derive(Resource)]
+pub struct ExplosionAssets {
+    pub atlas: Handle<TextureAtlas>
+}
+
+fn setup_explosion_assets(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    mut sprite_sheet_grids: ResMut<SpriteSheetGrids>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>
+) {
+    let atlas = sprite_sheet_grids.from_grid(&mut texture_atlases, game_assets.texture(EXPLOSION_TEXTURE), Vec2::splat(32.0), EXPLOSION_FRAMES, 1);
+    commands.insert_resource(ExplosionAssets { atlas });
+}
+
+// The blast animation sized to the radius, poofing away as it finishes
+fn spawn_blast(commands: &mut Commands, assets: &ExplosionAssets, explosion: &Explosion) {
+    let mut poof = DespawnEffect::default();
+    poof.timer = Timer::from_seconds(EXPLOSION_FRAMES as f32 * EXPLOSION_FRAME_TIME, TimerMode::Once);
+    commands.spawn((
+        AnimatedSpriteBundle {
+            sprite_sheet: SpriteSheetBundle {
+                texture_atlas: assets.atlas.clone(),
+                transform: Transform::from_translation(explosion.position.extend(600.0))
+                    .with_scale(Vec3::splat((explosion.radius * 2.0 / 32.0).max(1.0))),
+                ..Default::default()
+            },
+            sprite_sheet_definitions: SpriteSheetDefinition {
+                animation_definitions: vec![AnimationDefinition {
+                    name: "explode".to_string(),
+                    number_of_frames: EXPLOSION_FRAMES,
+                    frame_time: EXPLOSION_FRAME_TIME,
+                    repeating: false,
+                    ..Default::default()
+                }],
+                rows: 1,
+                columns: EXPLOSION_FRAMES,
+                ..Default::default()
+            },
+            current_row: Row(0),
+            current_col: Col(0),
+            controller: AnimationController::new("explode"),
+            ..Default::default()
+        },
+        poof
+    ));
+}
+
+// Sent for every actor an explosion reaches, for effects and sounds on whatever got hit
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ExplosionHit {
+    pub entity: Entity,
+    pub direction: Vec2,
+    pub damage: u32
+}
+
+// Uses the colliders from the end of the last step, so it runs before anything moves this one
+fn explode(
+    mut commands: Commands,
+    mut world: PhysicsQuery,
+    mut shake: ResMut<CameraShake>,
+    explosion_assets: Option<Res<ExplosionAssets>>,
+    mut sound_events: EventWriter<PlaySound>,
+    mut explosion_events: EventReader<Explosion>,
+    mut hit_events: EventWriter<ExplosionHit>,
+    mut wake_events: EventWriter<WakeBody>,
+    mut body_query: Query<(&mut Velocity, Option<&mut Health>)>,
+    // Things appearing or poofing away can't be hurt
+    effect_query: Query<(), Or<(With<SpawnEffect>, With<DespawnEffect>)>>
+) {
+    for explosion in explosion_events.read() {
+        shake.add(explosion.shake);
+        sound_events.send(PlaySound::sfx(EXPLOSION_SOUND));
+        if let Some(assets) = &explosion_assets {
+            spawn_blast(&mut commands, assets, explosion);
+        }
+
+        for hit in world.overlap_circle("explode", explosion.position, explosion.radius, QueryMask::ACTORS) {
+            let (mut velocity, health) = match body_query.get_mut(hit.entity) {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+
+            let falloff = if explosion.radius > 0.0 { 1.0 - hit.distance / explosion.radius } else { 1.0 };
+            // Bodies right on top of the blast get thrown straight up
+            let direction = (hit.point - explosion.position).try_normalize().unwrap_or(Vec2::Y);
+            velocity.0 += direction * explosion.impulse * falloff;
+
+            let damage = (explosion.damage as f32 * falloff).round() as u32;
+            if let Some(mut health) = health.filter(|_| !effect_query.contains(hit.entity)) {
+                health.0 = health.0.saturating_sub(damage);
+            }
+
+            wake_events.send(WakeBody(hit.entity));
+            hit_events.send(ExplosionHit {
+                entity: hit.entity,
+                direction,
+                damage
+            });
+        }
+    }
+}
+
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Explosion>()
+            .add_event::<ExplosionHit>()
+            .add_event::<PlaySound>()
+            .init_resource::<CameraShake>()
+            .add_systems(Update, setup_explosion_assets.run_if(resource_added::<GameAssets>()))
+            .add_systems(FixedUpdate, explode.in_set(PhysicsStages::PreStep));
+    }
+}
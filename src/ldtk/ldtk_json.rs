@@ -12,7 +12,7 @@
 // }
 
 use serde::{Serialize, Deserialize};
-use bevy::reflect::TypeUuid;
+use bevy::{asset::Asset, reflect::TypePath};
 use std::collections::HashMap;
 
 /// This file is a JSON schema of files created by LDtk level editor (https://ldtk.io).
@@ -20,8 +20,7 @@ use std::collections::HashMap;
 /// This is the root of any Project JSON file. It contains:  - the project settings, - an
 /// array of levels, - a group of definitions (that can probably be safely ignored for most
 /// users).
-#[derive(Serialize, Debug, Deserialize, TypeUuid)]
-#[uuid = "f6f79589-c8ba-4918-a97d-1d640e666239"]
+#[derive(Serialize, Debug, Deserialize, Asset, TypePath)]
 pub struct Project {
     /// Number of backup files to keep, if the `backupOnSave` is TRUE
     #[serde(rename = "backupLimit")]
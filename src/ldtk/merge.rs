@@ -0,0 +1,81 @@
+use bevy::math::{URect, UVec2};
+
+// Covers the filled cells of a `width` wide, row major grid with as few
+// rectangles as it greedily can. Each rectangle grows right as far as it can
+// from its top left cell, then down while the whole span below is filled.
+// With `vertical` off rectangles are only ever one row tall.
+pub fn merge_cells(cells: &[bool], width: u32, vertical: bool) -> Vec<URect> {
+    let width = width as usize;
+    if width == 0 {
+        return Vec::new();
+    }
+    let height = cells.len() / width;
+    let mut used = vec![false; cells.len()];
+    let free = |used: &[bool], x: usize, y: usize| cells[y * width + x] && !used[y * width + x];
+
+    let mut rects = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !free(&used, x, y) {
+                continue;
+            }
+
+            let mut right = x + 1;
+            while right < width && free(&used, right, y) {
+                right += 1;
+            }
+
+            let mut bottom = y + 1;
+            while vertical && bottom < height && (x..right).all(|column| free(&used, column, bottom)) {
+                bottom += 1;
+            }
+
+            for row in y..bottom {
+                for column in x..right {
+                    used[row * width + column] = true;
+                }
+            }
+            rects.push(URect::from_corners(UVec2::new(x as u32, y as u32), UVec2::new(right as u32, bottom as u32)));
+        }
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(min: (u32, u32), max: (u32, u32)) -> URect {
+        URect::from_corners(UVec2::new(min.0, min.1), UVec2::new(max.0, max.1))
+    }
+
+    #[test]
+    fn empty_grids_have_no_rects() {
+        assert!(merge_cells(&[], 0, true).is_empty());
+        assert!(merge_cells(&[false; 6], 3, true).is_empty());
+    }
+
+    #[test]
+    fn touching_cells_merge_into_one_rect() {
+        assert_eq!(merge_cells(&[true; 6], 3, true), vec![rect((0, 0), (3, 2))]);
+    }
+
+    #[test]
+    fn without_vertical_rects_stay_one_row_tall() {
+        assert_eq!(merge_cells(&[true; 6], 3, false), vec![rect((0, 0), (3, 1)), rect((0, 1), (3, 2))]);
+    }
+
+    #[test]
+    fn gaps_split_rects() {
+        let cells = [true, false, true];
+        assert_eq!(merge_cells(&cells, 3, true), vec![rect((0, 0), (1, 1)), rect((2, 0), (3, 1))]);
+    }
+
+    #[test]
+    fn rects_only_grow_down_over_full_spans() {
+        // ##
+        // #.
+        let cells = [true, true, true, false];
+        assert_eq!(merge_cells(&cells, 2, true), vec![rect((0, 0), (2, 1)), rect((0, 1), (1, 2))]);
+    }
+}
@@ -1,22 +1,32 @@
 use bevy::{
     prelude::*,
-    asset::{AssetLoader, LoadedAsset}
+    asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader},
+    utils::BoxedFuture,
 };
+// Generated from the LDtk json schema, keep lints off so it can be regenerated as is
+#[allow(clippy::all)]
 pub mod ldtk_json;
+pub mod merge;
 
 #[derive(Default)]
 pub struct LdtkAssetLoader;
 
 impl AssetLoader for LdtkAssetLoader {
+    type Asset = ldtk_json::Project;
+    type Settings = ();
+    type Error = anyhow::Error;
+
     fn load<'a>(
         &'a self,
-        bytes: &'a [u8],
-        load_context: &'a mut bevy::asset::LoadContext,
-    ) -> bevy::asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
         Box::pin(async move {
-            let custom_asset = serde_json::from_slice::<ldtk_json::Project>(bytes)?;
-            load_context.set_default_asset(LoadedAsset::new(custom_asset));
-            Ok(())
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let custom_asset = serde_json::from_slice::<ldtk_json::Project>(&bytes)?;
+            Ok(custom_asset)
         })
     }
 
@@ -25,11 +35,25 @@ impl AssetLoader for LdtkAssetLoader {
     }
 }
 
-pub struct LdtkLoaderPlugin;
+// How the level spawns the "Colliders" layer. `Greedy` joins grid aligned
+// colliders of the same kind that touch into as few bodies as it can, so a
+// long floor is a handful of AABBs instead of one per tile.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColliderMerge {
+    Off,
+    #[default]
+    Greedy
+}
+
+#[derive(Default)]
+pub struct LdtkLoaderPlugin {
+    pub collider_merge: ColliderMerge
+}
 
 impl Plugin for LdtkLoaderPlugin {
-    fn build(&self, app: &mut AppBuilder) {
-        app.add_asset::<ldtk_json::Project>();
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.collider_merge);
+        app.init_asset::<ldtk_json::Project>();
         app.init_asset_loader::<LdtkAssetLoader>();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,957 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::EntityCommands, math::URect, prelude::*};
+
+use crate::{audio::zones::{AudioZone, spawn_audio_zone}, animation::{AnimatedSpriteBundle, AnimationController, AnimationTimer, Col, Row, SpriteSheetDefinition, SpriteSheetGrids, frame_boxes::FrameColliders, sockets::AnimationSockets}, camera::{CameraTarget, MainCamera, parallax::ParallaxLayer}, ldtk::{ColliderMerge, ldtk_json::{EntityInstance, LayerDefinition, LayerInstance, Project, TileInstance}, merge::merge_cells}, objective::{Objective, ObjectiveKind}, physics::{actor_collision::CollideWithActors, 
+        activation::ActivationSource,
+        body::{BodyBundle, BodyType, Position, RootMotion, Velocity},
+        controller::{CharacterController, OrientToGravity},
+        collision::{AABB, OneWay, PhysicsMaterial, Slope, SurfaceVelocity},
+        gravity::{Gravity, GravityZone},
+        solid::OnSquished,
+        trigger::{Sensor, TriggerEnter},
+    }, player::{Health, PlayerBundle, PlayerInput, character::{CharacterDef, CharacterSelection}, player_physics::PlayerGlideParams, carry::Carrier, rewind::Rewind, throw::BombThrower}, error::{ErrorSource, GameError}, preload::GameAssets, silhouette::Silhouette, spawn_effect::{DespawnEffect, SpawnEffect}, timeline::{TimelineFlags, TimelinePlayer}};
+
+use self::mood::{AmbientTint, AmbientTinted, LevelMood, apply_ambient_tint, level_tint, pick_level_mood};
+
+pub mod mood;
+
+// Timelines target the player by this, whichever character was picked
+pub const PLAYER_NAME: &str = "Player";
+
+const INTRO_TIMELINE: &str = "timelines/intro.timeline.ron";
+
+#[derive(Resource, Clone)]
+pub struct LdtkMapAssets(HashMap<i32, Handle<TextureAtlas>>);
+
+// Which map in GameAssets gets played, mod maps are under `mods://pack/path`
+#[derive(Resource, Debug, Clone)]
+pub struct MapSelection(pub String);
+
+impl Default for MapSelection {
+    fn default() -> Self {
+        MapSelection("maps/test-world2.ldtk".to_string())
+    }
+}
+
+#[derive(Resource)]
+pub struct Map {
+    pub ldtk_file: Handle<Project>,
+    pub path: String,
+    pub redraw: bool,
+    pub current_level: usize,
+}
+
+#[derive(States, Default, Debug, Clone, Eq, PartialEq, Hash)]
+pub enum AppState {
+    #[default]
+    Loading,
+    // Picking characters before the level spawns, skipped with only one to pick
+    CharacterSelect,
+    InGame,
+    // Rolls after the game is completed, back to InGame once done
+    Credits,
+    // Lists what went wrong, see GameError. Continuing goes back to whichever
+    // state it came from.
+    Error
+}
+
+// Sent when the player reaches the final level exit
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameCompleted;
+
+// Exits lead nowhere yet since the world is a single level, so for now every
+// exit is the final one
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LevelExit;
+
+// Gives whichever player touches it the glide ability, then goes away
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GlidePickup;
+
+#[derive(Clone, Copy)]
+struct LayerInfo {
+    _grid_width: i32,
+    _grid_height: i32,
+    grid_cell_size: i32,
+    _z_index: i32,
+    px_width: f32,
+    px_height: f32,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Scale(pub f32);
+
+// Sprite sheet atlas for each character in the manifest
+#[derive(Resource)]
+pub struct CharacterAtlases(pub HashMap<AssetId<CharacterDef>, Handle<TextureAtlas>>);
+
+#[derive(Resource)]
+pub struct Backgrounds {
+    pub bgs: Vec<(Handle<Image>, i32, f32)>
+}
+
+// LDtk provides pixel locations starting in the top left. For Bevy we need to
+// flip the Y axis and offset from the center of the screen.
+fn convert_to_world(
+    width: f32,
+    height: f32,
+    grid_size: i32,
+    scale: f32,
+    x: i32,
+    y: i32,
+    z: i32,
+) -> Vec3 {
+    let world_x = (x as f32 * scale) + (grid_size as f32 * scale / 2.) - (width / 2.);
+    let world_y = -(y as f32 * scale) - (grid_size as f32 * scale / 2.) + (height / 2.);
+    let world_z = z as f32;
+    Vec3::new(world_x, world_y, world_z)
+}
+
+// Spawn a tile. Check to see if it needs to flip on the x and/or y axis before spawning.
+fn spawn_tile(
+    layer_info: LayerInfo,
+    tile: &TileInstance,
+    level_world_pos: Vec2,
+    commands: &mut Commands,
+    handle: Handle<TextureAtlas>,
+    scale: &Scale
+) {
+    let mut flip_x = false;
+    let mut flip_y = false;
+    match tile.f {
+        1 => flip_x = true,
+        2 => flip_y = true,
+        3 => {
+            flip_x = true;
+            flip_y = true
+        }
+        _ => (),
+    }
+    commands.spawn(SpriteSheetBundle {
+        transform: Transform {
+            translation: convert_to_world(
+                layer_info.px_width,
+                layer_info.px_height,
+                layer_info.grid_cell_size,
+                scale.0,
+                (tile.px[0] as f32 + level_world_pos.x) as i32,
+                (tile.px[1] as f32 + level_world_pos.y) as i32,
+                500,
+            ),
+            scale: Vec3::splat(scale.0),
+            ..Default::default()
+        },
+        sprite: TextureAtlasSprite {
+            index: tile.t as usize,
+            flip_x,
+            flip_y,
+            ..Default::default()
+        },
+        texture_atlas: handle,
+        ..Default::default()
+    })
+    .insert(AmbientTinted::default());
+}
+
+pub fn convert_ldtk_entity_to_bevy(
+    position: Vec2,
+    size: Vec2,
+    layer_size: Vec2,
+    scale: f32,
+) -> (Vec2, Vec2) {
+    let ldtk_half_extents = size / 2.0;
+    let ldtk_position = position + ldtk_half_extents;
+
+    (
+        Vec2::new(
+            (ldtk_position.x * scale) - (layer_size.x / 2.),
+            -(ldtk_position.y * scale) + (layer_size.y / 2.),
+        ),
+        ldtk_half_extents * scale,
+    )
+}
+
+fn sprite_flip(mut sprite_query: Query<(&Velocity, &mut TextureAtlasSprite, Option<&Gravity>, Option<&Transform>)>) {
+    for (vel, mut sprite, gravity, transform) in sprite_query.iter_mut() {
+        // Face the way the sprite is moving after it gets turned to stand on a wall
+        let facing = transform.map_or(Vec3::X, |transform| transform.rotation * Vec3::X).truncate();
+        let walking = vel.0.dot(facing);
+        if walking < 0.0 {
+            sprite.flip_x = true;
+        } else if walking > 0.0 {
+            sprite.flip_x = false;
+        }
+        // Stand on the ceiling when gravity is flipped
+        sprite.flip_y = gravity.is_some_and(|gravity| gravity.up() < 0.0);
+    }
+}
+
+fn spawn_collider_body<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    position: Vec2,
+    half_extents: Vec2
+) -> EntityCommands<'w, 's, 'a> {
+    let mut collider = commands.spawn(BodyBundle {
+        position: Position(position),
+        ..Default::default()
+    });
+    collider.insert(AABB {
+        position: IVec2::ZERO,
+        half_size: IVec2::new(
+            half_extents.x.round() as i32,
+            half_extents.y.round() as i32,
+        ),
+    });
+    collider
+}
+
+fn spawn_collider(
+    commands: &mut Commands,
+    position: Vec2,
+    half_extents: Vec2,
+    entity: &EntityInstance
+) {
+    let mut collider = spawn_collider_body(commands, position, half_extents);
+
+    // "OneWay" collider entities are jump through platforms, "Sensor" ones are
+    // trigger volumes that report actors passing through them. "GravityZone"
+    // sensors take a "Scale" float and a "Flipped" bool field.
+    match &entity.identifier[..] {
+        "OneWay" => { collider.insert(OneWay); }
+        "Sensor" => { collider.insert(Sensor); }
+        "GravityZone" => {
+            collider.insert((Sensor, GravityZone {
+                scale: float_field(entity, "Scale").unwrap_or(1.0),
+                direction: if bool_field(entity, "Flipped").unwrap_or(false) { Vec2::Y } else { Vec2::NEG_Y }
+            }));
+        }
+        _ => {}
+    }
+
+    if let Some(slope) = slope_from_fields(entity) {
+        collider.insert(slope);
+    }
+
+    if let Some(material) = material_from_fields(entity) {
+        collider.insert(material);
+    }
+
+    // Conveyor belts, positive moves actors to the right
+    if let Some(speed) = float_field(entity, "ConveyorSpeed") {
+        collider.insert(SurfaceVelocity(Vec2::new(speed, 0.0)));
+    }
+}
+
+// Colliders from the "Colliders" layer as (position, size, entity) in LDtk
+// pixels. When merging, grid aligned colliders with the same identifier and
+// fields get joined up and spawned with the first one's settings. Slopes and
+// sensors stay as they are since each one means something on its own, and one
+// way platforms are only joined sideways so stacked ones can each be landed on.
+fn collider_rects(entities: &[EntityInstance], grid_size: i64, merge: ColliderMerge) -> Vec<(Vec2, Vec2, &EntityInstance)> {
+    fn as_is(entity: &EntityInstance) -> (Vec2, Vec2, &EntityInstance) {
+        (Vec2::new(entity.px[0] as f32, entity.px[1] as f32), Vec2::new(entity.width as f32, entity.height as f32), entity)
+    }
+    let mergeable = |entity: &EntityInstance| {
+        let aligned = [entity.px[0], entity.px[1], entity.width, entity.height].iter().all(|value| value % grid_size == 0);
+        aligned && entity.width > 0 && entity.height > 0 && !matches!(&entity.identifier[..], "Slope" | "Sensor" | "GravityZone")
+    };
+    if merge == ColliderMerge::Off || grid_size <= 0 {
+        return entities.iter().map(as_is).collect();
+    }
+
+    let mut rects = Vec::new();
+    let mut groups: Vec<(String, Vec<&EntityInstance>)> = Vec::new();
+    for entity in entities.iter() {
+        if !mergeable(entity) {
+            rects.push(as_is(entity));
+            continue;
+        }
+        let fields: Vec<String> = entity.field_instances.iter()
+            .map(|field| format!("{}={}", field.identifier, field.value.as_ref().map_or(String::new(), |value| value.to_string())))
+            .collect();
+        let key = format!("{}:{}", entity.identifier, fields.join(","));
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, group)) => group.push(entity),
+            None => groups.push((key, vec![entity]))
+        }
+    }
+
+    for (_, group) in groups.iter() {
+        // Grid covering just this group, in cells
+        let min = group.iter().fold(IVec2::MAX, |min, entity| min.min(IVec2::new(entity.px[0] as i32, entity.px[1] as i32)));
+        let max = group.iter().fold(IVec2::MIN, |max, entity| max.max(IVec2::new((entity.px[0] + entity.width) as i32, (entity.px[1] + entity.height) as i32)));
+        let cell = grid_size as i32;
+        let size = (max - min) / cell;
+
+        let mut cells = vec![false; (size.x * size.y) as usize];
+        for entity in group.iter() {
+            let start = (IVec2::new(entity.px[0] as i32, entity.px[1] as i32) - min) / cell;
+            let end = start + IVec2::new(entity.width as i32, entity.height as i32) / cell;
+            for y in start.y..end.y {
+                for x in start.x..end.x {
+                    cells[(y * size.x + x) as usize] = true;
+                }
+            }
+        }
+
+        let vertical = group[0].identifier != "OneWay";
+        for rect in merge_cells(&cells, size.x as u32, vertical) {
+            let position = min.as_vec2() + rect.min.as_vec2() * cell as f32;
+            rects.push((position, rect.size().as_vec2() * cell as f32, group[0]));
+        }
+    }
+    rects
+}
+
+// IntGrid values named "Solid" or "OneWay" in the layer definition become
+// colliders, merged into rectangles like the "Colliders" layer. Other values,
+// like the decorative "Wall" one, only drive auto tiling.
+fn spawn_int_grid_colliders(
+    commands: &mut Commands,
+    layer: &LayerInstance,
+    definition: &LayerDefinition,
+    level_position: Vec2,
+    layer_size: Vec2,
+    scale: f32,
+    merge: ColliderMerge
+) {
+    let width = layer.c_wid as u32;
+    for (name, one_way) in [("Solid", false), ("OneWay", true)].iter().copied() {
+        let values: Vec<i64> = definition.int_grid_values.iter()
+            .filter(|value| value.identifier.as_deref() == Some(name))
+            .map(|value| value.value)
+            .collect();
+        let cells: Vec<bool> = layer.int_grid_csv.iter().map(|value| values.contains(value)).collect();
+
+        let rects = match merge {
+            ColliderMerge::Greedy => merge_cells(&cells, width, !one_way),
+            ColliderMerge::Off => cells.iter().enumerate()
+                .filter(|(_, filled)| **filled)
+                .map(|(index, _)| {
+                    let cell = UVec2::new(index as u32 % width, index as u32 / width);
+                    URect::from_corners(cell, cell + UVec2::ONE)
+                })
+                .collect(),
+        };
+
+        let cell_size = layer.grid_size as f32;
+        for rect in rects {
+            let (position, half_extents) = convert_ldtk_entity_to_bevy(
+                rect.min.as_vec2() * cell_size + level_position,
+                rect.size().as_vec2() * cell_size,
+                layer_size,
+                scale,
+            );
+            let mut collider = spawn_collider_body(commands, position, half_extents);
+            if one_way {
+                collider.insert(OneWay);
+            }
+        }
+    }
+}
+
+fn float_field(entity: &EntityInstance, name: &str) -> Option<f32> {
+    entity.field_instances.iter()
+        .find(|field| field.identifier == name)
+        .and_then(|field| field.value.as_ref())
+        .and_then(|value| value.as_f64())
+        .map(|value| value as f32)
+}
+
+fn string_field<'a>(entity: &'a EntityInstance, name: &str) -> Option<&'a str> {
+    entity.field_instances.iter()
+        .find(|field| field.identifier == name)
+        .and_then(|field| field.value.as_ref())
+        .and_then(|value| value.as_str())
+}
+
+fn bool_field(entity: &EntityInstance, name: &str) -> Option<bool> {
+    entity.field_instances.iter()
+        .find(|field| field.identifier == name)
+        .and_then(|field| field.value.as_ref())
+        .and_then(|value| value.as_bool())
+}
+
+// "Slope" collider entities carry "Left" and "Right" float fields with the
+// surface height at each end as a fraction of the entity height
+fn slope_from_fields(entity: &EntityInstance) -> Option<Slope> {
+    if entity.identifier != "Slope" {
+        return None;
+    }
+
+    Some(Slope {
+        left: float_field(entity, "Left").unwrap_or(0.0),
+        right: float_field(entity, "Right").unwrap_or(1.0)
+    })
+}
+
+// Any collider can set "Friction" and "Restitution" float fields, missing
+// ones keep the default
+fn material_from_fields(entity: &EntityInstance) -> Option<PhysicsMaterial> {
+    let (friction, restitution) = (float_field(entity, "Friction"), float_field(entity, "Restitution"));
+    if friction.is_none() && restitution.is_none() {
+        return None;
+    }
+
+    let default = PhysicsMaterial::default();
+    Some(PhysicsMaterial {
+        friction: friction.unwrap_or(default.friction),
+        restitution: restitution.unwrap_or(default.restitution)
+    })
+}
+
+fn spawn_player(
+    commands: &mut Commands,
+    character: &CharacterDef,
+    texture_atlas: Handle<TextureAtlas>,
+    position: Vec2,
+    half_extents: Vec2,
+    scale: f32
+) {
+    let idle_row = character.animation_row("idle").unwrap_or(0);
+    let collider = AABB {
+        position: IVec2::ZERO,
+        half_size: half_extents.round().as_ivec2()
+    };
+
+    commands
+    .spawn(PlayerBundle {
+        health: Health(10u32),
+        body_bundle: BodyBundle {
+            body_type: BodyType::Actor,
+            position: Position(position),
+            ..Default::default()
+        },
+        collider,
+        animation: AnimatedSpriteBundle {
+            sprite_sheet: SpriteSheetBundle {
+                texture_atlas,
+                transform:Transform::from_translation(Vec3::new(0.0, 0.0, 500.0)).mul_transform(Transform::from_scale(
+                    Vec3::splat(scale))),
+                ..Default::default()
+            },
+            sprite_sheet_definitions:
+                SpriteSheetDefinition {
+                    animation_definitions: character.animations.clone(),
+                    rows: character.rows,
+                    columns: character.columns,
+                    transitions: character.transitions.clone(),
+                },
+            animation_timer: AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+            current_row: Row(idle_row), // Set it up as the idle animation right away
+            current_col: Col(0),
+            controller: AnimationController::new("idle"),
+        },
+        player_walk_params: character.movement.walk_params(),
+        player_jump_params: character.movement.jump_params(),
+        ..Default::default()
+    })
+    .insert((CameraTarget, ActivationSource, Silhouette::default()))
+    // Steps up two pixels of the art and slips past corners clipped by three
+    .insert(CharacterController::new((2.0 * scale) as u32, (3.0 * scale) as u32))
+    .insert(OnSquished::Kill)
+    // Walks on walls and ceilings when a zone turns gravity that way
+    .insert(OrientToGravity { collider })
+    // Can stand on enemies and other players instead of falling through them
+    .insert(CollideWithActors::Block)
+    .insert(Rewind::default())
+    .insert(BombThrower::default())
+    .insert(Carrier::default())
+    .insert(FrameColliders::default())
+    .insert(RootMotion::default())
+    .insert(AnimationSockets::default())
+    .insert(Name::new(PLAYER_NAME));
+
+    debug!("Spawned {} at {:?}", character.name, position);
+}
+
+// Plays the intro when the player first shows up, it sets `intro_seen` so
+// later respawns skip it
+fn start_intro(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    flags: Res<TimelineFlags>,
+    added_query: Query<(), Added<PlayerInput>>,
+    timeline_query: Query<&TimelinePlayer>
+) {
+    if added_query.is_empty() || flags.get("intro_seen") {
+        return;
+    }
+    let intro = game_assets.timeline(INTRO_TIMELINE);
+    if timeline_query.iter().all(|player| player.timeline != intro) {
+        commands.spawn(TimelinePlayer::new(intro));
+    }
+}
+
+fn spawn_objective(
+    commands: &mut Commands,
+    kind: ObjectiveKind,
+    position: Vec2,
+) {
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position.extend(500.0))),
+        Objective::new(kind),
+        SpawnEffect::default()
+    ));
+}
+
+fn spawn_exit(
+    commands: &mut Commands,
+    position: Vec2,
+    half_extents: Vec2,
+) {
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position.extend(500.0))),
+        Objective::new(ObjectiveKind::Exit),
+        BodyBundle {
+            position: Position(position),
+            ..Default::default()
+        },
+        AABB {
+            position: IVec2::ZERO,
+            half_size: half_extents.round().as_ivec2()
+        },
+        Sensor,
+        LevelExit
+    ));
+}
+
+fn spawn_glide_pickup(
+    commands: &mut Commands,
+    position: Vec2,
+    half_extents: Vec2,
+) {
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(position.extend(500.0))),
+        BodyBundle {
+            position: Position(position),
+            ..Default::default()
+        },
+        AABB {
+            position: IVec2::ZERO,
+            half_size: half_extents.round().as_ivec2()
+        },
+        Sensor,
+        GlidePickup,
+        SpawnEffect::default()
+    ));
+}
+
+fn collect_glide_pickup(
+    mut commands: Commands,
+    mut trigger_events: EventReader<TriggerEnter>,
+    pickup_query: Query<(), (With<GlidePickup>, Without<SpawnEffect>, Without<DespawnEffect>)>,
+    mut glide_query: Query<&mut PlayerGlideParams>
+) {
+    for event in trigger_events.read() {
+        if !pickup_query.contains(event.sensor) {
+            continue;
+        }
+        if let Ok(mut glide) = glide_query.get_mut(event.entity) {
+            glide.enabled = true;
+            commands.entity(event.sensor).insert(DespawnEffect::default());
+        }
+    }
+}
+
+fn complete_on_exit(
+    mut trigger_events: EventReader<TriggerEnter>,
+    mut game_completed: EventWriter<GameCompleted>,
+    exit_query: Query<(), With<LevelExit>>,
+    player_query: Query<(), With<PlayerInput>>
+) {
+    for event in trigger_events.read() {
+        if exit_query.contains(event.sensor) && player_query.contains(event.entity) {
+            game_completed.send(GameCompleted);
+        }
+    }
+}
+
+// Cuts every character's spritesheet into an atlas, character select can swap
+// between them without reloading anything
+fn setup_animation_assets(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    characters: Res<Assets<CharacterDef>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut sprite_sheet_grids: ResMut<SpriteSheetGrids>,
+) {
+    let mut character_atlases = CharacterAtlases(HashMap::new());
+    for handle in game_assets.characters.values() {
+        if let Some(character) = characters.get(handle) {
+            let atlas = sprite_sheet_grids.from_grid(&mut texture_atlases, character.spritesheet.clone(), character.tile_size, character.columns, character.rows);
+            character_atlases.0.insert(handle.id(), atlas);
+        }
+    }
+
+    commands.insert_resource(character_atlases);
+}
+
+fn load_tilemap(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    map_selection: Res<MapSelection>,
+) {
+    // Load up the map, tilesets are found from the folder it's in
+    let map = Map {
+        ldtk_file: game_assets.map(&map_selection.0),
+        path: map_selection.0.rfind('/').map_or(String::new(), |end| map_selection.0[..=end].to_string()),
+        redraw: true,
+        current_level: 0,
+    };
+
+    // Slap this bad boy into a resource
+    commands.insert_resource(map);
+}
+
+fn load_tilesets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    game_assets: Res<GameAssets>,
+    map: Res<Map>,
+    ldtk_maps: Res<Assets<Project>>,
+    mut state: ResMut<NextState<AppState>>,
+    mut error_events: EventWriter<GameError>
+) {
+    // Stay on the loading screen until everything in the manifest is in, or
+    // has failed and been reported
+    let failed = game_assets.failed(&asset_server);
+    for path in failed.iter() {
+        error_events.send(GameError::new(ErrorSource::Asset(path.clone()), "Failed to load, see the log for why"));
+    }
+    let (loaded, total) = game_assets.progress(&asset_server);
+    if loaded + failed.len() < total {
+        return;
+    }
+
+    // Go through and grab all the map tile sets
+    if let Some(ldtk_file) = ldtk_maps.get(&map.ldtk_file) {
+        let mut map_assets = LdtkMapAssets(HashMap::new());
+
+        for tileset in ldtk_file.defs.tilesets.iter() {
+            let path_to_asset = format!("{}{}", map.path, &tileset.rel_path[..]);
+            let texture_handle = asset_server.load(path_to_asset);
+
+            let texture_atlas = TextureAtlas::from_grid(
+                texture_handle,
+                Vec2::new(tileset.tile_grid_size as f32, tileset.tile_grid_size as f32),
+                (tileset.px_wid / tileset.tile_grid_size) as usize,
+                (tileset.px_hei / tileset.tile_grid_size) as usize,
+                None,
+                None,
+            );
+            let texture_atlas_handle = texture_atlases.add(texture_atlas);
+            map_assets
+                .0
+                .insert(tileset.uid as i32, texture_atlas_handle);
+        }
+
+        commands.insert_resource(map_assets);
+        state.set(AppState::CharacterSelect);
+    }
+
+    commands.insert_resource(Backgrounds {
+        bgs: vec![
+            (game_assets.texture("tiles and background_foreground/bg_0.png"), 100, 0.9),
+            (game_assets.texture("tiles and background_foreground/bg_1.png"), 200, 0.4),
+            (game_assets.texture("tiles and background_foreground/bg_2.png"), 500, 0.2),
+            (game_assets.texture("tiles and background_foreground/fg_1.png"), 501, -0.2),
+        ]
+    });
+}
+
+fn update_ldtk_map(
+    mut commands: Commands,
+    mut map: ResMut<Map>,
+    backgrounds: Res<Backgrounds>,
+    map_assets: Res<LdtkMapAssets>,
+    game_assets: Res<GameAssets>,
+    characters: Res<Assets<CharacterDef>>,
+    character_atlases: Res<CharacterAtlases>,
+    character_selection: Res<CharacterSelection>,
+    scale: Res<Scale>,
+    collider_merge: Res<ColliderMerge>,
+    ldtk_maps: Res<Assets<Project>>,
+    mut error_events: EventWriter<GameError>
+) {
+    if !map.redraw {
+        return;
+    }
+
+    let mut cam = Camera2dBundle::default();
+    cam.transform.translation.z = 800.0;
+    commands.spawn(cam).insert((MainCamera, ActivationSource));
+
+    for background_handle in &backgrounds.bgs {
+        let mut transform = Transform::from_scale(Vec3::splat(scale.0 * 2.0));
+        transform.translation.z = background_handle.1 as f32;
+        commands.spawn(SpriteBundle {
+            texture: background_handle.0.clone(),
+            transform,
+            ..Default::default()
+        }).insert((
+            ParallaxLayer {
+                parallax_factor: background_handle.2,
+                ..Default::default()
+            },
+            AmbientTinted::default()
+        ));
+    }
+
+    if let Some(ldtk_file) = ldtk_maps.get(&map.ldtk_file) {
+        let _map_span = info_span!("spawn_map").entered();
+        info!("Spawning {} levels", ldtk_file.levels.len());
+        match ldtk_file.levels.first().map(|level| (level, Color::hex(level.bg_color.trim_start_matches('#')))) {
+            Some((_, Ok(color))) => commands.insert_resource(ClearColor(color)),
+            Some((level, Err(_))) => error_events.send(GameError::new(ErrorSource::Level(level.identifier.clone()), format!("Background color {} isn't a hex color", level.bg_color))),
+            None => error_events.send(GameError::new(ErrorSource::Asset(map.ldtk_file.path().map_or(map.path.clone(), |path| path.to_string())), "The map has no levels")),
+        }
+
+        for i in 0..ldtk_file.levels.len() {
+            let level_ldtk_world_pos = Vec2::new(
+                ldtk_file.levels[i].world_x as f32,
+                ldtk_file.levels[i].world_y as f32,
+            );
+            let _level_span = debug_span!("level", identifier = %ldtk_file.levels[i].identifier).entered();
+            debug!("LDtk world position {:?}", level_ldtk_world_pos);
+
+            let level_size = Vec2::new(ldtk_file.levels[i].px_wid as f32, ldtk_file.levels[i].px_hei as f32);
+            let (mood_position, mood_half_extent) = convert_ldtk_entity_to_bevy(level_ldtk_world_pos, level_size, level_size * scale.0, scale.0);
+            commands.spawn(LevelMood {
+                level: ldtk_file.levels[i].identifier.clone(),
+                bounds: Rect::from_center_half_size(mood_position, mood_half_extent),
+                tint: level_tint(&ldtk_file.levels[i])
+            });
+            // Levels saved to their own files have no layers in the project
+            let layer_instances = match ldtk_file.levels[i].layer_instances.as_ref() {
+                Some(layer_instances) => layer_instances,
+                None => {
+                    error_events.send(GameError::new(ErrorSource::Level(ldtk_file.levels[i].identifier.clone()), "No layers, levels saved to separate files aren't supported"));
+                    continue;
+                }
+            };
+            for (idx, layer) in layer_instances
+                .iter()
+                .enumerate()
+                .rev()
+            {
+                let layer_info = LayerInfo {
+                    _grid_width: layer.c_wid as i32,
+                    _grid_height: layer.c_hei as i32,
+                    grid_cell_size: layer.grid_size as i32,
+                    _z_index: 50 - idx as i32,
+                    // todo gotta swap this over from a hard coded scale
+                    px_width: layer.c_wid as f32 * (layer.grid_size as f32 * scale.0),
+                    px_height: layer.c_hei as f32 * (layer.grid_size as f32 * scale.0),
+                };
+                let _layer_span = debug_span!("layer", identifier = %layer.identifier).entered();
+                let layer_source = || ErrorSource::Layer(ldtk_file.levels[i].identifier.clone(), layer.identifier.clone());
+
+                match &layer.layer_instance_type[..] {
+                    "Tiles" => {
+                        if let Some(layer_tileset_def_uid) = layer.tileset_def_uid {
+                            debug!("Spawning tiles");
+                            match map_assets.0.get(&(layer_tileset_def_uid as i32)) {
+                                Some(atlas) => for tile in layer.grid_tiles.iter() {
+                                    spawn_tile(
+                                        layer_info,
+                                        tile,
+                                        level_ldtk_world_pos,
+                                        &mut commands,
+                                        atlas.clone(),
+                                        &scale
+                                    )
+                                },
+                                None => error_events.send(GameError::new(layer_source(), format!("Tileset {} isn't in the map", layer_tileset_def_uid))),
+                            }
+                        }
+                    }
+                    "AutoLayer" => {}
+                    "IntGrid" => {
+                        if let Some(layer_tileset_def_uid) = layer.tileset_def_uid {
+                            debug!("Spawning tiles");
+                            match map_assets.0.get(&(layer_tileset_def_uid as i32)) {
+                                Some(atlas) => for tile in layer.auto_layer_tiles.iter() {
+                                    spawn_tile(
+                                        layer_info,
+                                        tile,
+                                        level_ldtk_world_pos,
+                                        &mut commands,
+                                        atlas.clone(),
+                                        &scale
+                                    )
+                                },
+                                None => error_events.send(GameError::new(layer_source(), format!("Tileset {} isn't in the map", layer_tileset_def_uid))),
+                            }
+                        }
+                        if let Some(definition) = ldtk_file.defs.layers.iter().find(|definition| definition.uid == layer.layer_def_uid) {
+                            spawn_int_grid_colliders(
+                                &mut commands,
+                                layer,
+                                definition,
+                                level_ldtk_world_pos,
+                                Vec2::new(layer_info.px_width, layer_info.px_height),
+                                scale.0,
+                                *collider_merge
+                            );
+                        }
+                    }
+                    "Entities" => {
+                        debug!("Spawning entities");
+                        match &layer.identifier[..] {
+                            "Colliders" => {
+                                let rects = collider_rects(&layer.entity_instances, layer.grid_size, *collider_merge);
+                                debug!("{} colliders from {} entities", rects.len(), layer.entity_instances.len());
+                                for (px, size, entity) in rects {
+                                    let (bevy_pos, bevy_half_extent) = convert_ldtk_entity_to_bevy(
+                                        px + level_ldtk_world_pos,
+                                        size,
+                                        Vec2::new(layer_info.px_width, layer_info.px_height),
+                                        scale.0,
+                                    );
+
+                                    trace!("Collider at {:?} with half size {:?}", bevy_pos, bevy_half_extent);
+
+                                    spawn_collider(&mut commands, bevy_pos, bevy_half_extent, entity);
+                                }
+                            }
+                            "Entities" => {
+                                for entity in layer.entity_instances.iter() {
+
+                                    let (bevy_pos, bevy_half_extent) = convert_ldtk_entity_to_bevy(
+                                        Vec2::new(entity.px[0] as f32, entity.px[1] as f32)
+                                            + level_ldtk_world_pos,
+                                        Vec2::new(entity.width as f32, entity.height as f32),
+                                        Vec2::new(layer_info.px_width, layer_info.px_height),
+                                        scale.0,
+                                    );
+
+                                    trace!("{} at {:?} with half size {:?}", entity.identifier, bevy_pos, bevy_half_extent);
+
+                                    match &entity.identifier[..] {
+                                        "Player" => {
+                                            let handle = game_assets.character(character_selection.character(0));
+                                            match (characters.get(&handle), character_atlases.0.get(&handle.id())) {
+                                                (Some(character), Some(atlas)) => spawn_player(&mut commands, character, atlas.clone(), bevy_pos, bevy_half_extent, scale.0),
+                                                _ => error_events.send(GameError::new(ErrorSource::Entity(entity.identifier.clone()), format!("Character {} isn't loaded, not spawning the player", character_selection.character(0))))
+                                            }
+                                        }
+                                        "Exit" => spawn_exit(&mut commands, bevy_pos, bevy_half_extent),
+                                        "Glide" => spawn_glide_pickup(&mut commands, bevy_pos, bevy_half_extent),
+                                        "Key" => spawn_objective(&mut commands, ObjectiveKind::Key, bevy_pos),
+                                        "Boss" => spawn_objective(&mut commands, ObjectiveKind::Boss, bevy_pos),
+                                        // Ambient loops, "Sound" is a path in the assets folder
+                                        "AudioZone" => match string_field(entity, "Sound") {
+                                            Some(sound) => spawn_audio_zone(&mut commands, AudioZone {
+                                                sound: sound.to_string(),
+                                                volume: float_field(entity, "Volume").unwrap_or(1.0),
+                                                falloff: float_field(entity, "Falloff").unwrap_or(32.0) * scale.0,
+                                                crossfade: float_field(entity, "Crossfade").unwrap_or(1.0),
+                                                bounds: Rect::from_center_half_size(bevy_pos, bevy_half_extent)
+                                            }),
+                                            None => error_events.send(GameError::new(ErrorSource::Entity(entity.identifier.clone()), "Audio zone without a Sound field"))
+                                        },
+                                        _ => {}
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    layer_type => error_events.send(GameError::new(layer_source(), format!("Unknown layer type {}", layer_type))),
+                }
+            }
+        }
+        map.redraw = false;
+
+    } else {
+        map.redraw = true;
+    }
+}
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        // states
+        app.add_state::<AppState>()
+            .add_event::<GameCompleted>()
+            .init_resource::<MapSelection>()
+            .init_resource::<AmbientTint>()
+            .init_resource::<TimelineFlags>();
+
+        // Loading state, waits for the preloader to list the manifest's assets
+        app.add_systems(Update, (
+            load_tilemap.run_if(resource_added::<GameAssets>()),
+            load_tilesets.run_if(resource_exists::<Map>())
+        ).chain().run_if(in_state(AppState::Loading)));
+
+        // Character select previews need the atlases
+        app.add_systems(OnEnter(AppState::CharacterSelect), setup_animation_assets);
+
+        // InGame state
+        app.add_systems(Update, (update_ldtk_map, sprite_flip, complete_on_exit, collect_glide_pickup, start_intro, (pick_level_mood, apply_ambient_tint).chain()).run_if(in_state(AppState::InGame)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(identifier: &str, px: (i64, i64), size: (i64, i64)) -> EntityInstance {
+        EntityInstance {
+            grid: vec![px.0 / 16, px.1 / 16],
+            identifier: identifier.to_string(),
+            pivot: vec![0.0, 0.0],
+            tile: None,
+            def_uid: 0,
+            field_instances: Vec::new(),
+            height: size.1,
+            px: vec![px.0, px.1],
+            width: size.0
+        }
+    }
+
+    fn rects(entities: &[EntityInstance], merge: ColliderMerge) -> Vec<(Vec2, Vec2, String)> {
+        collider_rects(entities, 16, merge).into_iter().map(|(position, size, entity)| (position, size, entity.identifier.clone())).collect()
+    }
+
+    #[test]
+    fn touching_colliders_merge() {
+        let entities = [entity("Collider", (0, 0), (16, 16)), entity("Collider", (16, 0), (16, 16)), entity("Collider", (0, 16), (32, 16))];
+        assert_eq!(rects(&entities, ColliderMerge::Greedy), vec![(Vec2::ZERO, Vec2::new(32.0, 32.0), "Collider".to_string())]);
+    }
+
+    #[test]
+    fn different_kinds_stay_apart() {
+        let entities = [entity("Collider", (0, 0), (16, 16)), entity("OneWay", (16, 0), (16, 16))];
+        assert_eq!(rects(&entities, ColliderMerge::Greedy), vec![
+            (Vec2::ZERO, Vec2::splat(16.0), "Collider".to_string()),
+            (Vec2::new(16.0, 0.0), Vec2::splat(16.0), "OneWay".to_string())
+        ]);
+    }
+
+    #[test]
+    fn one_ways_only_merge_along_rows() {
+        let entities = [entity("OneWay", (0, 0), (16, 16)), entity("OneWay", (0, 16), (16, 16))];
+        assert_eq!(rects(&entities, ColliderMerge::Greedy).len(), 2);
+    }
+
+    #[test]
+    fn slopes_and_off_grid_colliders_are_kept_as_is() {
+        let entities = [entity("Slope", (0, 0), (16, 16)), entity("Slope", (16, 0), (16, 16)), entity("Collider", (40, 0), (8, 16))];
+        assert_eq!(rects(&entities, ColliderMerge::Greedy), vec![
+            (Vec2::ZERO, Vec2::splat(16.0), "Slope".to_string()),
+            (Vec2::new(16.0, 0.0), Vec2::splat(16.0), "Slope".to_string()),
+            (Vec2::new(40.0, 0.0), Vec2::new(8.0, 16.0), "Collider".to_string())
+        ]);
+    }
+
+    #[test]
+    fn merging_off_keeps_every_collider() {
+        let entities = [entity("Collider", (0, 0), (16, 16)), entity("Collider", (16, 0), (16, 16))];
+        assert_eq!(rects(&entities, ColliderMerge::Off).len(), 2);
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+
+use crate::{camera::MainCamera, ldtk::ldtk_json::Level};
+
+// Spawned for each level with its LDtk identifier, its bounds in world space
+// and the tint from its "Tint" color field, white when the level doesn't set one
+#[derive(Component, Debug, Clone)]
+pub struct LevelMood {
+    pub level: String,
+    pub bounds: Rect,
+    pub tint: Color
+}
+
+// Put on sprites the level tint applies to, holding the color they'd have
+// without it. Characters are left out so they stay readable in dark levels.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AmbientTinted(pub Color);
+
+impl Default for AmbientTinted {
+    fn default() -> Self {
+        AmbientTinted(Color::WHITE)
+    }
+}
+
+// The tint every AmbientTinted sprite is multiplied by. Heading into another
+// level fades from whatever the tint was to that level's over the
+// `transition` timer.
+#[derive(Resource, Debug)]
+pub struct AmbientTint {
+    pub transition: Timer,
+    from: Color,
+    target: Color,
+    current: Color
+}
+
+impl Default for AmbientTint {
+    fn default() -> Self {
+        AmbientTint {
+            transition: Timer::from_seconds(1.5, TimerMode::Once),
+            from: Color::WHITE,
+            target: Color::WHITE,
+            current: Color::WHITE
+        }
+    }
+}
+
+impl AmbientTint {
+    pub fn current(&self) -> Color {
+        self.current
+    }
+
+    pub fn fade_to(&mut self, target: Color) {
+        if target == self.target {
+            return;
+        }
+        self.from = self.current;
+        self.target = target;
+        self.transition.reset();
+    }
+}
+
+pub fn level_tint(level: &Level) -> Color {
+    level.field_instances.iter()
+        .find(|field| field.identifier == "Tint")
+        .and_then(|field| field.value.as_ref())
+        .and_then(|value| value.as_str())
+        .and_then(|hex| Color::hex(hex.trim_start_matches('#')).ok())
+        .unwrap_or(Color::WHITE)
+}
+
+fn multiply(base: Color, tint: Color) -> Color {
+    Color::from(Vec4::from(base.as_rgba_f32()) * Vec4::from(tint.as_rgba_f32()))
+}
+
+// Outside of every level the last tint is kept
+pub fn pick_level_mood(
+    mut ambient_tint: ResMut<AmbientTint>,
+    mood_query: Query<&LevelMood>,
+    camera_query: Query<&Transform, With<MainCamera>>
+) {
+    let camera = match camera_query.get_single() {
+        Ok(camera) => camera.translation.truncate(),
+        Err(_) => return,
+    };
+    if let Some(mood) = mood_query.iter().find(|mood| mood.bounds.contains(camera)) {
+        ambient_tint.fade_to(mood.tint);
+    }
+}
+
+pub fn apply_ambient_tint(
+    time: Res<Time>,
+    mut ambient_tint: ResMut<AmbientTint>,
+    mut sprite_query: Query<(&AmbientTinted, Option<&mut Sprite>, Option<&mut TextureAtlasSprite>)>
+) {
+    if !ambient_tint.transition.finished() {
+        ambient_tint.transition.tick(time.delta());
+        let t = ambient_tint.transition.percent();
+        let (from, target) = (Vec4::from(ambient_tint.from.as_rgba_f32()), Vec4::from(ambient_tint.target.as_rgba_f32()));
+        ambient_tint.current = Color::from(from.lerp(target, t));
+    }
+
+    // Only writing changed colors keeps change detection quiet on every tile
+    let tint = ambient_tint.current;
+    for (tinted, sprite, atlas_sprite) in sprite_query.iter_mut() {
+        let color = multiply(tinted.0, tint);
+        if let Some(mut sprite) = sprite {
+            if sprite.color != color {
+                sprite.color = color;
+            }
+        }
+        if let Some(mut sprite) = atlas_sprite {
+            if sprite.color != color {
+                sprite.color = color;
+            }
+        }
+    }
+}
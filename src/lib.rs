@@ -0,0 +1,72 @@
+// Bevy systems routinely take wide queries and many params
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+pub mod animation;
+pub mod audio;
+pub mod camera;
+pub mod control;
+pub mod credits;
+pub mod error;
+pub mod event_log;
+pub mod explosion;
+pub mod ldtk;
+pub mod level;
+pub mod logging;
+pub mod menu;
+pub mod mods;
+pub mod objective;
+pub mod pacing;
+pub mod physics;
+pub mod player;
+pub mod preload;
+#[cfg(feature = "presence")]
+pub mod presence;
+pub mod silhouette;
+pub mod spawn_effect;
+pub mod stats;
+pub mod timeline;
+
+pub mod prelude {
+    pub use crate::{
+        animation::{AnimatedSpriteBundle, AnimationController, AnimationDefinition, AnimationTransition, AnimationEvent, AnimationFinished, AnimationPlugin, AnimationTimeScale, AnimationTimer, Col, PlaybackSpeed, Row, SpriteSheetDefinition, SpriteSheetGrids, TransitionKind, TransitionTable, frame_boxes::{AttackPhase, FrameBox, FrameBoxDebugPlugin, FrameBoxKind, FrameCollider, FrameColliders, attack_phase}, layers::{AnimationLayer, AnimationLayerBundle}, palette::{Palette, PaletteMaterial, PaletteSwap}, sockets::{AnimationSockets, FrameSocket}},
+        audio::{AudioBus, AudioDebugPlugin, AudioMixer, AudioPlugin, AudioVolumes, DuckRule, Ducking, MixedSound, PlaySound, zones::{AudioZone, AudioZoneLevel}},
+        camera::{CameraFocus, CameraPlugin, CameraTarget, MainCamera, parallax::ParallaxLayer, shake::CameraShake, pixel_perfect::{GameView, PixelPerfectPlugin, PixelPerfectSettings}},
+        control::{ControlLockReason, ControlLockToken, PlayerControlLock, actions::PlayerActions, device::{ActiveInputDevice, InputDevice, InputDeviceChanged, InputDevicePlugin, InputSmoothing}, glyphs::{InputAction, InputGlyph, InputGlyphPlugin, InputGlyphs}, touch::{TouchControlSettings, TouchControls, TouchControlsPlugin}},
+        credits::{Credits, CreditsPlugin, CreditsSection},
+        error::{ErrorPlugin, ErrorSource, GameError, GameErrors},
+        event_log::{EventLog, EventLogPlugin, LogCategory, LogEntry, LogEvent, LogEventAppExt},
+        explosion::{Explosion, ExplosionAssets, ExplosionHit, ExplosionPlugin},
+        ldtk::{ColliderMerge, LdtkLoaderPlugin},
+        level::{AppState, CharacterAtlases, GameCompleted, GlidePickup, LevelExit, LevelPlugin, MapSelection, Scale, mood::{AmbientTint, AmbientTinted, LevelMood}},
+        logging::{DEFAULT_LOG_FILTER, LOG_FILTER_ENV, log_plugin},
+        menu::MenuPlugin,
+        mods::{ModPacks, ModsPlugin, pack_path, resolve_path},
+        objective::{Objective, ObjectiveKind, ObjectiveMarkerEvent, ObjectiveMarkerPlugin},
+        pacing::{FramePacing, FramePacingDebugPlugin, FramePacingPlugin, FrameRates},
+        physics::{
+            activation::{ActivationSettings, ActivationSource, Inactive, KeepAwake, WakeBody},
+            actor_collision::{CollideWithActors, nearby_actors},
+            sleep::{SleepSettings, Sleeping},
+            shape::{CapsuleCollider, CircleCollider, RoundShape},
+            DebugPhysicsPlugin, PhysicsConfig, PhysicsDebugConfig, PhysicsPlugin, PhysicsSettings, PhysicsStages, PhysicsStep, StepSystemLabels,
+            body::{Acceleration, BodyBundle, BodyType, CustomIntegration, GravityDirection, GravityScale, Held, Position, Remainder, RootMotion, SpeedLimits, Velocity},
+            controller::{CharacterController, ControllerMove, Grounded, MoveFrame, OrientToGravity, TouchingFlags},
+            cast_debug::{CastDebugHistory, CastHit, CastLog, CastRecord, CastShape},
+            gravity::{Gravity, GravityZone, apply_gravity},
+            integrator::{IntegrationScheme, clamp_fall, integrate, integrate_acceleration, integrate_bodies},
+            collision::{AABB, Collision, CollisionEvent, CollisionExclusion, CollisionExclusions, CollisionResult, DropThrough, OneWay, PhysicsMaterial, Slope, SolidCollider, SurfaceVelocity, SweepHit, cast_aabb},
+            solid::{OnSquished, Squished, is_riding},
+            solid_cache::SolidColliderCache,
+            world::{OverlapHit, PhysicsQuery, PhysicsWorld, QueryMask, RayHit, WorldCollider},
+            trigger::{Sensor, TriggerContacts, TriggerEnter, TriggerExit, TriggerStay},
+        },
+        player::{Health, PlayerBundle, PlayerDebugPlugin, PlayerInput, PlayerInputBuffer, PlayerPlugin, carry::{Carrier, Carryable}, character::{CharacterDef, CharacterMovement, CharacterSelection}, player_physics::{PlayerGlideParams, PlayerJumpParams, PlayerWalkParams}, rewind::{Rewind, RewindFrame}, throw::{Bomb, BombAssets, BombThrower, predict_throw_arc}},
+        preload::{AssetManifest, GameAssets, PreloadPlugin},
+        silhouette::{Silhouette, SilhouetteMaterial, SilhouettePlugin},
+        spawn_effect::{DespawnEffect, SpawnEffect, SpawnEffectPlugin},
+        stats::{EntityStats, EntityStatsPlugin},
+        timeline::{Timeline, TimelineFinished, TimelineFlags, TimelinePlayer, TimelinePlugin},
+    };
+    #[cfg(feature = "presence")]
+    pub use crate::presence::{NoPresence, Presence, PresenceBackend, PresenceBackends, PresencePlugin, presence_mode};
+}
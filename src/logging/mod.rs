@@ -0,0 +1,23 @@
+use bevy::log::{Level, LogPlugin};
+
+// Log levels per module, in the same format as RUST_LOG. Setting RUST_LOG
+// replaces these entirely, PLATFORMER_LOG is added on top of them instead, so
+// `PLATFORMER_LOG=platformer::level=trace` shows every tile, collider and
+// entity the map spawns without turning up everything else.
+pub const DEFAULT_LOG_FILTER: &str = "wgpu=error,naga=warn,platformer=info,platformer::level=info";
+pub const LOG_FILTER_ENV: &str = "PLATFORMER_LOG";
+
+pub fn log_filter() -> String {
+    match std::env::var(LOG_FILTER_ENV) {
+        Ok(extra) if !extra.is_empty() => format!("{},{}", DEFAULT_LOG_FILTER, extra),
+        _ => DEFAULT_LOG_FILTER.to_string(),
+    }
+}
+
+// Goes in place of the default LogPlugin, `DefaultPlugins.set(log_plugin())`
+pub fn log_plugin() -> LogPlugin {
+    LogPlugin {
+        filter: log_filter(),
+        level: Level::INFO
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+
+use crate::{animation::AnimationTimer, control::{actions::PlayerActions, glyphs::InputAction}, level::{AppState, CharacterAtlases}, player::{PlayerInput, character::{CharacterDef, CharacterSelection}}, preload::GameAssets};
+
+// Root of the character select UI, everything under it goes when the level starts
+#[derive(Component)]
+struct CharacterSelectRoot;
+
+// One local player's pick, cycled with their left and right keys and locked
+// in with jump
+#[derive(Component)]
+struct CharacterCursor {
+    player: usize,
+    index: usize,
+    ready: bool
+}
+
+// Idle animation of the character a cursor is on
+#[derive(Component)]
+struct CharacterPreview {
+    player: usize,
+    col: usize
+}
+
+#[derive(Component)]
+struct CharacterLabel {
+    player: usize
+}
+
+// Characters in the manifest sorted by path so they always list in the same order
+fn character_paths(game_assets: &GameAssets) -> Vec<String> {
+    let mut paths: Vec<String> = game_assets.characters.keys().cloned().collect();
+    paths.sort();
+    paths
+}
+
+fn spawn_character_select(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    selection: Res<CharacterSelection>,
+    mut state: ResMut<NextState<AppState>>
+) {
+    let paths = character_paths(&game_assets);
+    if paths.len() <= 1 {
+        state.set(AppState::InGame);
+        return;
+    }
+
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(32.0),
+                ..Default::default()
+            },
+            background_color: Color::BLACK.into(),
+            z_index: ZIndex::Global(20),
+            ..Default::default()
+        },
+        CharacterSelectRoot
+    )).with_children(|root| {
+        root.spawn(TextBundle::from_section("Choose your character", TextStyle {
+            font_size: 48.0,
+            color: Color::GOLD,
+            ..Default::default()
+        }));
+
+        for (player, path) in selection.players.iter().enumerate() {
+            let index = paths.iter().position(|candidate| candidate == path).unwrap_or(0);
+            root.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(16.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                CharacterCursor { player, index, ready: false }
+            )).with_children(|row| {
+                let text_style = TextStyle {
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                };
+                row.spawn(TextBundle::from_section(format!("Player {}", player + 1), text_style.clone()));
+                row.spawn(TextBundle::from_section("<", text_style.clone()));
+                row.spawn((
+                    AtlasImageBundle {
+                        style: Style {
+                            width: Val::Px(96.0),
+                            height: Val::Px(96.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+                    CharacterPreview { player, col: 0 }
+                ));
+                row.spawn(TextBundle::from_section(">", text_style.clone()));
+                row.spawn((TextBundle::from_section("", text_style), CharacterLabel { player }));
+            });
+        }
+    });
+}
+
+fn move_character_cursors(
+    actions: PlayerActions,
+    game_assets: Res<GameAssets>,
+    mut selection: ResMut<CharacterSelection>,
+    mut state: ResMut<NextState<AppState>>,
+    mut cursor_query: Query<&mut CharacterCursor>
+) {
+    let paths = character_paths(&game_assets);
+    if paths.is_empty() {
+        return;
+    }
+
+    for mut cursor in cursor_query.iter_mut() {
+        let input = PlayerInput::for_local_player(cursor.player);
+        if actions.just_pressed(&input, InputAction::Jump) {
+            cursor.ready = !cursor.ready;
+        }
+        if cursor.ready {
+            continue;
+        }
+        if actions.just_pressed(&input, InputAction::Left) {
+            cursor.index = (cursor.index + paths.len() - 1) % paths.len();
+        }
+        if actions.just_pressed(&input, InputAction::Right) {
+            cursor.index = (cursor.index + 1) % paths.len();
+        }
+    }
+
+    if !cursor_query.is_empty() && cursor_query.iter().all(|cursor| cursor.ready) {
+        for cursor in cursor_query.iter() {
+            if let Some(player) = selection.players.get_mut(cursor.player) {
+                *player = paths[cursor.index % paths.len()].clone();
+            }
+        }
+        state.set(AppState::InGame);
+    }
+}
+
+// Plays each cursor's character idling, restarting when the cursor moves
+fn animate_character_previews(
+    time: Res<Time>,
+    game_assets: Res<GameAssets>,
+    characters: Res<Assets<CharacterDef>>,
+    character_atlases: Res<CharacterAtlases>,
+    cursor_query: Query<&CharacterCursor>,
+    mut preview_query: Query<(&mut CharacterPreview, &mut AnimationTimer, &mut Handle<TextureAtlas>, &mut UiTextureAtlasImage)>,
+    mut label_query: Query<(&CharacterLabel, &mut Text)>
+) {
+    let paths = character_paths(&game_assets);
+
+    for cursor in cursor_query.iter() {
+        let handle = match paths.get(cursor.index) {
+            Some(path) => game_assets.character(path),
+            None => continue,
+        };
+        let (character, atlas) = match (characters.get(&handle), character_atlases.0.get(&handle.id())) {
+            (Some(character), Some(atlas)) => (character, atlas),
+            _ => continue,
+        };
+        let row = character.animation_row("idle").unwrap_or(0);
+        // Characters without animations get their first cell, standing still
+        let animation = character.animations.get(row);
+
+        for (mut preview, mut timer, mut preview_atlas, mut image) in preview_query.iter_mut() {
+            if preview.player != cursor.player {
+                continue;
+            }
+            if *preview_atlas != *atlas {
+                *preview_atlas = atlas.clone();
+                if let Some(animation) = animation {
+                    *timer = animation.timer();
+                }
+                preview.col = 0;
+            }
+            let animation = match animation {
+                Some(animation) => animation,
+                None => {
+                    image.index = 0;
+                    continue;
+                }
+            };
+
+            timer.tick(time.delta());
+            if timer.just_finished() {
+                preview.col = (preview.col + 1) % animation.number_of_frames.max(1);
+            }
+            image.index = preview.col + character.columns * row;
+        }
+
+        for (label, mut text) in label_query.iter_mut() {
+            if label.player == cursor.player {
+                let ready = if cursor.ready { " (ready)" } else { "" };
+                text.sections[0].value = format!("{}{}", character.name, ready);
+            }
+        }
+    }
+}
+
+fn despawn_character_select(
+    mut commands: Commands,
+    root_query: Query<Entity, With<CharacterSelectRoot>>
+) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct CharacterSelectPlugin;
+
+impl Plugin for CharacterSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::CharacterSelect), spawn_character_select)
+            .add_systems(Update, (move_character_cursors, animate_character_previews).chain().run_if(in_state(AppState::CharacterSelect)))
+            .add_systems(OnExit(AppState::CharacterSelect), despawn_character_select);
+    }
+}
@@ -0,0 +1,14 @@
+use bevy::prelude::{App, Plugin};
+
+use self::character_select::CharacterSelectPlugin;
+
+pub mod character_select;
+
+// Screens shown outside of the level
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(CharacterSelectPlugin);
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::{asset::{AssetApp, AssetPath, io::{AssetSource, AssetSourceId}}, prelude::*};
+
+// Asset source the `mods` folder is read through, next to the assets folder
+pub const MODS_SOURCE: &str = "mods";
+
+// Folders in `mods/` with a `manifest.ron`, each is one pack. The manifest is
+// the same as the game's own, with paths relative to the pack's folder, and
+// whatever it lists gets loaded along with the game's assets. Characters show
+// up in character select and maps can be picked with MapSelection. Found
+// once at startup, the web build has no folder to look in so never has any.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ModPacks(pub Vec<String>);
+
+impl ModPacks {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn scan() -> Self {
+        let folder = bevy::asset::io::file::FileAssetReader::get_base_path().join(MODS_SOURCE);
+        let mut packs: Vec<String> = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join("manifest.ron").is_file())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        // Load order is alphabetical so it's the same every run
+        packs.sort();
+        for pack in packs.iter() {
+            info!("Found mod {}", pack);
+        }
+        ModPacks(packs)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn scan() -> Self {
+        ModPacks::default()
+    }
+}
+
+// Where a path listed by `pack` ends up, this is also the key it gets in GameAssets
+pub fn pack_path(pack: &str, path: &str) -> String {
+    format!("{}://{}/{}", MODS_SOURCE, pack, path)
+}
+
+// Paths inside data files like character definitions are from the assets
+// folder for the game's own files and from the pack's folder for a mod's
+pub fn resolve_path(owner: &AssetPath, path: &str) -> AssetPath<'static> {
+    match owner.source() {
+        AssetSourceId::Name(name) if &**name == MODS_SOURCE => {
+            let pack = owner.path().components().next().map(|pack| pack.as_os_str().to_string_lossy().into_owned()).unwrap_or_default();
+            AssetPath::from(pack_path(&pack, path))
+        },
+        _ => AssetPath::from(path.to_string()),
+    }
+}
+
+// Has to be added before DefaultPlugins so the asset source exists when the
+// asset server is made
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_asset_source(MODS_SOURCE, AssetSource::build().with_reader(AssetSource::get_default_reader(MODS_SOURCE.to_string())))
+            .insert_resource(ModPacks::scan());
+    }
+}
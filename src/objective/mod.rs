@@ -0,0 +1,240 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use crate::{camera::{MainCamera, pixel_perfect::GameView, screen_size, world_to_screen}, preload::GameAssets};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectiveKind {
+    Exit,
+    Key,
+    Boss
+}
+
+// Anything the player should be guided towards. The marker only shows up while
+// the objective is outside of the camera view.
+#[derive(Component)]
+pub struct Objective {
+    pub kind: ObjectiveKind,
+    pub show_marker: bool
+}
+
+impl Objective {
+    pub fn new(kind: ObjectiveKind) -> Self {
+        Objective {
+            kind,
+            show_marker: true
+        }
+    }
+}
+
+// Send these to turn the marker for a single objective on or off
+#[derive(Event)]
+pub enum ObjectiveMarkerEvent {
+    Show(Entity),
+    Hide(Entity)
+}
+
+#[derive(Resource)]
+pub struct ObjectiveMarkerSettings {
+    // Distance in pixels the marker is kept from the edge of the screen
+    pub edge_padding: f32,
+    pub icon_size: f32,
+    pub arrow_size: f32,
+    // World distance where the marker starts fading out and where it reaches min_alpha
+    pub fade_start: f32,
+    pub fade_end: f32,
+    pub min_alpha: f32
+}
+
+impl Default for ObjectiveMarkerSettings {
+    fn default() -> Self {
+        ObjectiveMarkerSettings {
+            edge_padding: 48.0,
+            icon_size: 48.0,
+            arrow_size: 32.0,
+            fade_start: 250.0,
+            fade_end: 1000.0,
+            min_alpha: 0.25
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ObjectiveMarkerAssets {
+    pub exit: Handle<Image>,
+    pub key: Handle<Image>,
+    pub boss: Handle<Image>,
+    pub arrow: Handle<Image>
+}
+
+impl ObjectiveMarkerAssets {
+    pub fn icon(&self, kind: ObjectiveKind) -> Handle<Image> {
+        match kind {
+            ObjectiveKind::Exit => self.exit.clone(),
+            ObjectiveKind::Key => self.key.clone(),
+            ObjectiveKind::Boss => self.boss.clone(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ObjectiveMarker {
+    pub objective: Entity
+}
+
+#[derive(Component)]
+pub struct ObjectiveMarkerArrow;
+
+fn setup_objective_markers(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>
+) {
+    commands.insert_resource(ObjectiveMarkerAssets {
+        exit: game_assets.texture("miscellaneous sprites/door.png"),
+        key: game_assets.texture("miscellaneous sprites/orb.png"),
+        boss: game_assets.texture("miscellaneous sprites/spikes.png"),
+        arrow: game_assets.texture("miscellaneous sprites/arrow_plate_right.png"),
+    });
+}
+
+fn spawn_objective_markers(
+    mut commands: Commands,
+    marker_assets: Res<ObjectiveMarkerAssets>,
+    settings: Res<ObjectiveMarkerSettings>,
+    objective_query: Query<(Entity, &Objective), Added<Objective>>
+) {
+    for (entity, objective) in objective_query.iter() {
+        commands.spawn(ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(settings.icon_size),
+                height: Val::Px(settings.icon_size),
+                ..Default::default()
+            },
+            image: UiImage::new(marker_assets.icon(objective.kind)),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        })
+        .insert(ObjectiveMarker { objective: entity })
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(settings.arrow_size),
+                    height: Val::Px(settings.arrow_size),
+                    ..Default::default()
+                },
+                image: UiImage::new(marker_assets.arrow.clone()),
+                ..Default::default()
+            })
+            .insert(ObjectiveMarkerArrow);
+        });
+    }
+}
+
+fn toggle_objective_markers(
+    mut marker_events: EventReader<ObjectiveMarkerEvent>,
+    mut objective_query: Query<&mut Objective>
+) {
+    for event in marker_events.read() {
+        let (entity, show) = match event {
+            ObjectiveMarkerEvent::Show(entity) => (*entity, true),
+            ObjectiveMarkerEvent::Hide(entity) => (*entity, false),
+        };
+
+        if let Ok(mut objective) = objective_query.get_mut(entity) {
+            objective.show_marker = show;
+        }
+    }
+}
+
+fn update_objective_markers(
+    mut commands: Commands,
+    settings: Res<ObjectiveMarkerSettings>,
+    view: Res<GameView>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    objective_query: Query<(&GlobalTransform, &Objective)>,
+    mut marker_query: Query<(Entity, &ObjectiveMarker, &mut Style, &mut Visibility, &mut BackgroundColor, &Children), Without<ObjectiveMarkerArrow>>,
+    mut arrow_query: Query<(&mut Style, &mut Transform, &mut BackgroundColor), With<ObjectiveMarkerArrow>>
+) {
+    let (camera, camera_transform) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    // Markers are UI so work in window sized pixels, relative to the top left of the game view
+    let size = match screen_size(camera) {
+        Some(size) => size * view.scale,
+        None => return,
+    };
+
+    for (marker_entity, marker, mut style, mut visibility, mut color, children) in marker_query.iter_mut() {
+        let (objective_transform, objective) = match objective_query.get(marker.objective) {
+            Ok(objective) => objective,
+            Err(_) => {
+                // The objective is gone so the marker goes with it
+                commands.entity(marker_entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        let screen_position = match world_to_screen(camera, camera_transform, objective_transform.translation()) {
+            Some(screen_position) => screen_position * view.scale,
+            None => {
+                *visibility = Visibility::Hidden;
+                continue;
+            }
+        };
+
+        let on_screen = screen_position.cmpge(Vec2::ZERO).all() && screen_position.cmple(size).all();
+        if !objective.show_marker || on_screen {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        // Walk from the center of the screen towards the objective until the padded screen edge is hit
+        let center = size / 2.0;
+        let direction = screen_position - center;
+        let bounds = (center - Vec2::splat(settings.edge_padding)).max(Vec2::ZERO);
+        let t = (bounds.x / direction.x.abs()).min(bounds.y / direction.y.abs());
+        let edge_position = center + direction * t;
+
+        let half_icon = settings.icon_size / 2.0;
+        style.left = Val::Px(view.offset.x + edge_position.x - half_icon);
+        style.top = Val::Px(view.offset.y + edge_position.y - half_icon);
+
+        let distance = camera_transform.translation().xy().distance(objective_transform.translation().xy());
+        let fade = ((distance - settings.fade_start) / (settings.fade_end - settings.fade_start)).clamp(0.0, 1.0);
+        let alpha = 1.0 - fade * (1.0 - settings.min_alpha);
+        color.0.set_a(alpha);
+
+        // Arrow sits just outside the icon on the side facing the objective
+        let direction = direction.normalize();
+        let half_arrow = settings.arrow_size / 2.0;
+        let arrow_center = Vec2::splat(half_icon) + direction * (half_icon + half_arrow);
+        for child in children.iter() {
+            if let Ok((mut arrow_style, mut arrow_transform, mut arrow_color)) = arrow_query.get_mut(*child) {
+                arrow_style.left = Val::Px(arrow_center.x - half_arrow);
+                arrow_style.top = Val::Px(arrow_center.y - half_arrow);
+                // Screen space y points down, rotations are counter clockwise in y up space
+                arrow_transform.rotation = Quat::from_rotation_z(-direction.y.atan2(direction.x));
+                arrow_color.0.set_a(alpha);
+            }
+        }
+    }
+}
+
+pub struct ObjectiveMarkerPlugin;
+
+impl Plugin for ObjectiveMarkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ObjectiveMarkerEvent>()
+            .init_resource::<ObjectiveMarkerSettings>()
+            .add_systems(Update, setup_objective_markers.run_if(resource_added::<GameAssets>()))
+            .add_systems(Update, (
+                spawn_objective_markers,
+                toggle_objective_markers,
+                update_objective_markers.after(toggle_objective_markers)
+            ).run_if(resource_exists::<ObjectiveMarkerAssets>()));
+    }
+}
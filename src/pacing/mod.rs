@@ -0,0 +1,130 @@
+use bevy::{prelude::*, utils::Instant, window::{PresentMode, PrimaryWindow}};
+use bevy_egui::{EguiContexts, egui};
+
+// How frames get paced. `frame_cap` is the most frames a second to draw when
+// set, on top of vsync. Browsers pace frames themselves so the cap does
+// nothing on wasm.
+#[derive(Resource, Debug, Clone)]
+pub struct FramePacing {
+    pub vsync: bool,
+    pub frame_cap: Option<f32>
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        FramePacing {
+            vsync: true,
+            frame_cap: None
+        }
+    }
+}
+
+// Render frames and fixed steps run over the last second, for the debug readout
+#[derive(Resource)]
+pub struct FrameRates {
+    pub refresh: Timer,
+    pub render: f32,
+    pub simulation: f32,
+    frames: u32,
+    steps: u32
+}
+
+impl Default for FrameRates {
+    fn default() -> Self {
+        FrameRates {
+            refresh: Timer::from_seconds(1.0, TimerMode::Repeating),
+            render: 0.0,
+            simulation: 0.0,
+            frames: 0,
+            steps: 0
+        }
+    }
+}
+
+fn apply_vsync(
+    pacing: Res<FramePacing>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>
+) {
+    let present_mode = if pacing.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync };
+    for mut window in window_query.iter_mut() {
+        if window.present_mode != present_mode {
+            window.present_mode = present_mode;
+        }
+    }
+}
+
+// Sleeps off whatever is left of the frame's share of a second. Runs last so
+// the frame's own work counts towards it.
+fn cap_frame_rate(
+    pacing: Res<FramePacing>,
+    mut frame_start: Local<Option<Instant>>
+) {
+    if let Some(cap) = pacing.frame_cap.filter(|cap| *cap > 0.0 && !cfg!(target_arch = "wasm32")) {
+        let frame_time = std::time::Duration::from_secs_f32(1.0 / cap);
+        if let Some(elapsed) = frame_start.map(|start| start.elapsed()) {
+            if elapsed < frame_time {
+                std::thread::sleep(frame_time - elapsed);
+            }
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
+
+fn count_fixed_steps(mut rates: ResMut<FrameRates>) {
+    rates.steps += 1;
+}
+
+fn count_frames(
+    time: Res<Time<Real>>,
+    mut rates: ResMut<FrameRates>
+) {
+    rates.frames += 1;
+    if rates.refresh.tick(time.delta()).just_finished() {
+        let seconds = rates.refresh.duration().as_secs_f32();
+        rates.render = rates.frames as f32 / seconds;
+        rates.simulation = rates.steps as f32 / seconds;
+        rates.frames = 0;
+        rates.steps = 0;
+    }
+}
+
+fn debug_frame_pacing(
+    mut egui_ctx: EguiContexts,
+    rates: Res<FrameRates>,
+    mut pacing: ResMut<FramePacing>
+) {
+    egui::Window::new("Frame Pacing").show(egui_ctx.ctx_mut(), |ui| {
+        ui.label(format!("Render: {:.0} fps", rates.render));
+        ui.label(format!("Simulation: {:.0} steps/s", rates.simulation));
+        ui.separator();
+
+        ui.checkbox(&mut pacing.vsync, "Vsync");
+        let mut capped = pacing.frame_cap.is_some();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut capped, "Frame cap");
+            let mut cap = pacing.frame_cap.unwrap_or(60.0);
+            ui.add_enabled(capped, egui::DragValue::new(&mut cap).clamp_range(10.0..=500.0));
+            pacing.frame_cap = if capped { Some(cap) } else { None };
+        });
+    });
+}
+
+pub struct FramePacingPlugin;
+
+impl Plugin for FramePacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FramePacing>()
+            .init_resource::<FrameRates>()
+            .add_systems(Update, (apply_vsync, count_frames))
+            .add_systems(FixedUpdate, count_fixed_steps)
+            .add_systems(Last, cap_frame_rate);
+    }
+}
+
+pub struct FramePacingDebugPlugin;
+
+impl Plugin for FramePacingDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, debug_frame_pacing);
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::{math::Vec2, prelude::{Commands, Component, Entity, Event, EventReader, Query, Res, Resource, Time, Timer, TimerMode, Transform, With, Without}};
+
+use super::body::{BodyType, Position};
+
+// Actors only get simulated near something with this, usually the camera and
+// the player
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct ActivationSource;
+
+// Put on actors that are too far from every activation source. Physics and
+// animation skip them until they come back in range or get woken.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Inactive;
+
+// Keeps a woken actor simulated for a while even out of range
+#[derive(Component, Debug)]
+pub struct KeepAwake(pub Timer);
+
+// Send to wake an actor, e.g. when it gets hit from off screen
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WakeBody(pub Entity);
+
+#[derive(Resource, Debug, Clone)]
+pub struct ActivationSettings {
+    pub enabled: bool,
+    // In pixels around each activation source
+    pub radius: f32,
+    // Seconds a WakeBody keeps an actor awake for
+    pub wake_time: f32
+}
+
+impl Default for ActivationSettings {
+    fn default() -> Self {
+        ActivationSettings {
+            enabled: true,
+            radius: 375.0,
+            wake_time: 2.0
+        }
+    }
+}
+
+pub fn update_activation(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ActivationSettings>,
+    mut wake_events: EventReader<WakeBody>,
+    source_query: Query<&Transform, With<ActivationSource>>,
+    mut body_query: Query<(Entity, &Position, &BodyType, Option<&Inactive>, Option<&mut KeepAwake>), Without<ActivationSource>>
+) {
+    for WakeBody(entity) in wake_events.read() {
+        if body_query.contains(*entity) {
+            commands.entity(*entity).remove::<Inactive>().insert(KeepAwake(Timer::from_seconds(settings.wake_time, TimerMode::Once)));
+        }
+    }
+
+    let sources: Vec<Vec2> = source_query.iter().map(|transform| transform.translation.truncate()).collect();
+    let radius_squared = settings.radius * settings.radius;
+
+    for (entity, position, body_type, inactive, keep_awake) in body_query.iter_mut() {
+        if *body_type != BodyType::Actor {
+            continue;
+        }
+
+        let awake = match keep_awake {
+            Some(mut keep_awake) => {
+                if keep_awake.0.tick(time.delta()).finished() {
+                    commands.entity(entity).remove::<KeepAwake>();
+                }
+                true
+            }
+            None => false
+        };
+
+        // With no sources there is nothing to measure from, so simulate everything
+        let in_range = !settings.enabled || sources.is_empty()
+            || sources.iter().any(|source| source.distance_squared(position.0) <= radius_squared);
+
+        match (in_range || awake, inactive.is_some()) {
+            (true, true) => { commands.entity(entity).remove::<Inactive>(); }
+            (false, false) => { commands.entity(entity).insert(Inactive); }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::{math::Vec2, prelude::{Component, Entity, Query, Res, Without}};
+
+use super::{activation::Inactive, cast_debug::CastLog, body::{BodyType, Position, Remainder}, broad_phase::SpatialHash, collision::{AABB, Intersection, SolidCollider, world_aabb}, move_x, nearby_solids, solid_cache::SolidColliderCache};
+
+// Actors with this run into each other, actors without it pass through every
+// other actor. Movement treats the others as solids, so an actor can stand on
+// another one's head.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollideWithActors {
+    #[default]
+    Block,
+    // Also gets nudged out of other actors it ends up inside, like after
+    // spawning on top of one or being carried into one
+    Push
+}
+
+// Other colliding actors near a move, as solids to stop against. Ones already
+// overlapping the mover are left out so the two can walk apart.
+pub fn nearby_actors(actors: &[(Entity, Vec2, AABB)], entity: Entity, position: Vec2, collider: &AABB, movement: Vec2) -> Vec<SolidCollider> {
+    let current_box = world_aabb(&position, collider);
+    let reach = AABB {
+        position: world_aabb(&(position + movement / 2.0), collider).position,
+        half_size: collider.half_size + (movement.abs() / 2.0).ceil().as_ivec2() + bevy::math::IVec2::ONE
+    };
+
+    actors.iter().copied()
+        .filter(|(other, _, _)| *other != entity)
+        .filter(|(_, other_position, other_collider)| {
+            let other_box = world_aabb(other_position, other_collider);
+            AABB::interescts(&reach, &other_box) && !AABB::interescts(&current_box, &other_box)
+        })
+        .map(|(other, other_position, other_collider)| SolidCollider {
+            entity: other,
+            position: other_position,
+            collider: other_collider,
+            one_way: false,
+            slope: None,
+            round: None
+        })
+        .collect()
+}
+
+// Nudges overlapping actors a pixel apart sideways each step while either of
+// them pushes. Solids still stop the nudge.
+pub fn separate_actors(
+    spatial_hash: Res<SpatialHash>,
+    cache: Res<SolidColliderCache>,
+    mut actor_query: Query<(Entity, &mut Position, &mut Remainder, &AABB, &BodyType, &CollideWithActors), Without<Inactive>>
+) {
+    let actors: Vec<(Entity, Vec2, AABB, bool)> = actor_query.iter()
+        .filter(|(.., body_type, _)| **body_type == BodyType::Actor)
+        .map(|(entity, position, _, collider, _, mode)| (entity, position.0, *collider, *mode == CollideWithActors::Push))
+        .collect();
+    let moving_solids = cache.moving_solids();
+
+    for (index, (entity, position, collider, pushes)) in actors.iter().enumerate() {
+        let actor_box = world_aabb(position, collider);
+        let push: f32 = actors.iter().enumerate()
+            .filter(|(other_index, (_, other_position, other_collider, other_pushes))| {
+                *other_index != index && (*pushes || *other_pushes) && AABB::interescts(&actor_box, &world_aabb(other_position, other_collider))
+            })
+            .map(|(other_index, (_, other_position, ..))| {
+                // Stacked right on top of each other, split them by spawn order
+                let apart = position.x - other_position.x;
+                if apart != 0.0 { apart.signum() } else if index < other_index { -1.0 } else { 1.0 }
+            })
+            .sum();
+
+        if push == 0.0 {
+            continue;
+        }
+        if let Ok((_, mut position, mut remainder, collider, ..)) = actor_query.get_mut(*entity) {
+            let nudge = push.signum();
+            let solids = nearby_solids(&spatial_hash, &moving_solids, position.0, collider, Vec2::new(nudge, 0.0));
+            // A pixel nudge is never long enough to sweep, so there is nothing to record
+            move_x(&nudge, &mut position, &mut remainder, collider, &solids, None, 0, &mut CastLog::default());
+        }
+    }
+}
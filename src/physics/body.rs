@@ -1,33 +1,87 @@
-use bevy::{math::{IVec2, Vec2}, prelude::Bundle};
+use bevy::{math::Vec2, prelude::{Bundle, Component}};
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Component, Default, Debug, Clone, Copy)]
 pub struct Position(pub Vec2);
 
-#[derive(Default, Debug)]
+#[derive(Component, Default, Debug)]
 pub struct Velocity(pub Vec2);
 
-#[derive(Default, Debug)]
-pub struct Acceleration(pub Vec2, pub Vec2);
+// `linear` is gathered fresh every step, from input and the like, and cleared
+// once the body has moved. `persistent` keeps applying every step until it is
+// changed, for continuous forces like wind.
+#[derive(Component, Default, Debug)]
+pub struct Acceleration {
+    pub linear: Vec2,
+    pub persistent: Vec2
+}
+
+impl Acceleration {
+    pub fn total(&self) -> Vec2 {
+        self.linear + self.persistent
+    }
+}
 
-#[derive(Default, Debug)]
+#[derive(Component, Default, Debug)]
 pub struct Remainder(pub Vec2);
 
-#[derive(PartialEq, Debug)]
-pub enum BodyType {
-    Actor,
-    Solid
+// Multiplier on the world gravity from PhysicsConfig, actors without one don't fall
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GravityScale(pub f32);
+
+impl Default for GravityScale {
+    fn default() -> Self {
+        GravityScale(1.0)
+    }
 }
 
-impl Default for BodyType {
+// Which way an actor falls, the strength still comes from PhysicsConfig.
+// Step heights and corners follow the actor's MoveFrame, only slopes are
+// still walked the right way up.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GravityDirection(pub Vec2);
+
+impl Default for GravityDirection {
     fn default() -> Self {
-        BodyType::Solid
+        GravityDirection(Vec2::NEG_Y)
     }
 }
 
+// Fastest an actor can go sideways and falling, both in units. Falling is
+// along gravity, so in an upside down room it limits how fast it goes up.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SpeedLimits {
+    pub max_horizontal: f32,
+    pub max_fall: f32
+}
+
+// Actors with this integrate their own velocity, like the player does, and
+// are left alone by the generic integrator
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct CustomIntegration;
+
+// Pixels an actor moves next step on top of its velocity, along with its
+// collisions, like an animation stepping it forward. Whatever couldn't be
+// moved in one step because of max_step_pixels is kept for the next.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct RootMotion(pub Vec2);
+
+// Bodies kept in place by gameplay code, like something being carried. They
+// keep their collider for queries, but gravity, integration and moving leave
+// them alone.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Held;
+
+#[derive(Component, PartialEq, Debug, Default)]
+pub enum BodyType {
+    Actor,
+    #[default]
+    Solid
+}
+
 #[derive(Bundle, Default, Debug)]
 pub struct BodyBundle {
     pub body_type: BodyType,
     pub velocity: Velocity,
     pub position: Position,
     pub remainder: Remainder,
-}
\ No newline at end of file
+}
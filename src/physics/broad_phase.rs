@@ -0,0 +1,94 @@
+use bevy::{math::{IVec2, Vec2}, prelude::{ResMut, Resource}, utils::HashMap};
+
+use super::{collision::SolidCollider, solid_cache::SolidColliderCache};
+
+// Uniform grid over the static solids so movement only has to test the
+// colliders in the cells it passes through. Solids with a velocity move during
+// the step so they are kept out of the grid and always tested.
+#[derive(Resource)]
+pub struct SpatialHash {
+    pub cell_size: i32,
+    cells: HashMap<IVec2, Vec<usize>>,
+    solids: Vec<SolidCollider>
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        SpatialHash::new(64)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: i32) -> Self {
+        SpatialHash {
+            cell_size: cell_size.max(1),
+            cells: HashMap::new(),
+            solids: Vec::new()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.solids.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.solids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solids.is_empty()
+    }
+
+    fn cell(&self, point: IVec2) -> IVec2 {
+        IVec2::new(point.x.div_euclid(self.cell_size), point.y.div_euclid(self.cell_size))
+    }
+
+    pub fn insert(&mut self, solid: SolidCollider) {
+        let world_box = solid.collider.adjusted_position(&solid.position.round().as_ivec2());
+        let (min, max) = (self.cell(world_box.min()), self.cell(world_box.max()));
+        let index = self.solids.len();
+        self.solids.push(solid);
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    // Every solid with a cell overlapping the given world space box, each one only once
+    pub fn query(&self, min: Vec2, max: Vec2) -> Vec<SolidCollider> {
+        let (min, max) = (self.cell(min.floor().as_ivec2()), self.cell(max.ceil().as_ivec2()));
+        let mut indices: Vec<usize> = Vec::new();
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(cell) = self.cells.get(&IVec2::new(x, y)) {
+                    indices.extend(cell.iter());
+                }
+            }
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|index| self.solids[index]).collect()
+    }
+}
+
+// The grid only gets rebuilt when a static solid was added, removed or moved,
+// or a solid started or stopped moving
+pub fn update_spatial_hash(
+    mut spatial_hash: ResMut<SpatialHash>,
+    mut cache: ResMut<SolidColliderCache>
+) {
+    if !cache.static_changed {
+        return;
+    }
+
+    spatial_hash.clear();
+    for solid in cache.static_solids() {
+        spatial_hash.insert(*solid);
+    }
+    cache.static_changed = false;
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+
+use super::{collision::{AABB, SweepHit}, world::{OverlapHit, RayHit}};
+
+#[derive(Debug, Clone, Copy)]
+pub enum CastShape {
+    Ray,
+    // Half size of the box being swept
+    Aabb(Vec2),
+    // Radius of an overlap test, these don't go anywhere
+    Circle(f32)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CastHit {
+    pub point: Vec2,
+    pub normal: Vec2
+}
+
+// A single query made against the physics world. `end` is where the cast would
+// stop if nothing was hit.
+#[derive(Debug, Clone)]
+pub struct CastRecord {
+    pub caller: &'static str,
+    pub shape: CastShape,
+    pub origin: Vec2,
+    pub end: Vec2,
+    pub hit: Option<CastHit>
+}
+
+impl CastRecord {
+    pub fn ray(caller: &'static str, origin: Vec2, direction: Vec2, max_distance: f32, hit: Option<&RayHit>) -> Self {
+        CastRecord {
+            caller,
+            shape: CastShape::Ray,
+            origin,
+            end: origin + direction.normalize_or_zero() * max_distance,
+            hit: hit.map(|hit| CastHit { point: hit.point, normal: hit.normal })
+        }
+    }
+
+    // `from` is the body's position, the record is of the collider itself
+    pub fn sweep(caller: &'static str, collider: &AABB, from: Vec2, delta: Vec2, hit: Option<&SweepHit>) -> Self {
+        let offset = collider.position.as_vec2();
+        CastRecord {
+            caller,
+            shape: CastShape::Aabb(collider.half_size.as_vec2()),
+            origin: from + offset,
+            end: from + delta + offset,
+            hit: hit.map(|hit| CastHit { point: hit.position + offset, normal: hit.normal })
+        }
+    }
+
+    // The normal of an overlap points from the hit back to the centre
+    pub fn circle(caller: &'static str, center: Vec2, radius: f32, hit: Option<&OverlapHit>) -> Self {
+        CastRecord {
+            caller,
+            shape: CastShape::Circle(radius),
+            origin: center,
+            end: center,
+            hit: hit.map(|hit| CastHit { point: hit.point, normal: (center - hit.point).normalize_or_zero() })
+        }
+    }
+}
+
+// The last `capacity` casts made through PhysicsQuery and the physics step. Callers tag their
+// casts with a name so they can be shown or hidden individually.
+#[derive(Resource)]
+pub struct CastDebugHistory {
+    pub enabled: bool,
+    pub capacity: usize,
+    pub records: VecDeque<CastRecord>,
+    // Every caller seen so far and whether its casts are drawn
+    pub callers: BTreeMap<&'static str, bool>
+}
+
+impl Default for CastDebugHistory {
+    fn default() -> Self {
+        CastDebugHistory {
+            enabled: false,
+            capacity: 64,
+            records: VecDeque::new(),
+            callers: BTreeMap::new()
+        }
+    }
+}
+
+impl CastDebugHistory {
+    pub fn record(&mut self, record: CastRecord) {
+        if !self.enabled {
+            return;
+        }
+
+        self.callers.entry(record.caller).or_insert(true);
+        self.records.push_back(record);
+        while self.records.len() > self.capacity {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn record_ray(&mut self, caller: &'static str, origin: Vec2, direction: Vec2, max_distance: f32, hit: Option<&RayHit>) {
+        self.record(CastRecord::ray(caller, origin, direction, max_distance, hit));
+    }
+
+    pub fn record_sweep(&mut self, caller: &'static str, collider: &AABB, from: Vec2, delta: Vec2, hit: Option<&SweepHit>) {
+        self.record(CastRecord::sweep(caller, collider, from, delta, hit));
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    fn is_visible(&self, caller: &str) -> bool {
+        *self.callers.get(caller).unwrap_or(&true)
+    }
+}
+
+// Casts made deep inside one system's move helpers, all tagged with that
+// system. They go into the history together once it's done with `finish`,
+// nothing is kept while recording is off. The default log never records.
+#[derive(Debug, Default)]
+pub struct CastLog {
+    caller: &'static str,
+    enabled: bool,
+    records: Vec<CastRecord>
+}
+
+impl CastLog {
+    pub fn new(caller: &'static str, history: Option<&CastDebugHistory>) -> Self {
+        CastLog {
+            caller,
+            enabled: history.is_some_and(|history| history.enabled),
+            records: Vec::new()
+        }
+    }
+
+    pub fn sweep(&mut self, collider: &AABB, from: Vec2, delta: Vec2, hit: Option<&SweepHit>) {
+        if self.enabled {
+            self.records.push(CastRecord::sweep(self.caller, collider, from, delta, hit));
+        }
+    }
+
+    pub fn finish(self, history: Option<&mut CastDebugHistory>) {
+        if let Some(history) = history {
+            for record in self.records {
+                history.record(record);
+            }
+        }
+    }
+}
+
+pub fn draw_cast_history(
+    mut gizmos: Gizmos,
+    history: Res<CastDebugHistory>
+) {
+    if !history.enabled {
+        return;
+    }
+
+    let count = history.records.len();
+    for (i, record) in history.records.iter().enumerate().filter(|(_, record)| history.is_visible(record.caller)) {
+        // Older casts fade out so the most recent ones stand out
+        let age = (i + 1) as f32 / count as f32;
+        let end = record.hit.map_or(record.end, |hit| hit.point);
+        let color = if record.hit.is_some() { Color::ORANGE } else { Color::CYAN };
+
+        gizmos.line_2d(record.origin, end, color.with_a(age));
+        match record.shape {
+            CastShape::Aabb(half_size) => {
+                gizmos.rect_2d(record.origin, 0.0, half_size * 2.0, color.with_a(age * 0.5));
+                gizmos.rect_2d(end, 0.0, half_size * 2.0, color.with_a(age));
+            },
+            CastShape::Circle(radius) => {
+                gizmos.circle_2d(record.origin, radius, color.with_a(age));
+            },
+            CastShape::Ray => {}
+        }
+
+        if let Some(hit) = record.hit {
+            gizmos.circle_2d(hit.point, 3.0, Color::RED.with_a(age));
+            gizmos.line_2d(hit.point, hit.point + hit.normal * 16.0, Color::YELLOW.with_a(age));
+        }
+    }
+}
+
+pub fn debug_cast_history(
+    mut egui_ctx: EguiContexts,
+    mut history: ResMut<CastDebugHistory>
+) {
+    egui::Window::new("Casts").vscroll(true).show(egui_ctx.ctx_mut(), |ui| {
+        ui.checkbox(&mut history.enabled, "Record casts");
+        ui.horizontal(|ui| {
+            ui.label("History");
+            ui.add(egui::DragValue::new(&mut history.capacity).clamp_range(1..=1024));
+            if ui.button("Clear").clicked() {
+                history.clear();
+            }
+        });
+
+        ui.separator();
+
+        let history = &mut *history;
+        for (caller, visible) in history.callers.iter_mut() {
+            let count = history.records.iter().filter(|record| record.caller == *caller).count();
+            ui.checkbox(visible, format!("{} ({})", caller, count));
+        }
+    });
+}
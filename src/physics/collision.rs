@@ -1,34 +1,198 @@
-use bevy::math::{IVec2, Vec2};
+use bevy::{math::{IVec2, Vec2}, prelude::{Component, Entity, Event, Timer, TimerMode}};
 
-use super::body::BodyBundle;
+use super::shape::{RoundShape, RoundedBox};
 
-#[derive(Default, Clone, Copy, Debug)]
+#[derive(Component, Default, Clone, Copy, Debug)]
 pub struct AABB {
     pub position: IVec2,
     pub half_size: IVec2
 }
 
+// Solids with this only block actors landing on them from above. Actors can
+// jump up through them and walk through their sides.
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct OneWay;
+
+// How a solid's surface treats actors touching it. Friction is the share of an
+// actor's horizontal speed lost each step while standing on it, so 1 stops it
+// dead and ice sits close to 0. For the player it is how quickly walking
+// speed changes. Restitution is the share of vertical speed kept when
+// bouncing off it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PhysicsMaterial {
+    pub friction: f32,
+    pub restitution: f32
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        PhysicsMaterial {
+            friction: 1.0,
+            restitution: 0.0
+        }
+    }
+}
+
+// Velocity, in units, given to actors standing on a solid on top of their
+// own, like a conveyor belt. It only moves them, it is never added to their
+// Velocity, so stepping off doesn't fling them.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct SurfaceVelocity(pub Vec2);
+
+// Lets an actor fall through the one way platform it was standing on until the timer runs out
+#[derive(Component, Debug)]
+pub struct DropThrough {
+    pub platform: Entity,
+    pub timer: Timer
+}
+
+// One entry of CollisionExclusions, without a timer it lasts until removed
+#[derive(Debug, Clone)]
+pub struct CollisionExclusion {
+    pub entity: Entity,
+    pub timer: Option<Timer>
+}
+
+// Colliders an actor moves straight through, and sensor and actor pairs that
+// don't trigger, without changing anything for everyone else. Moving only
+// looks at the moving actor's own list, triggers skip a pair if either side
+// lists the other.
+#[derive(Component, Default, Debug, Clone)]
+pub struct CollisionExclusions(pub Vec<CollisionExclusion>);
+
+impl CollisionExclusions {
+    pub fn with(entity: Entity) -> Self {
+        let mut exclusions = CollisionExclusions::default();
+        exclusions.add(entity);
+        exclusions
+    }
+
+    pub fn add(&mut self, entity: Entity) {
+        self.remove(entity);
+        self.0.push(CollisionExclusion { entity, timer: None });
+    }
+
+    // Only for the next `seconds`, like spawn protection
+    pub fn add_for(&mut self, entity: Entity, seconds: f32) {
+        self.remove(entity);
+        self.0.push(CollisionExclusion { entity, timer: Some(Timer::from_seconds(seconds, TimerMode::Once)) });
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.0.retain(|exclusion| exclusion.entity != entity);
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.iter().any(|exclusion| exclusion.entity == entity)
+    }
+}
+
+// Turns a solid into a ramp. The surface runs in a straight line from `left`
+// to `right`, both a fraction of the collider height, and everything below it
+// is solid. A 45° tile goes 0 to 1, a 22.5° ramp is two tiles going 0 to 0.5
+// and 0.5 to 1.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Slope {
+    pub left: f32,
+    pub right: f32
+}
+
+impl Slope {
+    pub const UP_RIGHT_45: Slope = Slope { left: 0.0, right: 1.0 };
+    pub const UP_LEFT_45: Slope = Slope { left: 1.0, right: 0.0 };
+    pub const UP_RIGHT_22_LOW: Slope = Slope { left: 0.0, right: 0.5 };
+    pub const UP_RIGHT_22_HIGH: Slope = Slope { left: 0.5, right: 1.0 };
+    pub const UP_LEFT_22_LOW: Slope = Slope { left: 0.5, right: 0.0 };
+    pub const UP_LEFT_22_HIGH: Slope = Slope { left: 1.0, right: 0.5 };
+
+    // Surface height at a world x inside the solid's box
+    pub fn height_at(&self, solid_box: &AABB, x: i32) -> i32 {
+        let (min, max) = (solid_box.min(), solid_box.max());
+        let t = ((x - min.x) as f32 / (max.x - min.x).max(1) as f32).clamp(0.0, 1.0);
+        min.y + ((self.left + (self.right - self.left) * t) * (max.y - min.y) as f32).round() as i32
+    }
+
+    // Highest point of the surface over a world x range, the surface is a
+    // straight line so it is at one of the ends
+    pub fn height_over(&self, solid_box: &AABB, min_x: i32, max_x: i32) -> i32 {
+        let min_x = min_x.max(solid_box.min().x);
+        let max_x = max_x.min(solid_box.max().x);
+        self.height_at(solid_box, min_x).max(self.height_at(solid_box, max_x))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SolidCollider {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub collider: AABB,
+    pub one_way: bool,
+    pub slope: Option<Slope>,
+    pub round: Option<RoundShape>
+}
+
+impl SolidCollider {
+    // Top of the solid over a world x range
+    pub fn top(&self, min_x: i32, max_x: i32) -> i32 {
+        let solid_box = world_aabb(&self.position, &self.collider);
+        match &self.slope {
+            Some(slope) => slope.height_over(&solid_box, min_x, max_x),
+            None => solid_box.max().y
+        }
+    }
+
+    // Whether the solid part of the collider overlaps a world space box,
+    // for slopes that is only the part under the surface and for round
+    // solids only the part inside the shape
+    pub fn overlaps(&self, other_box: &AABB) -> bool {
+        if let Some(round) = &self.round {
+            return AABB::interescts(other_box, &world_aabb(&self.position, &self.collider))
+                && RoundedBox::interescts(&RoundedBox::from_aabb(other_box), &round.at(self.position.round()));
+        }
+        AABB::interescts(other_box, &world_aabb(&self.position, &self.collider))
+            && other_box.min().y < self.top(other_box.min().x, other_box.max().x)
+    }
+}
+
+// What a single pixel step ran into. The normal points from the other collider
+// towards the mover and penetration is how far the step would have overlapped it.
+#[derive(Debug, Clone, Copy)]
 pub struct Collision {
+    pub entity: Entity,
     pub position: Vec2,
-    pub collider: AABB
+    pub collider: AABB,
+    pub normal: Vec2,
+    pub penetration: f32
 }
 
+#[derive(Component)]
 pub struct CollisionResult {
     pub x_collision_body: Option<Collision>,
     pub y_collision_body: Option<Collision>
 }
 
+// Sent from the step whenever an actor gets stopped by a solid. The normal
+// points from the solid towards the actor and penetration is how far the actor
+// would have moved into the solid had it not been stopped.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    pub entity: Entity,
+    pub other: Entity,
+    pub normal: Vec2,
+    pub penetration: f32
+}
+
 pub trait Intersection<T> {
     fn interescts(_: &Self, _: &T) -> bool;
 }
 
 impl AABB {
     pub fn min(&self) -> IVec2 {
-        return self.position - self.half_size;
+        self.position - self.half_size
     }
 
     pub fn max(&self) -> IVec2 {
-        return self.position + self.half_size;
+        self.position + self.half_size
     }
 
     pub fn adjusted_position(&self, pos: &IVec2) -> Self {
@@ -41,30 +205,322 @@ impl AABB {
 
 impl Intersection<AABB> for AABB {
     fn interescts(box1: &Self, box2: &AABB) -> bool {
-        return box1.min().x < box2.max().x &&
+        box1.min().x < box2.max().x &&
         box1.max().x > box2.min().x  &&
         box1.min().y < box2.max().y &&
-        box1.max().y > box2.min().y;
+        box1.max().y > box2.min().y
+    }
+}
+
+fn round_position(position: &Vec2) -> IVec2 {
+    IVec2::new(position.x.round() as i32, position.y.round() as i32)
+}
+
+// The collider placed in the world at a body position
+pub fn world_aabb(position: &Vec2, collider: &AABB) -> AABB {
+    collider.adjusted_position(&round_position(position))
+}
+
+// Where a ray enters the convex region below every plane (`normal · p <= offset`),
+// as a distance along the ray and the normal of the plane it came in through.
+// Nothing is returned for rays starting inside the region.
+fn clip_ray(origin: Vec2, direction: Vec2, max_distance: f32, planes: &[(Vec2, f32)]) -> Option<(f32, Vec2)> {
+    let mut enter = 0.0;
+    let mut exit = max_distance;
+    let mut enter_normal = None;
+
+    for (normal, offset) in planes.iter().copied() {
+        let towards = normal.dot(direction);
+        let inside = offset - normal.dot(origin);
+        if towards.abs() < f32::EPSILON {
+            // Parallel to the plane, either always outside it or never
+            if inside < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let distance = inside / towards;
+        if towards < 0.0 {
+            if distance >= enter {
+                enter = distance;
+                enter_normal = Some(normal);
+            }
+        } else {
+            exit = exit.min(distance);
+        }
+
+        if enter > exit {
+            return None;
+        }
     }
+
+    enter_normal.map(|normal| (enter, normal))
 }
 
+// Planes bounding a world space box, or the part under the surface for slopes,
+// grown by `expand` on every side. Growing by the half size of another box
+// turns a sweep of that box into a ray cast.
+fn collider_planes(world_box: &AABB, slope: Option<&Slope>, expand: Vec2) -> Vec<(Vec2, f32)> {
+    let (min, max) = (world_box.min().as_vec2(), world_box.max().as_vec2());
+    let mut planes = vec![
+        (Vec2::NEG_X, -min.x + expand.x),
+        (Vec2::X, max.x + expand.x),
+        (Vec2::NEG_Y, -min.y + expand.y),
+        (Vec2::Y, max.y + expand.y),
+    ];
+
+    if let Some(slope) = slope {
+        let left = Vec2::new(min.x, slope.height_at(world_box, world_box.min().x) as f32);
+        let right = Vec2::new(max.x, slope.height_at(world_box, world_box.max().x) as f32);
+        let normal = Vec2::new(left.y - right.y, right.x - left.x).normalize_or_zero();
+        planes.push((normal, normal.dot(left) + normal.abs().dot(expand)));
+    }
+
+    planes
+}
+
+// Distance along a normalized ray to a world space box and the normal of the
+// face it hits. Slopes only count the part of the box under the surface.
+pub fn check_ray_box_intersection(
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    world_box: &AABB,
+    slope: Option<&Slope>
+) -> Option<(f32, Vec2)> {
+    clip_ray(origin, direction, max_distance, &collider_planes(world_box, slope, Vec2::ZERO))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SweepHit {
+    pub entity: Entity,
+    // Fraction of the movement made before touching, 0 to 1
+    pub time: f32,
+    // Body position when it touches
+    pub position: Vec2,
+    pub normal: Vec2
+}
+
+// When and where a box moving by `delta` first touches another world space
+// box. Boxes it starts out overlapping are ignored, one way solids are only
+// hit from above.
+pub fn sweep_box(
+    collider: &AABB,
+    from: Vec2,
+    delta: Vec2,
+    world_box: &AABB,
+    slope: Option<&Slope>,
+    one_way: bool
+) -> Option<(f32, Vec2)> {
+    let length = delta.length();
+    if length <= f32::EPSILON {
+        return None;
+    }
+
+    let center = from + collider.position.as_vec2();
+    let planes = collider_planes(world_box, slope, collider.half_size.as_vec2());
+    let (distance, normal) = clip_ray(center, delta / length, length, &planes)?;
+    if one_way && normal != Vec2::Y {
+        return None;
+    }
+
+    Some((distance / length, normal))
+}
+
+// Earliest solid a collider at `from` runs into while moving by `delta`
+pub fn cast_aabb(
+    collider: &AABB,
+    from: Vec2,
+    delta: Vec2,
+    colliders: &[SolidCollider],
+    ignore: Option<Entity>
+) -> Option<SweepHit> {
+    colliders.iter().filter(|other| Some(other.entity) != ignore).filter_map(|other| {
+        let world_box = world_aabb(&other.position, &other.collider);
+        let (time, normal) = sweep_box(collider, from, delta, &world_box, other.slope.as_ref(), other.one_way)?;
+        Some(SweepHit {
+            entity: other.entity,
+            time,
+            position: from + delta * time,
+            normal
+        })
+    }).min_by(|a, b| a.time.total_cmp(&b.time))
+}
+
+// Normal and overlap depth for a box that stepped into a solid. Landing on a
+// slope reports the surface normal, everything else the face it stepped into.
+fn contact(moved_box: &AABB, other: &SolidCollider, direction: IVec2) -> (Vec2, f32) {
+    let other_box = world_aabb(&other.position, &other.collider);
+    if direction.x > 0 {
+        (Vec2::NEG_X, (moved_box.max().x - other_box.min().x) as f32)
+    } else if direction.x < 0 {
+        (Vec2::X, (other_box.max().x - moved_box.min().x) as f32)
+    } else if direction.y > 0 {
+        (Vec2::NEG_Y, (moved_box.max().y - other_box.min().y) as f32)
+    } else {
+        let normal = match &other.slope {
+            Some(slope) => {
+                let left = slope.height_at(&other_box, other_box.min().x);
+                let right = slope.height_at(&other_box, other_box.max().x);
+                Vec2::new((left - right) as f32, (other_box.max().x - other_box.min().x) as f32).normalize_or_zero()
+            }
+            None => Vec2::Y
+        };
+        (normal, (other.top(moved_box.min().x, moved_box.max().x) - moved_box.min().y) as f32)
+    }
+}
+
+// `direction` is the single pixel step that moved the collider to `position`,
+// one way platforms only block a downwards step that starts out above them
 pub fn check_for_collision(
     collider: &AABB,
     position: &Vec2,
-    colliders: &Vec<(Vec2, AABB)>
+    direction: IVec2,
+    colliders: &[SolidCollider],
+    ignore: Option<Entity>
 ) -> Option<Collision> {
+    let current_ent_pos = round_position(position);
+    let current_box = collider.adjusted_position(&current_ent_pos);
+
+    for other in colliders.iter() {
+        if Some(other.entity) == ignore {
+            continue;
+        }
 
-    for (other_position, other_collider) in colliders.iter() {
-        let current_ent_pos = IVec2::new(position.x.round() as i32, position.y.round() as i32);
-        let other_ent_pos = IVec2::new(other_position.x.round() as i32, other_position.y.round() as i32);
+        let other_box = other.collider.adjusted_position(&round_position(&other.position));
+        if !other.overlaps(&current_box) {
+            continue;
+        }
 
-        if AABB::interescts(&collider.adjusted_position(&current_ent_pos), &other_collider.adjusted_position(&other_ent_pos)) {
-            return Some(Collision {
-                position: *other_position,
-                collider: collider.clone(),
-            });
+        if other.one_way {
+            let previous_box = collider.adjusted_position(&(current_ent_pos - direction));
+            if direction.y >= 0 || AABB::interescts(&previous_box, &other_box) {
+                continue;
+            }
+        }
+
+        let (normal, penetration) = contact(&current_box, other, direction);
+        return Some(Collision {
+            entity: other.entity,
+            position: other.position,
+            collider: other.collider,
+            normal,
+            penetration
+        });
+    }
+
+    None
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: i32, y: i32, half_size: i32) -> AABB {
+        AABB {
+            position: IVec2::new(x, y),
+            half_size: IVec2::splat(half_size)
         }
     }
 
-    return None;
-}
\ No newline at end of file
+    #[test]
+    fn ray_hits_the_face_it_enters_through() {
+        let hit = check_ray_box_intersection(Vec2::new(-20.0, 0.0), Vec2::X, 100.0, &square(0, 0, 5), None);
+        assert_eq!(hit, Some((15.0, Vec2::NEG_X)));
+    }
+
+    #[test]
+    fn ray_stops_short_of_a_box_past_max_distance() {
+        assert_eq!(check_ray_box_intersection(Vec2::new(-20.0, 0.0), Vec2::X, 10.0, &square(0, 0, 5), None), None);
+    }
+
+    #[test]
+    fn ray_starting_inside_a_box_misses_it() {
+        assert_eq!(check_ray_box_intersection(Vec2::ZERO, Vec2::X, 100.0, &square(0, 0, 5), None), None);
+    }
+
+    #[test]
+    fn ray_parallel_to_a_face_outside_the_box_misses_it() {
+        assert_eq!(check_ray_box_intersection(Vec2::new(-20.0, 10.0), Vec2::X, 100.0, &square(0, 0, 5), None), None);
+    }
+
+    #[test]
+    fn zero_length_sweeps_hit_nothing() {
+        assert_eq!(sweep_box(&square(0, 0, 4), Vec2::new(-9.0, 0.0), Vec2::ZERO, &square(0, 0, 5), None, false), None);
+    }
+
+    #[test]
+    fn sweep_stops_when_the_boxes_touch() {
+        let (time, normal) = sweep_box(&square(0, 0, 4), Vec2::new(-19.0, 0.0), Vec2::new(20.0, 0.0), &square(0, 0, 5), None, false).unwrap();
+        assert_eq!(time, 0.5);
+        assert_eq!(normal, Vec2::NEG_X);
+    }
+
+    #[test]
+    fn sweep_starting_out_touching_hits_right_away() {
+        let (time, normal) = sweep_box(&square(0, 0, 4), Vec2::new(-9.0, 0.0), Vec2::new(5.0, 0.0), &square(0, 0, 5), None, false).unwrap();
+        assert_eq!(time, 0.0);
+        assert_eq!(normal, Vec2::NEG_X);
+    }
+
+    #[test]
+    fn sweep_sliding_along_a_touching_face_misses() {
+        assert_eq!(sweep_box(&square(0, 0, 4), Vec2::new(0.0, 9.0), Vec2::new(20.0, 0.0), &square(0, 0, 5), None, false), None);
+    }
+
+    #[test]
+    fn sweep_moving_away_from_a_touching_box_misses() {
+        assert_eq!(sweep_box(&square(0, 0, 4), Vec2::new(-9.0, 0.0), Vec2::new(-5.0, 0.0), &square(0, 0, 5), None, false), None);
+    }
+
+    #[test]
+    fn slope_endpoints_sit_at_their_fractions() {
+        let solid_box = square(0, 0, 8);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, -8), -8);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, 8), 8);
+        assert_eq!(Slope::UP_LEFT_45.height_at(&solid_box, -8), 8);
+        assert_eq!(Slope::UP_LEFT_45.height_at(&solid_box, 8), -8);
+        assert_eq!(Slope::UP_RIGHT_22_LOW.height_at(&solid_box, 8), 0);
+        assert_eq!(Slope::UP_RIGHT_22_HIGH.height_at(&solid_box, -8), 0);
+    }
+
+    #[test]
+    fn slope_height_runs_straight_between_the_ends() {
+        let solid_box = square(0, 0, 8);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, 0), 0);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, 4), 4);
+    }
+
+    #[test]
+    fn slope_height_outside_the_box_is_clamped_to_the_ends() {
+        let solid_box = square(0, 0, 8);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, -20), -8);
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, 20), 8);
+        assert_eq!(Slope::UP_RIGHT_45.height_over(&solid_box, -20, 20), 8);
+        assert_eq!(Slope::UP_LEFT_45.height_over(&solid_box, 0, 4), 0);
+    }
+
+    #[test]
+    fn slope_on_a_zero_width_box_has_its_left_height() {
+        let solid_box = AABB {
+            position: IVec2::ZERO,
+            half_size: IVec2::new(0, 8)
+        };
+        assert_eq!(Slope::UP_RIGHT_45.height_at(&solid_box, 0), -8);
+    }
+
+    #[test]
+    fn sweeps_land_on_the_slope_surface() {
+        let (time, normal) = sweep_box(&square(0, 0, 0), Vec2::new(0.0, 20.0), Vec2::new(0.0, -40.0), &square(0, 0, 8), Some(&Slope::UP_RIGHT_45), false).unwrap();
+        assert!((time - 0.5).abs() < 1e-5);
+        assert!((normal - Vec2::new(-1.0, 1.0).normalize()).length() < 1e-5);
+    }
+
+    #[test]
+    fn one_way_sweeps_only_hit_from_above() {
+        let one_way = square(0, 0, 5);
+        assert!(sweep_box(&square(0, 0, 4), Vec2::new(0.0, 19.0), Vec2::new(0.0, -20.0), &one_way, None, true).is_some());
+        assert_eq!(sweep_box(&square(0, 0, 4), Vec2::new(0.0, -19.0), Vec2::new(0.0, 20.0), &one_way, None, true), None);
+        assert_eq!(sweep_box(&square(0, 0, 4), Vec2::new(-19.0, 0.0), Vec2::new(20.0, 0.0), &one_way, None, true), None);
+    }
+}
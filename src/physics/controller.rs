@@ -0,0 +1,270 @@
+use bevy::{math::{IVec2, Quat, Vec2}, prelude::{Component, Entity, Query, Res, Time, Transform}};
+
+use super::{body::{Position, Remainder}, gravity::Gravity, cast_debug::CastLog, collision::{AABB, Collision, SolidCollider, check_for_collision}, ground_below, is_slope, move_across, move_along};
+
+// Which way is up for an actor's movement, always along a world axis.
+// Walking runs along the side, which points right on floors and ceilings and
+// up the screen on walls, so a key keeps moving the same way around a room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveFrame {
+    up: IVec2
+}
+
+impl Default for MoveFrame {
+    fn default() -> Self {
+        MoveFrame::UPRIGHT
+    }
+}
+
+impl MoveFrame {
+    pub const UPRIGHT: MoveFrame = MoveFrame { up: IVec2::Y };
+
+    // Snaps to whichever axis gravity mostly pulls along, no gravity counts as upright
+    pub fn from_gravity(gravity: Vec2) -> Self {
+        let up = if gravity == Vec2::ZERO {
+            IVec2::Y
+        } else if gravity.x.abs() > gravity.y.abs() {
+            IVec2::new(-gravity.x.signum() as i32, 0)
+        } else {
+            IVec2::new(0, -gravity.y.signum() as i32)
+        };
+        MoveFrame { up }
+    }
+
+    pub fn up(&self) -> IVec2 {
+        self.up
+    }
+
+    pub fn down(&self) -> IVec2 {
+        -self.up
+    }
+
+    pub fn side(&self) -> IVec2 {
+        if self.up.x == 0 { IVec2::X } else { IVec2::Y }
+    }
+
+    pub fn is_upright(&self) -> bool {
+        self.up == IVec2::Y
+    }
+
+    // World vector to along the side and along up
+    pub fn to_local(&self, world: Vec2) -> Vec2 {
+        Vec2::new(world.dot(self.side().as_vec2()), world.dot(self.up.as_vec2()))
+    }
+
+    pub fn to_world(&self, local: Vec2) -> Vec2 {
+        self.side().as_vec2() * local.x + self.up.as_vec2() * local.y
+    }
+
+    // Turn that points a sprite drawn upright along up
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_z(Vec2::Y.angle_between(self.up.as_vec2()))
+    }
+}
+
+// Pixel perfect movement for anything that walks around the level. move_actor
+// drives one for every actor, actors without the component get a default
+// controller that can't climb steps or slip past corners. Enemies and other
+// scripted movers can call move_and_collide themselves with solids from
+// nearby_solids, and a CastLog to see their sweeps in the cast history.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct CharacterController {
+    // Pixels the actor can climb when walking into a ledge while standing on something
+    pub step_height: u32,
+    // Pixels the actor gets nudged sideways to slip past a corner its head
+    // only just clipped moving up, instead of stopping
+    pub corner_correction: u32,
+    touching: TouchingFlags
+}
+
+// Sides of an actor that are right up against a solid, written by the physics
+// step every tick after the actor moves
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchingFlags {
+    pub left: bool,
+    pub right: bool,
+    pub above: bool,
+    pub below: bool
+}
+
+// Whether the actor is standing on something, from the one pixel probe below
+// it each physics step. Below follows Gravity, so with gravity flipped it is
+// the ceiling. `last_grounded` is the elapsed time the actor was last on the
+// ground, for coyote time and the like.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Grounded {
+    pub is_grounded: bool,
+    pub last_grounded: f32
+}
+
+impl Default for Grounded {
+    fn default() -> Self {
+        Grounded {
+            is_grounded: false,
+            // Never been on the ground, not even right at the start
+            last_grounded: f32::NEG_INFINITY
+        }
+    }
+}
+
+impl Grounded {
+    pub fn time_since_grounded(&self, time: &Time) -> f32 {
+        if self.is_grounded {
+            0.0
+        } else {
+            time.elapsed_seconds() - self.last_grounded
+        }
+    }
+}
+
+impl TouchingFlags {
+    // The side facing a unit direction
+    pub fn towards(&self, direction: IVec2) -> bool {
+        match (direction.x, direction.y) {
+            (-1, 0) => self.left,
+            (1, 0) => self.right,
+            (0, 1) => self.above,
+            (0, -1) => self.below,
+            _ => false
+        }
+    }
+}
+
+// What a single move ran into, by world axis. `climbed` is how far the actor
+// was moved along its up to get up steps and follow slopes, which isn't part
+// of its velocity.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct ControllerMove {
+    pub x_collision: Option<Collision>,
+    pub y_collision: Option<Collision>,
+    pub climbed: f32
+}
+
+impl CharacterController {
+    pub fn new(step_height: u32, corner_correction: u32) -> Self {
+        CharacterController {
+            step_height,
+            corner_correction,
+            ..Default::default()
+        }
+    }
+
+    // Moves `movement` world pixels, along the frame's side first, stopping
+    // against solids and carrying sub pixel leftovers in `remainder`. Steps and
+    // corners follow the frame's up. Contacts are updated from where the actor
+    // ends up.
+    pub fn move_and_collide(
+        &mut self,
+        frame: MoveFrame,
+        movement: Vec2,
+        position: &mut Position,
+        remainder: &mut Remainder,
+        collider: &AABB,
+        solid_colliders: &[SolidCollider],
+        ignore: Option<Entity>,
+        casts: &mut CastLog
+    ) -> ControllerMove {
+        let local = frame.to_local(movement);
+        let (across_collision, climbed) = move_across(frame, &local.x, position, remainder, collider, solid_colliders, ignore, self.step_height, casts);
+        let along_collision = move_along(frame, &local.y, position, remainder, collider, solid_colliders, ignore, self.corner_correction, casts);
+        self.update_contacts(position, collider, solid_colliders, ignore);
+
+        let (x_collision, y_collision) = if frame.side() == IVec2::X {
+            (across_collision, along_collision)
+        } else {
+            (along_collision, across_collision)
+        };
+
+        ControllerMove {
+            x_collision,
+            y_collision,
+            climbed
+        }
+    }
+
+    // Looks a pixel around the actor for solids it is touching. One way
+    // platforms only count from above, same as when moving into them, and
+    // slopes are walked up rather than being walls.
+    pub fn update_contacts(&mut self, position: &Position, collider: &AABB, solid_colliders: &[SolidCollider], ignore: Option<Entity>) {
+        let touching = |direction: IVec2| {
+            let next = position.0 + direction.as_vec2();
+            check_for_collision(collider, &next, direction, solid_colliders, ignore)
+                .is_some_and(|collision| direction.x == 0 || !is_slope(collision.entity, solid_colliders))
+        };
+
+        self.touching = TouchingFlags {
+            left: touching(IVec2::NEG_X),
+            right: touching(IVec2::X),
+            above: touching(IVec2::Y),
+            below: ground_below(&position.0, IVec2::NEG_Y, collider, solid_colliders, ignore).is_some()
+        };
+    }
+
+    pub fn touching(&self) -> TouchingFlags {
+        self.touching
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.touching.below
+    }
+
+    pub fn is_on_wall(&self) -> bool {
+        self.touching.left || self.touching.right
+    }
+
+    // Which side the wall is on, -1 for left and 1 for right
+    pub fn wall_direction(&self) -> Option<i32> {
+        match (self.touching.left, self.touching.right) {
+            (true, false) => Some(-1),
+            (false, true) => Some(1),
+            _ => None
+        }
+    }
+}
+
+// Runs after the actors have moved and had their contacts updated
+pub fn update_grounded(
+    time: Res<Time>,
+    mut grounded_query: Query<(&mut Grounded, &TouchingFlags, Option<&Gravity>)>
+) {
+    for (mut grounded, touching, gravity) in grounded_query.iter_mut() {
+        let frame = gravity.map_or(MoveFrame::UPRIGHT, |gravity| gravity.frame());
+        grounded.is_grounded = touching.towards(frame.down());
+        if grounded.is_grounded {
+            grounded.last_grounded = time.elapsed_seconds();
+        }
+    }
+}
+
+// Turns an actor's collider, and its sprite, to stand on whichever way gravity
+// pulls. `collider` is the actor's collider the right way up.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct OrientToGravity {
+    pub collider: AABB
+}
+
+// Runs before depenetration so a collider that got wider pushing into a wall
+// gets moved back out
+pub fn orient_to_gravity(
+    mut actor_query: Query<(&OrientToGravity, &Gravity, &mut AABB, Option<&mut Transform>)>
+) {
+    for (orient, gravity, mut collider, transform) in actor_query.iter_mut() {
+        let frame = gravity.frame();
+        let upright = orient.collider;
+        let oriented = AABB {
+            position: frame.to_world(upright.position.as_vec2()).round().as_ivec2(),
+            half_size: if frame.side() == IVec2::X { upright.half_size } else { IVec2::new(upright.half_size.y, upright.half_size.x) }
+        };
+        if collider.position != oriented.position || collider.half_size != oriented.half_size {
+            *collider = oriented;
+        }
+
+        // Upside down is left to the sprite flipping, so sprites don't end up mirrored
+        if let Some(mut transform) = transform {
+            let rotation = if frame.up().y != 0 { Quat::IDENTITY } else { frame.rotation() };
+            if transform.rotation != rotation {
+                transform.rotation = rotation;
+            }
+        }
+    }
+}
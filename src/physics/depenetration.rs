@@ -0,0 +1,62 @@
+use bevy::{math::{IVec2, Vec2}, prelude::{Added, Changed, Or, Query, Res, Without}};
+
+use super::{broad_phase::SpatialHash, body::{BodyType, Held, Position}, collision::{AABB, CollisionExclusions, DropThrough, SolidCollider, world_aabb}, nearby_solids, activation::Inactive, solid_cache::SolidColliderCache, trigger::Sensor};
+
+// Give up after this many pushes, an actor wedged between solids on every side stays put
+const MAX_PUSHES: usize = 8;
+
+// Smallest push that separates the actor from the solid. Ties go upwards so
+// things placed a little into the floor end up standing on it.
+fn minimal_push(actor_box: &AABB, solid: &SolidCollider) -> IVec2 {
+    let solid_box = world_aabb(&solid.position, &solid.collider);
+    let up = solid.top(actor_box.min().x, actor_box.max().x) - actor_box.min().y;
+    let down = actor_box.max().y - solid_box.min().y;
+    let right = solid_box.max().x - actor_box.min().x;
+    let left = actor_box.max().x - solid_box.min().x;
+
+    let pushes = [
+        (up, IVec2::new(0, up)),
+        (left, IVec2::new(-left, 0)),
+        (right, IVec2::new(right, 0)),
+        (down, IVec2::new(0, -down)),
+    ];
+    pushes.iter().min_by_key(|(distance, _)| *distance).map(|(_, push)| *push).unwrap_or(IVec2::ZERO)
+}
+
+// Actors that were just spawned or moved outside of the step can end up inside
+// a solid. Push them back out so they don't get stuck there.
+pub fn depenetrate_actors(
+    spatial_hash: Res<SpatialHash>,
+    cache: Res<SolidColliderCache>,
+    mut actor_query: Query<(&mut Position, &AABB, &BodyType, Option<&DropThrough>, Option<&CollisionExclusions>), (Or<(Added<AABB>, Changed<Position>)>, Without<Sensor>, Without<Inactive>, Without<Held>)>
+) {
+    // The cache was just updated and solids haven't moved yet this step
+    let moving_solids = cache.moving_solids();
+
+    for (mut position, collider, body_type, drop_through, exclusions) in actor_query.iter_mut() {
+        if *body_type != BodyType::Actor {
+            continue;
+        }
+
+        let ignore = drop_through.map(|drop_through| drop_through.platform);
+        let mut actor_position = position.0;
+
+        for _ in 0..MAX_PUSHES {
+            let actor_box = world_aabb(&actor_position, collider);
+            let overlapping = nearby_solids(&spatial_hash, &moving_solids, actor_position, collider, Vec2::ZERO).into_iter().find(|solid| {
+                // One way platforms are meant to be passed through so overlapping them is fine
+                !solid.one_way && Some(solid.entity) != ignore && !exclusions.is_some_and(|exclusions| exclusions.contains(solid.entity)) && solid.overlaps(&actor_box)
+            });
+
+            match overlapping {
+                Some(solid) => actor_position += minimal_push(&actor_box, &solid).as_vec2(),
+                None => break,
+            }
+        }
+
+        // Only write when something moved so change detection doesn't keep firing
+        if actor_position != position.0 {
+            position.0 = actor_position;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::{math::Vec2, prelude::{Component, Entity, Query, Res, With, Without}, utils::HashMap};
+
+use super::{PhysicsConfig, controller::MoveFrame, activation::Inactive, sleep::Sleeping, body::{Acceleration, BodyType, GravityDirection, GravityScale, Held}, trigger::{Sensor, TriggerContacts}};
+
+// Put on a sensor to change the gravity of actors inside it, like an upside
+// down room or a low gravity pool of water. Both replace the actor's own
+// GravityScale and GravityDirection while it is inside.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GravityZone {
+    pub scale: f32,
+    pub direction: Vec2
+}
+
+impl Default for GravityZone {
+    fn default() -> Self {
+        GravityZone {
+            scale: 1.0,
+            direction: Vec2::NEG_Y
+        }
+    }
+}
+
+// Gravity that pulled on an actor this step, zones included, in units. Things
+// that care which way is up, like jumping and landing, read it from here.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Gravity(pub Vec2);
+
+impl Gravity {
+    // 1 when the actor falls down the screen, -1 when it falls up. Weightless
+    // actors count as the right way up.
+    pub fn up(&self) -> f32 {
+        if self.0.y > 0.0 { -1.0 } else { 1.0 }
+    }
+
+    // Which axis the actor stands along, for walking on walls and ceilings
+    pub fn frame(&self) -> MoveFrame {
+        MoveFrame::from_gravity(self.0)
+    }
+
+    // `world` along the actor's frame, x along the ground and y away from it
+    pub fn to_local(&self, world: Vec2) -> Vec2 {
+        self.frame().to_local(world)
+    }
+}
+
+// Adds gravity to every actor with a GravityScale, including ones that
+// integrate themselves. Zones come from the contacts detected last step.
+pub fn apply_gravity(
+    config: Res<PhysicsConfig>,
+    contacts: Res<TriggerContacts>,
+    zone_query: Query<&GravityZone, With<Sensor>>,
+    mut body_query: Query<(Entity, &mut Acceleration, &BodyType, &GravityScale, Option<&GravityDirection>, Option<&mut Gravity>), (Without<Inactive>, Without<Sleeping>, Without<Held>)>
+) {
+    // When zones overlap the lowest sensor entity wins so the pick doesn't flicker
+    let mut zones: HashMap<Entity, (Entity, &GravityZone)> = HashMap::new();
+    for (sensor, entity) in contacts.0.iter().copied() {
+        if let Ok(zone) = zone_query.get(sensor) {
+            let current = zones.entry(entity).or_insert((sensor, zone));
+            if sensor < current.0 {
+                *current = (sensor, zone);
+            }
+        }
+    }
+
+    for (entity, mut acceleration, body_type, gravity_scale, gravity_direction, gravity) in body_query.iter_mut() {
+        if *body_type != BodyType::Actor {
+            continue;
+        }
+
+        let (scale, direction) = match zones.get(&entity) {
+            Some((_, zone)) => (zone.scale, zone.direction),
+            None => (gravity_scale.0, gravity_direction.copied().unwrap_or_default().0)
+        };
+        let pull = direction.normalize_or_zero() * config.gravity.length() * scale;
+
+        acceleration.linear += pull;
+        if let Some(mut gravity) = gravity {
+            gravity.0 = pull;
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::{math::Vec2, prelude::{Query, Res, Time, Without}};
+
+use super::{activation::Inactive, sleep::Sleeping, body::{Acceleration, BodyType, CustomIntegration, Held, SpeedLimits, Velocity}, gravity::Gravity};
+
+// How a step turns acceleration into movement. Velocity always picks up the
+// full acceleration, the schemes differ in which velocity the body moves with.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrationScheme {
+    // Moves with the velocity from the end of the step
+    #[default]
+    SemiImplicitEuler,
+    // Moves with the average of the velocity at the start and end of the step.
+    // Exact under constant acceleration, so arcs hold up at low step rates.
+    VelocityVerlet
+}
+
+impl IntegrationScheme {
+    // Velocity to move with this step, given the velocity after integrating and
+    // the acceleration that was applied to get there
+    pub fn step_velocity(&self, velocity: Vec2, applied_acceleration: Vec2, delta_seconds: f32) -> Vec2 {
+        match self {
+            IntegrationScheme::SemiImplicitEuler => velocity,
+            IntegrationScheme::VelocityVerlet => velocity - applied_acceleration * delta_seconds * 0.5
+        }
+    }
+}
+
+// Applies an acceleration to the velocity, `limit` clamps the result. Returns
+// the acceleration that was actually applied once limited.
+pub fn integrate(velocity: &mut Vec2, acceleration: Vec2, delta_seconds: f32, limit: impl Fn(Vec2) -> Vec2) -> Vec2 {
+    let start = *velocity;
+    *velocity = limit(start + acceleration * delta_seconds);
+    if delta_seconds > 0.0 { (*velocity - start) / delta_seconds } else { Vec2::ZERO }
+}
+
+// Integrates a body's total acceleration. What was applied is left in
+// `linear` so the move can work out the step velocity, it clears it after.
+pub fn integrate_acceleration(velocity: &mut Velocity, acceleration: &mut Acceleration, delta_seconds: f32, limit: impl Fn(Vec2) -> Vec2) {
+    acceleration.linear = integrate(&mut velocity.0, acceleration.total(), delta_seconds, limit);
+}
+
+// Acceleration for every actor that doesn't integrate itself, gravity has
+// already been added to it by apply_gravity
+pub fn integrate_bodies(
+    time: Res<Time>,
+    mut body_query: Query<(&mut Velocity, &mut Acceleration, &BodyType, Option<&SpeedLimits>, Option<&Gravity>), (Without<CustomIntegration>, Without<Inactive>, Without<Sleeping>, Without<Held>)>
+) {
+    for (mut velocity, mut acceleration, body_type, speed_limits, gravity) in body_query.iter_mut() {
+        if *body_type != BodyType::Actor {
+            continue;
+        }
+
+        let up = gravity.map_or(1.0, Gravity::up);
+        integrate_acceleration(&mut velocity, &mut acceleration, time.delta_seconds(), |velocity| match speed_limits {
+            Some(limits) => Vec2::new(
+                velocity.x.clamp(-limits.max_horizontal, limits.max_horizontal),
+                clamp_fall(velocity.y, limits.max_fall, up)
+            ),
+            None => velocity
+        });
+    }
+}
+
+// Limits how fast `vertical` can fall, `max_fall` is a positive speed and
+// `up` is Gravity::up
+pub fn clamp_fall(vertical: f32, max_fall: f32, up: f32) -> f32 {
+    if up > 0.0 { vertical.max(-max_fall) } else { vertical.min(max_fall) }
+}
@@ -1,13 +1,115 @@
-use bevy::{core::{FixedTimestep, FixedTimesteps, Time}, math::{IVec2, Vec2}, prelude::{Color, Commands, CoreStage, Entity, IntoSystem, ParallelSystemDescriptorCoercion, Plugin, Query, QuerySet, Res, ResMut, StageLabel, SystemLabel, SystemStage, Transform}};
+use std::time::Duration;
 
-#[cfg(target_arch = "x86_64")]
-use bevy_canvas::{Canvas, DrawMode, common_shapes::{Rectangle, RectangleAnchor}};
+use bevy::{ecs::schedule::ScheduleLabel, math::{IVec2, Vec2}, prelude::{App, Color, Commands, Entity, EventWriter, First, FixedUpdate, Gizmos, Has, IntoSystemConfigs, IntoSystemSetConfigs, ParamSet, Plugin, Query, Res, ResMut, Resource, SystemSet, Time, Transform, Update, With, Without, World}, time::{Fixed, Virtual}};
 
-use bevy_egui::{EguiContext, egui::Window};
-use self::{body::{Acceleration, BodyBundle, BodyType, Position, Remainder, Velocity}, collision::{AABB, Collision, CollisionResult, Intersection, check_for_collision}};
+use bevy_egui::{EguiContexts, egui::Window};
+use self::{actor_collision::{CollideWithActors, nearby_actors, separate_actors}, solid_cache::{SolidColliderCache, update_solid_cache}, activation::{ActivationSettings, Inactive, WakeBody, update_activation}, gravity::apply_gravity, sleep::{SleepSettings, Sleeping, update_sleep}, shape::{CapsuleCollider, CircleCollider, RoundShape}, integrator::{IntegrationScheme, integrate_bodies}, broad_phase::{SpatialHash, update_spatial_hash}, depenetration::depenetrate_actors, solid::{Squished, despawn_squished, move_solid}, cast_debug::{CastDebugHistory, CastLog, debug_cast_history, draw_cast_history}, body::{Acceleration, BodyType, CustomIntegration, Held, Position, Remainder, RootMotion, Velocity}, controller::{CharacterController, ControllerMove, MoveFrame, orient_to_gravity, update_grounded}, gravity::Gravity, collision::{AABB, Collision, CollisionEvent, CollisionExclusions, CollisionResult, DropThrough, PhysicsMaterial, Slope, SolidCollider, SurfaceVelocity, cast_aabb, check_for_collision}, trigger::{Sensor, TriggerContacts, TriggerEnter, TriggerExit, TriggerStay, detect_triggers}, world::{PhysicsWorld, update_physics_world}};
 
+pub mod activation;
+pub mod actor_collision;
 pub mod collision;
 pub mod body;
+pub mod cast_debug;
+pub mod controller;
+pub mod solid;
+pub mod solid_cache;
+pub mod broad_phase;
+pub mod depenetration;
+pub mod gravity;
+pub mod integrator;
+pub mod shape;
+pub mod sleep;
+pub mod trigger;
+pub mod world;
+
+// Velocities, accelerations and gravity are all in units, movement converts them
+// to pixels when it actually moves bodies. Retuning for a different art scale
+// only needs pixels_per_unit changed.
+#[derive(Resource, Debug, Clone)]
+pub struct PhysicsConfig {
+    pub pixels_per_unit: f32,
+    // Most pixels a body can move along one axis in a single step
+    pub max_step_pixels: u32,
+    pub gravity: Vec2,
+    pub integration: IntegrationScheme
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            // One 16 pixel tile
+            pixels_per_unit: 16.0,
+            max_step_pixels: 16,
+            gravity: Vec2::new(0.0, -46.875),
+            integration: IntegrationScheme::default()
+        }
+    }
+}
+
+impl PhysicsConfig {
+    // Pixels to move this step for a velocity in units per second
+    pub fn step_pixels(&self, velocity: Vec2, delta_seconds: f32) -> Vec2 {
+        let max = self.max_step_pixels as f32;
+        (velocity * self.pixels_per_unit * delta_seconds).clamp(Vec2::splat(-max), Vec2::splat(max))
+    }
+}
+
+// How the fixed step is split up. Every fixed step runs the PhysicsStep
+// schedule `substeps` times with the step's time divided between them, so
+// fast bodies move less per pass. `max_dt` is the most frame time the fixed
+// step will try to catch up on at once, in seconds, so a long hitch like
+// dragging the window or sitting on a breakpoint doesn't come out as a burst
+// of steps. `max_steps_per_frame` caps the same thing in steps, so a frame
+// that runs slow because of the physics can't snowball into more and more
+// steps every frame.
+#[derive(Resource, Debug, Clone)]
+pub struct PhysicsSettings {
+    pub substeps: u32,
+    pub max_dt: f32,
+    pub max_steps_per_frame: u32
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        PhysicsSettings {
+            substeps: 1,
+            max_dt: 0.1,
+            max_steps_per_frame: 5
+        }
+    }
+}
+
+// Everything in StepSystemLabels, run from PhysicsStages::Step once per substep
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhysicsStep;
+
+fn run_physics_substeps(world: &mut World) {
+    let substeps = world.resource::<PhysicsSettings>().substeps.max(1);
+    let time = *world.resource::<Time>();
+
+    // Systems in the step read the usual Time, point it at each substep in turn
+    let mut substep_time = Time::<()>::default();
+    substep_time.advance_to(time.elapsed() - time.delta());
+    for _ in 0..substeps {
+        substep_time.advance_by(time.delta() / substeps);
+        *world.resource_mut::<Time>() = substep_time;
+        let _ = world.try_run_schedule(PhysicsStep);
+    }
+    *world.resource_mut::<Time>() = time;
+}
+
+// Checked every frame since the fixed timestep can be changed from anywhere
+fn clamp_frame_time(
+    settings: Res<PhysicsSettings>,
+    fixed_time: Res<Time<Fixed>>,
+    mut time: ResMut<Time<Virtual>>
+) {
+    let max_steps = fixed_time.timestep() * settings.max_steps_per_frame.max(1);
+    let max_delta = Duration::from_secs_f32(settings.max_dt.max(0.0)).min(max_steps);
+    if time.max_delta() != max_delta {
+        time.set_max_delta(max_delta);
+    }
+}
 
 fn apply_body_position_to_transform(
     mut transform_body_query: Query<(&mut Transform, &Position)>
@@ -18,27 +120,186 @@ fn apply_body_position_to_transform(
     }
 }
 
-fn move_x(
+// Height of the lowest free spot within step_height pixels above the blocked
+// step, only while standing on something. Above and below follow `frame`.
+fn find_step_up(
+    frame: MoveFrame,
+    position: &Vec2,
+    sign: i32,
+    collider: &AABB,
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    step_height: u32,
+) -> Option<f32> {
+    let (up, step) = (frame.up(), frame.side() * sign);
+    ground_below(position, frame.down(), collider, solid_colliders, ignore)?;
+
+    for height in 1..=step_height as i32 {
+        let above = *position + (up * height).as_vec2();
+        if check_for_collision(collider, &above, up, solid_colliders, ignore).is_some() {
+            // Head hit the ceiling before clearing the ledge
+            return None;
+        }
+
+        let next = above + step.as_vec2();
+        if check_for_collision(collider, &next, step, solid_colliders, ignore).is_none() {
+            return Some(height as f32);
+        }
+    }
+
+    None
+}
+
+// Most pixels an actor gets lifted or lowered per pixel walked to follow a slope
+const SLOPE_SNAP: u32 = 2;
+
+fn is_slope(entity: Entity, solid_colliders: &[SolidCollider]) -> bool {
+    solid_colliders.iter().any(|solid| solid.entity == entity && solid.slope.is_some())
+}
+
+// Whatever is a pixel away in the `down` direction
+fn ground_below(position: &Vec2, down: IVec2, collider: &AABB, solid_colliders: &[SolidCollider], ignore: Option<Entity>) -> Option<Collision> {
+    check_for_collision(collider, &(*position + down.as_vec2()), down, solid_colliders, ignore)
+}
+
+// Moves longer than this many pixels sweep ahead first instead of testing every pixel
+const SWEEP_THRESHOLD: i32 = 8;
+
+// Pixels of `movement` along one axis that are known to be free, so fast
+// bodies only pixel step the last bit before whatever they hit
+fn free_pixels(movement: i32, axis: usize, position: &Vec2, collider: &AABB, solid_colliders: &[SolidCollider], ignore: Option<Entity>, casts: &mut CastLog) -> i32 {
+    if movement.abs() <= SWEEP_THRESHOLD {
+        return 0;
+    }
+
+    let mut delta = Vec2::ZERO;
+    delta[axis] = movement as f32;
+    let hit = cast_aabb(collider, position.round(), delta, solid_colliders, ignore);
+    casts.sweep(collider, position.round(), delta, hit.as_ref());
+    match hit {
+        Some(hit) => {
+            let reach = (hit.time * movement as f32).trunc() as i32;
+            // Stop a pixel short so the pixel steps still see the contact
+            if reach.abs() > 1 { reach - movement.signum() } else { 0 }
+        }
+        None => movement
+    }
+}
+
+// World axis a unit direction runs along
+fn axis_of(direction: IVec2) -> usize {
+    if direction.x != 0 { 0 } else { 1 }
+}
+
+// Walks `move_amount` pixels along the frame's side. Also returns how far the
+// actor was moved up to get up steps and follow slopes, that movement isn't
+// part of its velocity. Slopes are only followed the right way up.
+fn move_across(
+    frame: MoveFrame,
     move_amount: &f32,
-    position: &mut Position, 
-    remainder: &mut Remainder, 
+    position: &mut Position,
+    remainder: &mut Remainder,
     collider: &AABB,
-    solid_colliders: &Vec<(Vec2, AABB)>,
-) -> Option<Collision> {
-    remainder.0.x += move_amount;
-    let mut movement: i32 = remainder.0.x.round() as i32;
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    step_height: u32,
+    casts: &mut CastLog,
+) -> (Option<Collision>, f32) {
+    let (up, down, axis) = (frame.up(), frame.down(), axis_of(frame.side()));
+    // The side always points along a positive axis, so local and world amounts match
+    remainder.0[axis] += move_amount;
+    let mut movement: i32 = remainder.0[axis].round() as i32;
+    let mut climbed = 0.0;
 
     if movement != 0i32 {
-        remainder.0.x -= movement as f32;
+        remainder.0[axis] -= movement as f32;
         let sign = movement.signum();
+        let step = frame.side() * sign;
+
+        // Steps and slopes need every pixel checked, so only skip ahead in the air
+        if ground_below(&position.0, down, collider, solid_colliders, ignore).is_none() {
+            let free = free_pixels(movement, axis, &position.0, collider, solid_colliders, ignore, casts);
+            position.0[axis] += free as f32;
+            movement -= free;
+        }
+
         while movement != 0i32 {
-            let next = Position(position.0 + Vec2::new(sign as f32, 0.0));
-            if let Some(collision) = check_for_collision( &collider, &next.0, &solid_colliders) {
+            let ground = ground_below(&position.0, down, collider, solid_colliders, ignore);
+            let next = Position(position.0 + step.as_vec2());
+            if let Some(collision) = check_for_collision(collider, &next.0, step, solid_colliders, ignore) {
+                // Walk up slopes instead of stopping against them
+                let slope_height = if frame.is_upright() && is_slope(collision.entity, solid_colliders) {
+                    (1..=SLOPE_SNAP as i32).find(|height| {
+                        let above = next.0 + (up * *height).as_vec2();
+                        check_for_collision(collider, &above, up, solid_colliders, ignore).is_none()
+                    })
+                } else {
+                    None
+                };
+                let height = slope_height.map(|height| height as f32)
+                    .or_else(|| find_step_up(frame, &position.0, sign, collider, solid_colliders, ignore, step_height));
+                if let Some(height) = height {
+                    position.0 += step.as_vec2() + up.as_vec2() * height;
+                    climbed += height;
+                    movement -= sign;
+                    continue;
+                }
                 // STOP WE HIT SOMETHING
-                return Some(collision);
+                return (Some(collision), climbed);
             } else {
-                position.0.x += sign as f32;
+                position.0 += step.as_vec2();
                 movement -= sign;
+
+                // Stick to slopes going down rather than walking off into the air
+                if frame.is_upright() && ground.is_some() && ground_below(&position.0, down, collider, solid_colliders, ignore).is_none() {
+                    let drop = (1..=SLOPE_SNAP as i32).find_map(|depth| {
+                        let below = position.0 + (down * depth).as_vec2();
+                        ground_below(&below, down, collider, solid_colliders, ignore).map(|collision| (depth, collision))
+                    });
+                    if let Some((depth, collision)) = drop {
+                        let was_on_slope = ground.is_some_and(|ground| is_slope(ground.entity, solid_colliders));
+                        if was_on_slope || is_slope(collision.entity, solid_colliders) {
+                            position.0 += (down * depth).as_vec2();
+                            climbed -= depth as f32;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    (None, climbed)
+}
+
+// Closest nudge along the frame's side, within corner_correction pixels, that
+// lets a box blocked moving up carry on upwards
+fn find_corner_nudge(
+    frame: MoveFrame,
+    position: &Vec2,
+    collider: &AABB,
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    corner_correction: u32,
+) -> Option<Vec2> {
+    let up = frame.up();
+    let mut blocked = [false, false];
+    for offset in 1..=corner_correction as i32 {
+        for (side, sign) in [-1, 1].iter().copied().enumerate() {
+            if blocked[side] {
+                continue;
+            }
+
+            let step = frame.side() * sign;
+            let nudge = (step * offset).as_vec2();
+            let nudged = *position + nudge;
+            if check_for_collision(collider, &nudged, step, solid_colliders, ignore).is_some() {
+                blocked[side] = true;
+                continue;
+            }
+
+            let above = nudged + up.as_vec2();
+            if check_for_collision(collider, &above, up, solid_colliders, ignore).is_none() {
+                return Some(nudge);
             }
         }
     }
@@ -46,27 +307,45 @@ fn move_x(
     None
 }
 
-fn move_y(
+// Moves `move_amount` pixels along the frame's up, negative falls
+fn move_along(
+    frame: MoveFrame,
     move_amount: &f32,
-    position: &mut Position, 
-    remainder: &mut Remainder, 
+    position: &mut Position,
+    remainder: &mut Remainder,
     collider: &AABB,
-    solid_colliders: &Vec<(Vec2, AABB)>,
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    corner_correction: u32,
+    casts: &mut CastLog,
 ) -> Option<Collision> {
-    // println!("Remainder {:?}", remainder);
-    remainder.0.y += move_amount;
-    let mut movement: i32 = remainder.0.y.round() as i32;
+    let axis = axis_of(frame.up());
+    let up_sign = frame.up()[axis];
+    remainder.0[axis] += move_amount * up_sign as f32;
+    let mut movement: i32 = remainder.0[axis].round() as i32;
 
     if movement != 0i32 {
-        remainder.0.y -= movement as f32;
+        remainder.0[axis] -= movement as f32;
         let sign = movement.signum();
+        let mut step = IVec2::ZERO;
+        step[axis] = sign;
+        let free = free_pixels(movement, axis, &position.0, collider, solid_colliders, ignore, casts);
+        position.0[axis] += free as f32;
+        movement -= free;
+
         while movement != 0i32 {
-            let next = Position(position.0 + Vec2::new(0.0, sign as f32));
-            if let Some(collision) = check_for_collision(&collider, &next.0 , &solid_colliders) {
+            let next = Position(position.0 + step.as_vec2());
+            if let Some(collision) = check_for_collision(collider, &next.0, step, solid_colliders, ignore) {
+                if sign == up_sign {
+                    if let Some(nudge) = find_corner_nudge(frame, &position.0, collider, solid_colliders, ignore, corner_correction) {
+                        position.0 += nudge;
+                        continue;
+                    }
+                }
                 // STOP WE HIT SOMETHING
                 return Some(collision);
             } else {
-                position.0.y += sign as f32;
+                position.0 += step.as_vec2();
                 movement -= sign;
             }
         }
@@ -75,46 +354,198 @@ fn move_y(
     None
 }
 
+// World axis moves for things that don't care which way is up, like solids
+// pushing actors around
+fn move_x(
+    move_amount: &f32,
+    position: &mut Position,
+    remainder: &mut Remainder,
+    collider: &AABB,
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    step_height: u32,
+    casts: &mut CastLog,
+) -> (Option<Collision>, f32) {
+    move_across(MoveFrame::UPRIGHT, move_amount, position, remainder, collider, solid_colliders, ignore, step_height, casts)
+}
+
+fn move_y(
+    move_amount: &f32,
+    position: &mut Position,
+    remainder: &mut Remainder,
+    collider: &AABB,
+    solid_colliders: &[SolidCollider],
+    ignore: Option<Entity>,
+    corner_correction: u32,
+    casts: &mut CastLog,
+) -> Option<Collision> {
+    move_along(MoveFrame::UPRIGHT, move_amount, position, remainder, collider, solid_colliders, ignore, corner_correction, casts)
+}
+
+// Slower hits than this, in units, just stop instead of bouncing so actors
+// on bouncy solids come to rest
+const MIN_BOUNCE_SPEED: f32 = 1.0;
+
+// Static solids close to a box swept by `movement`, plus every moving solid
+pub fn nearby_solids(
+    spatial_hash: &SpatialHash,
+    moving_solids: &[SolidCollider],
+    position: Vec2,
+    collider: &AABB,
+    movement: Vec2,
+) -> Vec<SolidCollider> {
+    let min = position + collider.min().as_vec2() + movement.min(Vec2::ZERO) - Vec2::ONE;
+    let max = position + collider.max().as_vec2() + movement.max(Vec2::ZERO) + Vec2::ONE;
+    let mut solids = spatial_hash.query(min, max);
+    solids.extend_from_slice(moving_solids);
+    solids
+}
+
 fn move_actor(
     mut commands: Commands,
     time: Res<Time>,
-    fixed_timesteps: Res<FixedTimesteps>,
-    mut stuff: QuerySet<(
-        Query<(Entity, &mut Position, &mut Velocity, &mut Acceleration, &mut Remainder, &AABB, &BodyType)>,
-        Query<(&Position, &AABB, &BodyType)>
+    config: Res<PhysicsConfig>,
+    spatial_hash: Res<SpatialHash>,
+    cache: Res<SolidColliderCache>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    material_query: Query<&PhysicsMaterial>,
+    surface_query: Query<&SurfaceVelocity>,
+    mut cast_history: Option<ResMut<CastDebugHistory>>,
+    mut stuff: ParamSet<(
+        Query<(Entity, &mut Position, &mut Velocity, &mut Acceleration, &mut Remainder, &AABB, &BodyType, Option<&DropThrough>, Option<&mut CharacterController>, Option<&CustomIntegration>, Has<CollideWithActors>, Option<&Gravity>, Option<&CollisionExclusions>, Option<&mut RootMotion>), (Without<Inactive>, Without<Sleeping>, Without<Held>)>,
+        Query<&Position>,
+        Query<(Entity, &Position, &AABB, &BodyType), (With<CollideWithActors>, Without<Inactive>)>
     )>
 ) {
-    let solid_colliders: Vec<(Vec2, AABB)> = stuff.q1().iter().filter(|(_, _, body_type)| {
-        **body_type == BodyType::Solid
-    }).map(|(position, aabb, _)| {
-        (position.0, *aabb)
+    // Moving solids have already moved this step, so use where they are now
+    let moving_solids: Vec<SolidCollider> = cache.moving_solids().into_iter().filter_map(|solid| {
+        stuff.p1().get(solid.entity).ok().map(|position| SolidCollider {
+            position: position.0,
+            ..solid
+        })
     }).collect();
+    // Where the other colliding actors are, kept up to date as each one moves
+    let mut actors: Vec<(Entity, Vec2, AABB)> = stuff.p2().iter()
+        .filter(|(.., body_type)| **body_type == BodyType::Actor)
+        .map(|(entity, position, collider, _)| (entity, position.0, *collider))
+        .collect();
+    let mut casts = CastLog::new("move_actor", cast_history.as_deref());
 
-    for (entity, mut position, mut velocity, mut acceleration, mut remainder, collider, body_type) in stuff.q0_mut().iter_mut() {
+    for (entity, mut position, mut velocity, mut acceleration, mut remainder, collider, body_type, drop_through, controller, custom_integration, collides_with_actors, gravity, exclusions, root_motion) in stuff.p0().iter_mut() {
         if *body_type == BodyType::Actor {
-            let move_amount = velocity.0 * time.delta_seconds();
+            // Walls and ceilings are walked along when gravity pulls that way
+            let frame = gravity.map_or(MoveFrame::UPRIGHT, |gravity| gravity.frame());
+            let (up, side) = (frame.up().as_vec2(), frame.side().as_vec2());
+            let step_velocity = config.integration.step_velocity(velocity.0, acceleration.linear, time.delta_seconds());
+            let ignore = drop_through.map(|drop_through| drop_through.platform);
+            // Whatever the actor is standing on can carry it along on top of its own velocity
+            let surface_velocity = ground_below(&position.0, frame.down(), collider, &nearby_solids(&spatial_hash, &moving_solids, position.0, collider, Vec2::ZERO), ignore)
+                .and_then(|ground| surface_query.get(ground.entity).ok())
+                .map_or(Vec2::ZERO, |surface| surface.0);
+            let mut move_amount = config.step_pixels(step_velocity + surface_velocity, time.delta_seconds());
+            // Root motion doesn't count towards the velocity, like surface velocity
+            let mut root_moved = Vec2::ZERO;
+            if let Some(mut root_motion) = root_motion.filter(|root_motion| root_motion.0 != Vec2::ZERO) {
+                let max = config.max_step_pixels as f32;
+                let total = move_amount + root_motion.0;
+                root_moved = total.clamp(Vec2::splat(-max), Vec2::splat(max)) - move_amount;
+                root_motion.0 -= root_moved;
+                move_amount += root_moved;
+            }
             let start_position = position.0;
-            let x_collision = move_x(&move_amount.x, &mut position, &mut remainder, collider, &solid_colliders);
-            let y_collision = move_y(&move_amount.y, &mut position, &mut remainder, collider, &solid_colliders);
-            commands.entity(entity).insert(CollisionResult {
+            let mut solid_colliders = nearby_solids(&spatial_hash, &moving_solids, position.0, collider, move_amount);
+            if collides_with_actors {
+                solid_colliders.extend(nearby_actors(&actors, entity, position.0, collider, move_amount));
+            }
+            if let Some(exclusions) = exclusions {
+                solid_colliders.retain(|solid| !exclusions.contains(solid.entity));
+            }
+            let mut default_controller = CharacterController::default();
+            let controller = controller.map_or(&mut default_controller, |controller| controller.into_inner());
+            let ControllerMove { x_collision, y_collision, climbed } = controller.move_and_collide(frame, move_amount, &mut position, &mut remainder, collider, &solid_colliders, ignore, &mut casts);
+            let moved = position.0 - start_position - up * climbed;
+            let (x_moved, y_moved) = (moved.x, moved.y);
+            if let Some(actor) = actors.iter_mut().find(|(other, ..)| *other == entity) {
+                actor.1 = position.0;
+            }
+
+            if let Some(collision) = &x_collision {
+                collision_events.send(CollisionEvent {
+                    entity,
+                    other: collision.entity,
+                    normal: Vec2::new(-move_amount.x.signum(), 0.0),
+                    penetration: (move_amount.x.abs() - x_moved.abs()).max(0.0)
+                });
+            }
+            if let Some(collision) = &y_collision {
+                collision_events.send(CollisionEvent {
+                    entity,
+                    other: collision.entity,
+                    normal: Vec2::new(0.0, -move_amount.y.signum()),
+                    penetration: (move_amount.y.abs() - y_moved.abs()).max(0.0)
+                });
+            }
+
+            commands.entity(entity).insert((CollisionResult {
                 x_collision_body: x_collision,
                 y_collision_body: y_collision,
-            });
-            velocity.0 = (position.0 - start_position) / time.delta_seconds();
-            acceleration.0 = Vec2::ZERO;
+            }, controller.touching()));
+            velocity.0 = (moved - root_moved) / time.delta_seconds() / config.pixels_per_unit - surface_velocity;
+
+            // Bounce and friction come from whatever was hit along up, the floor or ceiling of the frame
+            let along_collision = if frame.side() == IVec2::X { y_collision } else { x_collision };
+            if let Some(material) = along_collision.as_ref().and_then(|collision| material_query.get(collision.entity).ok()) {
+                let along_speed = step_velocity.dot(up);
+                if along_speed.abs() > MIN_BOUNCE_SPEED {
+                    let bounce = -along_speed * material.restitution - velocity.0.dot(up);
+                    velocity.0 += up * bounce;
+                }
+                // Bodies that integrate themselves apply friction their own way
+                if move_amount.dot(up) < 0.0 && custom_integration.is_none() {
+                    let slide = velocity.0.dot(side);
+                    velocity.0 -= side * slide * material.friction.clamp(0.0, 1.0);
+                }
+            }
+            acceleration.linear = Vec2::ZERO;
+        }
+    }
+
+    casts.finish(cast_history.as_deref_mut());
+}
+
+fn expire_collision_exclusions(
+    time: Res<Time>,
+    mut exclusions_query: Query<&mut CollisionExclusions>
+) {
+    for mut exclusions in exclusions_query.iter_mut() {
+        // Lists without timed entries are left alone so change detection doesn't fire
+        if exclusions.0.iter().any(|exclusion| exclusion.timer.is_some()) {
+            exclusions.0.retain_mut(|exclusion| exclusion.timer.as_mut().is_none_or(|timer| !timer.tick(time.delta()).finished()));
+        }
+    }
+}
+
+fn tick_drop_through(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut drop_through_query: Query<(Entity, &mut DropThrough)>
+) {
+    for (entity, mut drop_through) in drop_through_query.iter_mut() {
+        if drop_through.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<DropThrough>();
         }
     }
 }
 
+#[allow(dead_code)]
 fn debug_body_information(
-    mut egui_ctx: ResMut<EguiContext>,
+    mut egui_ctx: EguiContexts,
     actors_query: Query<(&Position, &Velocity, &Acceleration, &Remainder, &AABB, &BodyType)>,
     solids_query: Query<(&Position, &Velocity, &Remainder, &AABB, &BodyType)>
 ) {
-    Window::new("Bodies").scroll(true).show(egui_ctx.ctx(), |ui| {
+    Window::new("Bodies").vscroll(true).show(egui_ctx.ctx_mut(), |ui| {
         ui.collapsing("Actors", |ui| {
-            let mut i = 0u32;
-            for (pos, vel, accel, remain, aabb, _) in actors_query.iter().filter(|(_, _, _, _, _, body_type)| { return **body_type == BodyType::Actor; }) {
+            for (i, (pos, vel, accel, remain, aabb, _)) in actors_query.iter().filter(|(_, _, _, _, _, body_type)| { **body_type == BodyType::Actor }).enumerate() {
                 ui.collapsing(format!("Actor {}", i), |ui| {
                     ui.label(format!("Position: {:?}", pos));
                     ui.label(format!("Velocity: {:?}", vel));
@@ -122,14 +553,13 @@ fn debug_body_information(
                     ui.label(format!("Remainder: {:?}", remain));
                     ui.label(format!("AABB: {:?}", aabb));
                 });
-                i += 1;
             }
         });
 
         ui.separator();
 
         ui.collapsing("Solids", |ui| {
-            for (pos, vel, remain, aabb, _) in solids_query.iter().filter(|(_, _, _, _, body_type)| { return **body_type == BodyType::Solid; }) {
+            for (pos, vel, remain, aabb, _) in solids_query.iter().filter(|(_, _, _, _, body_type)| { **body_type == BodyType::Solid }) {
                 ui.label(format!("Position: {:?}", pos));
                 ui.label(format!("Velocity: {:?}", vel));
                 ui.label(format!("Remainder: {:?}", remain));
@@ -139,64 +569,189 @@ fn debug_body_information(
     });
 }
 
-#[cfg(target_arch = "x86_64")]
+// What debug_aabb draws, toggled from the "Physics Debug" window
+#[derive(Resource, Debug, Clone)]
+pub struct PhysicsDebugConfig {
+    pub colliders: bool,
+    pub velocity: bool,
+    pub contacts: bool,
+    pub normals: bool
+}
+
+impl Default for PhysicsDebugConfig {
+    fn default() -> Self {
+        PhysicsDebugConfig {
+            colliders: true,
+            velocity: false,
+            contacts: false,
+            normals: false
+        }
+    }
+}
+
+// Velocity arrows point to where the body would be this long from now
+const VELOCITY_ARROW_SECONDS: f32 = 0.1;
+const NORMAL_LENGTH: f32 = 8.0;
+
+fn draw_arrow(gizmos: &mut Gizmos, start: Vec2, end: Vec2, color: Color) {
+    gizmos.line_2d(start, end, color);
+    // Head is two short lines folded back about 30 degrees either side
+    let back = (start - end).clamp_length_max(3.0);
+    for angle in [0.5f32, -0.5].iter().copied() {
+        gizmos.line_2d(end, end + Vec2::from_angle(angle).rotate(back), color);
+    }
+}
+
 fn debug_aabb(
-    mut canvas: ResMut<Canvas>,
-    aabb_qery: Query<(&Position, &AABB, &BodyType)>,
+    mut gizmos: Gizmos,
+    debug_config: Res<PhysicsDebugConfig>,
+    config: Res<PhysicsConfig>,
+    aabb_qery: Query<(&Position, &AABB, &BodyType, Option<&Sensor>, Option<&Slope>, Option<&CircleCollider>, Option<&CapsuleCollider>, Option<&Velocity>, Option<&CollisionResult>)>,
 ) {
-    for (position, aabb, body_type) in aabb_qery.iter() {
+    for (position, aabb, body_type, sensor, slope, circle, capsule, velocity, collision_result) in aabb_qery.iter() {
+        let center = position.0 + aabb.position.as_vec2();
+        if let Some(velocity) = velocity.filter(|velocity| debug_config.velocity && velocity.0 != Vec2::ZERO) {
+            draw_arrow(&mut gizmos, center, center + velocity.0 * config.pixels_per_unit * VELOCITY_ARROW_SECONDS, Color::CYAN);
+        }
+
+        // Contacts sit on the side of the actor that got stopped
+        let contacts = collision_result.iter().flat_map(|result| result.x_collision_body.iter().chain(result.y_collision_body.iter()));
+        for collision in contacts {
+            let contact = center - collision.normal * aabb.half_size.as_vec2();
+            if debug_config.contacts {
+                gizmos.circle_2d(contact, 1.5, Color::FUCHSIA);
+            }
+            if debug_config.normals {
+                gizmos.line_2d(contact, contact + collision.normal * NORMAL_LENGTH, Color::WHITE);
+            }
+        }
+
+        if !debug_config.colliders {
+            continue;
+        }
+
+        if let Some(slope) = slope {
+            let solid_box = collision::world_aabb(&position.0, aabb);
+            let (min_x, max_x) = (solid_box.min().x, solid_box.max().x);
+            gizmos.line_2d(
+                Vec2::new(min_x as f32, slope.height_at(&solid_box, min_x) as f32),
+                Vec2::new(max_x as f32, slope.height_at(&solid_box, max_x) as f32),
+                Color::ORANGE
+            );
+        }
+
+        let color = if sensor.is_some() { Color::YELLOW } else if *body_type == BodyType::Actor { Color::GREEN } else { Color::RED };
+        if let Some(round) = RoundShape::from_components(circle, capsule) {
+            let shape = round.at(position.0.round());
+            gizmos.circle_2d(shape.min, shape.radius, color);
+            if shape.max != shape.min {
+                gizmos.circle_2d(shape.max, shape.radius, color);
+                gizmos.line_2d(shape.min - Vec2::new(shape.radius, 0.0), shape.max - Vec2::new(shape.radius, 0.0), color);
+                gizmos.line_2d(shape.min + Vec2::new(shape.radius, 0.0), shape.max + Vec2::new(shape.radius, 0.0), color);
+            }
+        }
+
         let temp_extents = aabb.half_size * 2i32;
-        let color = if *body_type == BodyType::Actor { Color::GREEN } else { Color::RED };
-        canvas.draw(&Rectangle {
-            origin: position.0 + Vec2::new(aabb.position.x as f32, aabb.position.y as f32),
-            extents: Vec2::new(temp_extents.x as f32, temp_extents.y as f32),
-            anchor_point: RectangleAnchor::Center
-        }, DrawMode::stroke_1px(), color);
+        gizmos.rect_2d(
+            position.0 + Vec2::new(aabb.position.x as f32, aabb.position.y as f32),
+            0.0,
+            Vec2::new(temp_extents.x as f32, temp_extents.y as f32),
+            color
+        );
     }
 }
 
+fn debug_physics_config(
+    mut egui_ctx: EguiContexts,
+    mut debug_config: ResMut<PhysicsDebugConfig>
+) {
+    Window::new("Physics Debug").show(egui_ctx.ctx_mut(), |ui| {
+        ui.checkbox(&mut debug_config.colliders, "Colliders");
+        ui.checkbox(&mut debug_config.velocity, "Velocity");
+        ui.checkbox(&mut debug_config.contacts, "Contact points");
+        ui.checkbox(&mut debug_config.normals, "Contact normals");
+    });
+}
+
 pub struct DebugPhysicsPlugin;
 
 impl Plugin for DebugPhysicsPlugin {
-    fn build(&self, app: &mut bevy::prelude::AppBuilder) {
-        #[cfg(target_arch = "x86_64")]
-        app.add_system(debug_aabb.system());
-        // app.add_system_to_stage(PhysicsStages::PreStep, debug_body_information.system());
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CastDebugHistory>()
+            .init_resource::<PhysicsDebugConfig>()
+            .add_systems(Update, (debug_aabb, debug_physics_config, draw_cast_history, debug_cast_history));
+        // app.add_systems(Update, debug_body_information);
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum PhysicsStages {
     PreStep,
     Step,
     PostStep
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum StepSystemLabels {
+    // Brings the solid cache and spatial hash up to date with whatever the
+    // pre step systems changed
+    BroadPhase,
+    // Adds gravity to the acceleration before anything integrates it
+    Gravity,
     Integrate,
+    // Solids move first so actors see where platforms ended up this step
+    MoveSolids,
     MoveActors
 }
 
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
-    fn build(&self, app: &mut bevy::prelude::AppBuilder) {
-
-        // Step stages
-        app.add_stage_before(
-            CoreStage::Update,
-             PhysicsStages::Step, 
-             SystemStage::parallel()
-            // .with_run_criteria(
-            //     FixedTimestep::step(1.0 / 60.0).with_label("FIXED_TIME_STEP")
-            // )
-            .with_system(
-                move_actor.system().label(StepSystemLabels::MoveActors)
-            ));
-
-        // Pre and post stages
-        app.add_stage_before(PhysicsStages::Step, PhysicsStages::PreStep, SystemStage::parallel())
-            .add_stage_after(PhysicsStages::Step, PhysicsStages::PostStep, SystemStage::parallel().with_system(apply_body_position_to_transform.system()));
-    }
-}  
\ No newline at end of file
+    fn build(&self, app: &mut App) {
+        // The whole physics pipeline runs on a fixed timestep in the FixedUpdate schedule
+        app.insert_resource(Time::<Fixed>::from_hz(60.0))
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<PhysicsSettings>()
+            .init_resource::<TriggerContacts>()
+            .init_resource::<PhysicsWorld>()
+            .init_resource::<ActivationSettings>()
+            .init_resource::<SleepSettings>()
+            .add_event::<WakeBody>()
+            .add_event::<CollisionEvent>()
+            .add_event::<Squished>()
+            .add_event::<TriggerEnter>()
+            .add_event::<TriggerStay>()
+            .add_event::<TriggerExit>();
+
+        app.configure_sets(FixedUpdate, (
+            PhysicsStages::PreStep,
+            PhysicsStages::Step,
+            PhysicsStages::PostStep
+        ).chain());
+
+        app.configure_sets(PhysicsStep, (
+            StepSystemLabels::BroadPhase,
+            StepSystemLabels::Gravity,
+            StepSystemLabels::Integrate,
+            StepSystemLabels::MoveSolids,
+            StepSystemLabels::MoveActors
+        ).chain());
+        app.add_systems(First, clamp_frame_time);
+
+        // Pre stages
+        app.add_systems(FixedUpdate, (update_activation, tick_drop_through, expire_collision_exclusions).in_set(PhysicsStages::PreStep));
+
+        // Step stages, these go in the PhysicsStep schedule
+        app.add_systems(FixedUpdate, run_physics_substeps.in_set(PhysicsStages::Step));
+        app.init_resource::<SolidColliderCache>().init_resource::<SpatialHash>();
+        app.add_systems(PhysicsStep, (update_sleep, update_solid_cache, update_spatial_hash, orient_to_gravity, depenetrate_actors).chain().in_set(StepSystemLabels::BroadPhase));
+
+        app.add_systems(PhysicsStep, apply_gravity.in_set(StepSystemLabels::Gravity));
+        app.add_systems(PhysicsStep, integrate_bodies.in_set(StepSystemLabels::Integrate));
+        app.add_systems(PhysicsStep, move_solid.in_set(StepSystemLabels::MoveSolids));
+        app.add_systems(PhysicsStep, (move_actor, separate_actors, update_grounded).chain().in_set(StepSystemLabels::MoveActors));
+
+        // Post stages
+        app.add_systems(FixedUpdate, (apply_body_position_to_transform, detect_triggers, update_physics_world, despawn_squished).in_set(PhysicsStages::PostStep));
+    }
+}
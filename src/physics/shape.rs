@@ -0,0 +1,96 @@
+use bevy::{math::Vec2, prelude::Component};
+
+use super::collision::{AABB, Intersection, world_aabb};
+
+// Round shapes for bodies whose AABB is only a bounding box around them. The
+// AABB still does the broad phase, sweeps and ray casts, the shape refines
+// overlaps for per pixel movement, pushing and sensors. Offsets and sizes are
+// in pixels from the body position.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
+pub struct CircleCollider {
+    pub offset: Vec2,
+    pub radius: f32
+}
+
+// Stands upright, two half circles `half_height` above and below the centre
+// joined by straight sides
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
+pub struct CapsuleCollider {
+    pub offset: Vec2,
+    pub half_height: f32,
+    pub radius: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundShape {
+    Circle(CircleCollider),
+    Capsule(CapsuleCollider)
+}
+
+impl RoundShape {
+    // Circles win if a body somehow has both
+    pub fn from_components(circle: Option<&CircleCollider>, capsule: Option<&CapsuleCollider>) -> Option<RoundShape> {
+        circle.map(|circle| RoundShape::Circle(*circle)).or_else(|| capsule.map(|capsule| RoundShape::Capsule(*capsule)))
+    }
+
+    pub fn at(&self, position: Vec2) -> RoundedBox {
+        match self {
+            RoundShape::Circle(circle) => RoundedBox {
+                min: position + circle.offset,
+                max: position + circle.offset,
+                radius: circle.radius
+            },
+            RoundShape::Capsule(capsule) => RoundedBox {
+                min: position + capsule.offset - Vec2::new(0.0, capsule.half_height),
+                max: position + capsule.offset + Vec2::new(0.0, capsule.half_height),
+                radius: capsule.radius
+            }
+        }
+    }
+}
+
+// Every shape in world space as a box with its edges pushed out by `radius`
+// and the corners rounded off. A plain AABB has no radius, a circle has no
+// size and an upright capsule has no width. Two of them overlap when the gap
+// between their boxes is shorter than both radii together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundedBox {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub radius: f32
+}
+
+impl RoundedBox {
+    pub fn from_aabb(world_box: &AABB) -> RoundedBox {
+        RoundedBox {
+            min: world_box.min().as_vec2(),
+            max: world_box.max().as_vec2(),
+            radius: 0.0
+        }
+    }
+
+    // Shortest distance between the two boxes, ignoring the radii
+    pub fn gap(&self, other: &RoundedBox) -> f32 {
+        (other.min - self.max).max(self.min - other.max).max(Vec2::ZERO).length()
+    }
+}
+
+impl Intersection<RoundedBox> for RoundedBox {
+    fn interescts(shape1: &Self, shape2: &RoundedBox) -> bool {
+        let radius = shape1.radius + shape2.radius;
+        if radius <= 0.0 {
+            // Two boxes, touching edges don't count same as AABB
+            return shape1.min.x < shape2.max.x && shape1.max.x > shape2.min.x
+                && shape1.min.y < shape2.max.y && shape1.max.y > shape2.min.y;
+        }
+        shape1.gap(shape2) < radius
+    }
+}
+
+// A body's shape placed in the world, its round shape if it has one
+pub fn world_shape(position: &Vec2, collider: &AABB, round: Option<&RoundShape>) -> RoundedBox {
+    match round {
+        Some(round) => round.at(position.round()),
+        None => RoundedBox::from_aabb(&world_aabb(position, collider))
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::{math::Vec2, prelude::{Commands, Component, Entity, EventReader, Query, Res, Resource, Without}};
+
+use super::{activation::{ActivationSource, Inactive, KeepAwake, WakeBody}, body::{Acceleration, BodyType, Position, Velocity}};
+
+// Put on actors that have sat still long enough. Gravity, integration and
+// movement skip them until something touches them, unlike Inactive they keep
+// animating and still get carried by platforms.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Sleeping;
+
+// How long an actor has been still, and where, to tell when it gets moved
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct StillSteps {
+    pub steps: u32,
+    pub position: Vec2
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SleepSettings {
+    pub enabled: bool,
+    // Speed in units below which an actor counts as still
+    pub velocity_threshold: f32,
+    // Steps an actor has to stay still for before it sleeps
+    pub steps: u32,
+    // Pixels around a moving body that sleeping actors get woken in
+    pub wake_radius: f32
+}
+
+impl Default for SleepSettings {
+    fn default() -> Self {
+        SleepSettings {
+            enabled: true,
+            velocity_threshold: 0.05,
+            steps: 30,
+            wake_radius: 48.0
+        }
+    }
+}
+
+// Puts still actors to sleep and wakes them when they get a velocity or a
+// force, get moved by something else, get a WakeBody or something moves near
+// them. Activation sources like the player never sleep.
+pub fn update_sleep(
+    mut commands: Commands,
+    settings: Res<SleepSettings>,
+    mut wake_events: EventReader<WakeBody>,
+    moving_query: Query<(&Position, &Velocity), (Without<Sleeping>, Without<Inactive>)>,
+    mut body_query: Query<(Entity, &Position, &Velocity, &Acceleration, &BodyType, Option<&mut StillSteps>, Option<&Sleeping>, Option<&KeepAwake>), (Without<ActivationSource>, Without<Inactive>)>
+) {
+    let woken: Vec<Entity> = wake_events.read().map(|WakeBody(entity)| *entity).collect();
+    if !settings.enabled {
+        for (entity, ..) in body_query.iter().filter(|(.., sleeping, _)| sleeping.is_some()) {
+            commands.entity(entity).remove::<Sleeping>();
+        }
+        return;
+    }
+
+    let moving: Vec<Vec2> = moving_query.iter()
+        .filter(|(_, velocity)| velocity.0.length() > settings.velocity_threshold)
+        .map(|(position, _)| position.0)
+        .collect();
+    let wake_radius_squared = settings.wake_radius * settings.wake_radius;
+
+    for (entity, position, velocity, acceleration, body_type, still_steps, sleeping, keep_awake) in body_query.iter_mut() {
+        if *body_type != BodyType::Actor {
+            continue;
+        }
+        let mut still_steps = match still_steps {
+            Some(still_steps) => still_steps,
+            None => {
+                commands.entity(entity).insert(StillSteps { steps: 0, position: position.0 });
+                continue;
+            }
+        };
+
+        let still = velocity.0.length() <= settings.velocity_threshold
+            && acceleration.total() == Vec2::ZERO
+            && still_steps.position == position.0
+            && keep_awake.is_none()
+            && !woken.contains(&entity);
+        let disturbed = sleeping.is_some()
+            && moving.iter().any(|other| other.distance_squared(position.0) <= wake_radius_squared);
+
+        if still && !disturbed {
+            still_steps.steps = still_steps.steps.saturating_add(1);
+            if still_steps.steps >= settings.steps && sleeping.is_none() {
+                commands.entity(entity).insert(Sleeping);
+            }
+        } else {
+            still_steps.steps = 0;
+            if sleeping.is_some() {
+                commands.entity(entity).remove::<Sleeping>();
+            }
+        }
+        still_steps.position = position.0;
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::{hierarchy::DespawnRecursiveExt, math::Vec2, prelude::{Commands, Component, Entity, Event, EventReader, EventWriter, Query, Res, ResMut, Time, Without}};
+
+use super::{PhysicsConfig, cast_debug::{CastDebugHistory, CastLog}, body::{BodyType, Position, Remainder, Velocity}, broad_phase::SpatialHash, solid_cache::SolidColliderCache, collision::{AABB, DropThrough, Intersection, OneWay, Slope, SolidCollider, world_aabb}, shape::{CapsuleCollider, CircleCollider, RoundShape, RoundedBox, world_shape}, move_x, move_y, nearby_solids, trigger::Sensor};
+
+// Sent for an actor that a moving solid tried to push into another solid
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Squished(pub Entity);
+
+// What happens to an actor when it gets squished. Actors without it are left
+// where they are and only the event is sent.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnSquished {
+    Despawn,
+    // Handled by whoever owns the actor's health, the player sets its Health to zero
+    Kill
+}
+
+// An actor rides a solid when it is standing right on top of it
+pub fn is_riding(actor_position: &Vec2, actor_collider: &AABB, solid_position: &Vec2, solid_collider: &AABB) -> bool {
+    AABB::interescts(
+        &world_aabb(&(*actor_position - Vec2::new(0.0, 1.0)), actor_collider),
+        &world_aabb(solid_position, solid_collider)
+    )
+}
+
+struct ActorState {
+    entity: Entity,
+    position: Position,
+    remainder: Remainder,
+    collider: AABB,
+    ignore: Option<Entity>,
+    // Solids this actor could get pushed into
+    nearby: Vec<SolidCollider>,
+    riding: bool,
+    squished: bool
+}
+
+// Moves a solid one axis at a time. Actors in the way get pushed out of the
+// solid and actors riding it get carried along. If a pushed actor hits another
+// solid it can't go anywhere so it gets squished. One way solids never push,
+// they only carry.
+fn move_solid_axis(
+    amount: f32,
+    axis: usize,
+    solid_position: &mut Vec2,
+    solid_collider: &AABB,
+    solid_round: Option<&RoundShape>,
+    one_way: bool,
+    actors: &mut [ActorState],
+    casts: &mut CastLog
+) {
+    if amount == 0.0 {
+        return;
+    }
+
+    solid_position[axis] += amount;
+    let solid_box = world_aabb(solid_position, solid_collider);
+    let solid_shape = world_shape(solid_position, solid_collider, solid_round);
+    let overlaps = |actor_box: &AABB| AABB::interescts(actor_box, &solid_box)
+        && RoundedBox::interescts(&RoundedBox::from_aabb(actor_box), &solid_shape);
+
+    for actor in actors.iter_mut() {
+        let actor_box = world_aabb(&actor.position.0, &actor.collider);
+        let (movement, pushed) = if !one_way && overlaps(&actor_box) {
+            // Push just far enough to be flush with the side the solid moved towards
+            let flush = if amount > 0.0 {
+                (solid_box.max()[axis] - actor_box.min()[axis]) as f32
+            } else {
+                (solid_box.min()[axis] - actor_box.max()[axis]) as f32
+            };
+            // Round solids only need to push until the actor clears the curve
+            let push = match solid_round {
+                Some(_) => (1..flush.abs() as i32).map(|pixels| pixels as f32 * flush.signum()).find(|push| {
+                    let mut pushed_position = actor.position.0;
+                    pushed_position[axis] += push;
+                    !overlaps(&world_aabb(&pushed_position, &actor.collider))
+                }).unwrap_or(flush),
+                None => flush
+            };
+            (push, true)
+        } else if actor.riding {
+            (amount, false)
+        } else {
+            continue;
+        };
+
+        let collision = if axis == 0 {
+            move_x(&movement, &mut actor.position, &mut actor.remainder, &actor.collider, &actor.nearby, actor.ignore, 0, casts).0
+        } else {
+            move_y(&movement, &mut actor.position, &mut actor.remainder, &actor.collider, &actor.nearby, actor.ignore, 0, casts)
+        };
+
+        if pushed && collision.is_some() {
+            actor.squished = true;
+        }
+    }
+}
+
+pub fn move_solid(
+    mut squished_events: EventWriter<Squished>,
+    time: Res<Time>,
+    config: Res<PhysicsConfig>,
+    spatial_hash: Res<SpatialHash>,
+    cache: Res<SolidColliderCache>,
+    mut body_query: Query<(Entity, &mut Position, &Velocity, &mut Remainder, &AABB, &BodyType, Option<&OneWay>, Option<&DropThrough>, Option<&Slope>), Without<Sensor>>,
+    shape_query: Query<(Option<&CircleCollider>, Option<&CapsuleCollider>)>,
+    mut cast_history: Option<ResMut<CastDebugHistory>>
+) {
+    let round_shape = |entity: Entity| shape_query.get(entity).ok().and_then(|(circle, capsule)| RoundShape::from_components(circle, capsule));
+
+    let moving_solids = cache.moving();
+    let mut casts = CastLog::new("move_solid", cast_history.as_deref());
+
+    for solid_entity in moving_solids.iter().copied() {
+        let (mut solid_position, solid_collider, one_way, movement) = match body_query.get_mut(solid_entity) {
+            Ok((_, position, velocity, mut remainder, collider, _, one_way, ..)) => {
+                remainder.0 += config.step_pixels(velocity.0, time.delta_seconds());
+                let movement = remainder.0.round();
+                remainder.0 -= movement;
+                (position.0, *collider, one_way.is_some(), movement)
+            }
+            Err(_) => continue,
+        };
+
+        if movement == Vec2::ZERO {
+            continue;
+        }
+
+        let other_moving_solids: Vec<SolidCollider> = moving_solids.iter().filter(|entity| **entity != solid_entity).filter_map(|entity| {
+            body_query.get(*entity).ok()
+        }).map(|(entity, position, _, _, collider, _, other_one_way, _, slope)| SolidCollider {
+            entity,
+            position: position.0,
+            collider: *collider,
+            one_way: other_one_way.is_some(),
+            slope: slope.copied(),
+            round: round_shape(entity)
+        }).collect();
+
+        // Only actors riding the solid or in the area it sweeps through can be affected
+        let swept_box = AABB {
+            position: world_aabb(&(solid_position + movement / 2.0), &solid_collider).position,
+            half_size: solid_collider.half_size + (movement.abs() / 2.0).ceil().as_ivec2()
+        };
+        let mut actors: Vec<ActorState> = body_query.iter().filter(|(_, _, _, _, _, body_type, _, drop_through, _)| {
+            // Actors dropping through this platform aren't carried by it
+            **body_type == BodyType::Actor && !drop_through.is_some_and(|drop_through| drop_through.platform == solid_entity)
+        }).map(|(entity, position, _, remainder, collider, _, _, drop_through, _)| ActorState {
+            entity,
+            position: *position,
+            remainder: Remainder(remainder.0),
+            collider: *collider,
+            ignore: drop_through.map(|drop_through| drop_through.platform),
+            nearby: Vec::new(),
+            riding: is_riding(&position.0, collider, &solid_position, &solid_collider),
+            squished: false
+        }).filter(|actor| {
+            actor.riding || AABB::interescts(&world_aabb(&actor.position.0, &actor.collider), &swept_box)
+        }).collect();
+
+        // A push can move an actor up to the full size of the solid plus its movement
+        for actor in actors.iter_mut() {
+            let reach = movement.signum() * (solid_collider.half_size.as_vec2() * 2.0 + movement.abs());
+            actor.nearby = nearby_solids(&spatial_hash, &other_moving_solids, actor.position.0, &actor.collider, reach);
+        }
+
+        let solid_round = round_shape(solid_entity);
+        move_solid_axis(movement.x, 0, &mut solid_position, &solid_collider, solid_round.as_ref(), one_way, &mut actors, &mut casts);
+        move_solid_axis(movement.y, 1, &mut solid_position, &solid_collider, solid_round.as_ref(), one_way, &mut actors, &mut casts);
+
+        if let Ok((_, mut position, ..)) = body_query.get_mut(solid_entity) {
+            position.0 = solid_position;
+        }
+
+        for actor in actors {
+            if let Ok((_, mut position, _, mut remainder, ..)) = body_query.get_mut(actor.entity) {
+                *position = actor.position;
+                *remainder = actor.remainder;
+            }
+            if actor.squished {
+                squished_events.send(Squished(actor.entity));
+            }
+        }
+    }
+
+    casts.finish(cast_history.as_deref_mut());
+}
+
+pub fn despawn_squished(
+    mut commands: Commands,
+    mut squished_events: EventReader<Squished>,
+    response_query: Query<&OnSquished>
+) {
+    for Squished(entity) in squished_events.read().copied() {
+        if response_query.get(entity).is_ok_and(|response| *response == OnSquished::Despawn) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
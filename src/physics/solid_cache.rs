@@ -0,0 +1,130 @@
+use bevy::{ecs::{query::Has, removal_detection::RemovedComponents}, math::Vec2, prelude::{Added, Changed, Entity, Or, Query, ResMut, Resource}, utils::{HashMap, HashSet}};
+
+use super::{body::{BodyType, Position, Velocity}, collision::{AABB, OneWay, Slope, SolidCollider}, shape::{CapsuleCollider, CircleCollider, RoundShape}, trigger::Sensor};
+
+// Every solid's collider, kept up to date from change detection so movement
+// doesn't have to go over every solid each step. Moving solids are tracked
+// separately since they are the only ones that need their position looked up
+// again after move_solid.
+#[derive(Resource, Default)]
+pub struct SolidColliderCache {
+    solids: HashMap<Entity, SolidCollider>,
+    moving: HashSet<Entity>,
+    // Set whenever a static solid was added, removed or changed, cleared by the
+    // spatial hash once it has rebuilt
+    pub static_changed: bool
+}
+
+impl SolidColliderCache {
+    pub fn get(&self, entity: Entity) -> Option<&SolidCollider> {
+        self.solids.get(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.solids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solids.is_empty()
+    }
+
+    pub fn is_moving(&self, entity: Entity) -> bool {
+        self.moving.contains(&entity)
+    }
+
+    pub fn static_solids(&self) -> impl Iterator<Item = &SolidCollider> {
+        let moving = &self.moving;
+        self.solids.iter().filter(move |(entity, _)| !moving.contains(*entity)).map(|(_, solid)| solid)
+    }
+
+    // Sorted so solids always move in the same order
+    pub fn moving(&self) -> Vec<Entity> {
+        let mut moving: Vec<Entity> = self.moving.iter().copied().collect();
+        moving.sort_unstable();
+        moving
+    }
+
+    // Moving solids as of the last cache update
+    pub fn moving_solids(&self) -> Vec<SolidCollider> {
+        self.moving().into_iter().filter_map(|entity| self.solids.get(&entity).copied()).collect()
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if self.solids.remove(&entity).is_some() && !self.moving.remove(&entity) {
+            self.static_changed = true;
+        }
+    }
+
+    fn update(&mut self, solid: SolidCollider, moving: bool) {
+        let was_moving = self.moving.contains(&solid.entity);
+        let previous = self.solids.insert(solid.entity, solid);
+
+        if moving {
+            self.moving.insert(solid.entity);
+            // Stopped being static, so it has to leave the spatial hash
+            if previous.is_some() && !was_moving {
+                self.static_changed = true;
+            }
+        } else {
+            self.moving.remove(&solid.entity);
+            let unchanged = previous.is_some_and(|previous| {
+                !was_moving && previous.position == solid.position && previous.one_way == solid.one_way
+                    && previous.slope == solid.slope && previous.round == solid.round
+                    && previous.collider.position == solid.collider.position && previous.collider.half_size == solid.collider.half_size
+            });
+            if !unchanged {
+                self.static_changed = true;
+            }
+        }
+    }
+}
+
+type SolidQueryData<'a> = (Entity, &'a Position, &'a Velocity, &'a AABB, &'a BodyType, Has<OneWay>, Option<&'a Slope>, Has<Sensor>, Option<&'a CircleCollider>, Option<&'a CapsuleCollider>);
+type SolidQueryItem<'a> = (Entity, &'a Position, &'a Velocity, &'a AABB, &'a BodyType, bool, Option<&'a Slope>, bool, Option<&'a CircleCollider>, Option<&'a CapsuleCollider>);
+
+fn refresh(cache: &mut SolidColliderCache, (entity, position, velocity, collider, body_type, one_way, slope, sensor, circle, capsule): SolidQueryItem) {
+    if *body_type != BodyType::Solid || sensor {
+        cache.remove(entity);
+        return;
+    }
+
+    cache.update(SolidCollider {
+        entity,
+        position: position.0,
+        collider: *collider,
+        one_way,
+        slope: slope.copied(),
+        round: RoundShape::from_components(circle, capsule)
+    }, velocity.0 != Vec2::ZERO);
+}
+
+pub fn update_solid_cache(
+    mut cache: ResMut<SolidColliderCache>,
+    mut removed_colliders: RemovedComponents<AABB>,
+    mut removed_one_ways: RemovedComponents<OneWay>,
+    mut removed_slopes: RemovedComponents<Slope>,
+    mut removed_sensors: RemovedComponents<Sensor>,
+    mut removed_circles: RemovedComponents<CircleCollider>,
+    mut removed_capsules: RemovedComponents<CapsuleCollider>,
+    changed_query: Query<SolidQueryData, Or<(Changed<Position>, Changed<Velocity>, Changed<AABB>, Changed<BodyType>, Added<OneWay>, Changed<Slope>, Added<Sensor>, Changed<CircleCollider>, Changed<CapsuleCollider>)>>,
+    solid_query: Query<SolidQueryData>
+) {
+    for solid in changed_query.iter() {
+        refresh(&mut cache, solid);
+    }
+
+    // Removing a component doesn't count as a change, so look those up again
+    let removed: Vec<Entity> = removed_colliders.read()
+        .chain(removed_one_ways.read())
+        .chain(removed_slopes.read())
+        .chain(removed_sensors.read())
+        .chain(removed_circles.read())
+        .chain(removed_capsules.read())
+        .collect();
+    for entity in removed {
+        match solid_query.get(entity) {
+            Ok(solid) => refresh(&mut cache, solid),
+            Err(_) => cache.remove(entity),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::{prelude::{Component, Entity, Event, EventWriter, Query, ResMut, Resource, With, Without}, utils::HashSet};
+
+use super::{body::{BodyType, Position}, collision::{AABB, CollisionExclusions, Intersection}, shape::{CapsuleCollider, CircleCollider, RoundShape, RoundedBox, world_shape}};
+
+// A collider that actors can move through. Instead of blocking it reports
+// actors overlapping it with the trigger events. Sensors never take part in
+// movement, so they don't block, push or carry anything.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Sensor;
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerEnter {
+    pub sensor: Entity,
+    pub entity: Entity
+}
+
+// Sent every step an actor stays inside a sensor, including the step it entered
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerStay {
+    pub sensor: Entity,
+    pub entity: Entity
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerExit {
+    pub sensor: Entity,
+    pub entity: Entity
+}
+
+// Sensor and actor pairs that overlapped last step
+#[derive(Resource, Default, Debug)]
+pub struct TriggerContacts(pub HashSet<(Entity, Entity)>);
+
+pub fn detect_triggers(
+    mut contacts: ResMut<TriggerContacts>,
+    mut enter_events: EventWriter<TriggerEnter>,
+    mut stay_events: EventWriter<TriggerStay>,
+    mut exit_events: EventWriter<TriggerExit>,
+    sensor_query: Query<(Entity, &Position, &AABB, Option<&CircleCollider>, Option<&CapsuleCollider>, Option<&CollisionExclusions>), With<Sensor>>,
+    actor_query: Query<(Entity, &Position, &AABB, &BodyType, Option<&CircleCollider>, Option<&CapsuleCollider>, Option<&CollisionExclusions>), Without<Sensor>>
+) {
+    let mut current = HashSet::new();
+
+    // Round sensors and actors use their shape, like a round pickup
+    for (sensor, sensor_position, sensor_collider, sensor_circle, sensor_capsule, sensor_exclusions) in sensor_query.iter() {
+        let sensor_shape = world_shape(&sensor_position.0, sensor_collider, RoundShape::from_components(sensor_circle, sensor_capsule).as_ref());
+        for (entity, position, collider, body_type, circle, capsule, exclusions) in actor_query.iter() {
+            let excluded = sensor_exclusions.is_some_and(|exclusions| exclusions.contains(entity))
+                || exclusions.is_some_and(|exclusions| exclusions.contains(sensor));
+            if excluded {
+                continue;
+            }
+            let shape = world_shape(&position.0, collider, RoundShape::from_components(circle, capsule).as_ref());
+            if *body_type == BodyType::Actor && RoundedBox::interescts(&shape, &sensor_shape) {
+                current.insert((sensor, entity));
+            }
+        }
+    }
+
+    for (sensor, entity) in current.iter().copied() {
+        if !contacts.0.contains(&(sensor, entity)) {
+            enter_events.send(TriggerEnter { sensor, entity });
+        }
+        stay_events.send(TriggerStay { sensor, entity });
+    }
+
+    // Despawned sensors or actors count as leaving
+    for (sensor, entity) in contacts.0.iter().copied() {
+        if !current.contains(&(sensor, entity)) {
+            exit_events.send(TriggerExit { sensor, entity });
+        }
+    }
+
+    contacts.0 = current;
+}
@@ -0,0 +1,209 @@
+use std::ops::BitOr;
+
+use bevy::{ecs::system::SystemParam, math::Vec2, prelude::{Entity, Query, Res, ResMut, Resource}};
+
+use super::{cast_debug::{CastDebugHistory, CastRecord}, body::{BodyType, Position}, broad_phase::SpatialHash, collision::{AABB, OneWay, Slope, SweepHit, check_ray_box_intersection, sweep_box, world_aabb}, solid_cache::SolidColliderCache, trigger::Sensor};
+
+// Which kinds of collider a query can hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryMask(pub u32);
+
+impl QueryMask {
+    pub const SOLIDS: QueryMask = QueryMask(1);
+    pub const ACTORS: QueryMask = QueryMask(1 << 1);
+    pub const SENSORS: QueryMask = QueryMask(1 << 2);
+    pub const ALL: QueryMask = QueryMask(u32::MAX);
+
+    pub fn contains(&self, other: QueryMask) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for QueryMask {
+    type Output = QueryMask;
+
+    fn bitor(self, other: QueryMask) -> QueryMask {
+        QueryMask(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub distance: f32
+}
+
+// `point` is the part of the collider closest to the query centre
+#[derive(Debug, Clone, Copy)]
+pub struct OverlapHit {
+    pub entity: Entity,
+    pub point: Vec2,
+    pub distance: f32
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WorldCollider {
+    pub entity: Entity,
+    pub world_box: AABB,
+    pub kind: QueryMask,
+    pub one_way: bool,
+    pub slope: Option<Slope>
+}
+
+// Every collider apart from the static solids as it was at the end of the
+// last physics step, those are looked up in the SpatialHash instead. Gameplay
+// code queries it through PhysicsQuery so its casts show up in the debug layer.
+#[derive(Resource, Default, Debug)]
+pub struct PhysicsWorld {
+    pub colliders: Vec<WorldCollider>
+}
+
+impl PhysicsWorld {
+    // Colliders in `mask` touching the box from `min` to `max`, static solids
+    // from the cells of the spatial hash it covers
+    fn nearby(&self, spatial_hash: &SpatialHash, min: Vec2, max: Vec2, mask: QueryMask) -> Vec<WorldCollider> {
+        let static_solids = if mask.contains(QueryMask::SOLIDS) { spatial_hash.query(min, max) } else { Vec::new() };
+        static_solids.into_iter().map(|solid| WorldCollider {
+            entity: solid.entity,
+            world_box: world_aabb(&solid.position, &solid.collider),
+            kind: QueryMask::SOLIDS,
+            one_way: solid.one_way,
+            slope: solid.slope
+        })
+        .chain(self.colliders.iter().filter(|other| mask.contains(other.kind)).copied())
+        .filter(|other| other.world_box.min().as_vec2().cmple(max).all() && other.world_box.max().as_vec2().cmpge(min).all())
+        .collect()
+    }
+
+    // Closest collider along the ray within max_distance. Colliders the ray
+    // starts inside are skipped so a body can cast out from its own center,
+    // one way platforms are only hit from above.
+    pub fn raycast(&self, spatial_hash: &SpatialHash, origin: Vec2, direction: Vec2, max_distance: f32, mask: QueryMask) -> Option<RayHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO || max_distance <= 0.0 {
+            return None;
+        }
+
+        let end = origin + direction * max_distance;
+        let (ray_min, ray_max) = (origin.min(end), origin.max(end));
+
+        self.nearby(spatial_hash, ray_min, ray_max, mask).iter().filter_map(|other| {
+            let (distance, normal) = check_ray_box_intersection(origin, direction, max_distance, &other.world_box, other.slope.as_ref())?;
+            if other.one_way && normal != Vec2::Y {
+                return None;
+            }
+
+            Some(RayHit {
+                entity: other.entity,
+                point: origin + direction * distance,
+                normal,
+                distance
+            })
+        }).min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    // Earliest collider a box at `from` runs into while moving by `delta`,
+    // for things like fast projectiles
+    pub fn cast_aabb(&self, spatial_hash: &SpatialHash, collider: &AABB, from: Vec2, delta: Vec2, mask: QueryMask) -> Option<SweepHit> {
+        let min = from + collider.min().as_vec2() + delta.min(Vec2::ZERO);
+        let max = from + collider.max().as_vec2() + delta.max(Vec2::ZERO);
+        self.nearby(spatial_hash, min, max, mask).iter().filter_map(|other| {
+            let (time, normal) = sweep_box(collider, from, delta, &other.world_box, other.slope.as_ref(), other.one_way)?;
+            Some(SweepHit {
+                entity: other.entity,
+                time,
+                position: from + delta * time,
+                normal
+            })
+        }).min_by(|a, b| a.time.total_cmp(&b.time))
+    }
+
+    // Every collider with some part within `radius` of `center`, nearest first.
+    // Colliders around the centre are at a distance of 0.
+    pub fn overlap_circle(&self, spatial_hash: &SpatialHash, center: Vec2, radius: f32, mask: QueryMask) -> Vec<OverlapHit> {
+        let extent = Vec2::splat(radius.max(0.0));
+        let mut hits: Vec<OverlapHit> = self.nearby(spatial_hash, center - extent, center + extent, mask).iter().filter_map(|other| {
+            let point = center.clamp(other.world_box.min().as_vec2(), other.world_box.max().as_vec2());
+            let distance = point.distance(center);
+            if distance > radius {
+                return None;
+            }
+
+            Some(OverlapHit {
+                entity: other.entity,
+                point,
+                distance
+            })
+        }).collect();
+
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+}
+
+// What gameplay systems query the physics world through, every cast made here
+// ends up in the cast history under `caller` while the debug layer records
+#[derive(SystemParam)]
+pub struct PhysicsQuery<'w> {
+    pub world: Res<'w, PhysicsWorld>,
+    pub spatial_hash: Res<'w, SpatialHash>,
+    history: Option<ResMut<'w, CastDebugHistory>>
+}
+
+impl PhysicsQuery<'_> {
+    pub fn raycast(&mut self, caller: &'static str, origin: Vec2, direction: Vec2, max_distance: f32, mask: QueryMask) -> Option<RayHit> {
+        let hit = self.world.raycast(&self.spatial_hash, origin, direction, max_distance, mask);
+        if let Some(history) = self.history.as_mut() {
+            history.record_ray(caller, origin, direction, max_distance, hit.as_ref());
+        }
+        hit
+    }
+
+    pub fn cast_aabb(&mut self, caller: &'static str, collider: &AABB, from: Vec2, delta: Vec2, mask: QueryMask) -> Option<SweepHit> {
+        let hit = self.world.cast_aabb(&self.spatial_hash, collider, from, delta, mask);
+        if let Some(history) = self.history.as_mut() {
+            history.record_sweep(caller, collider, from, delta, hit.as_ref());
+        }
+        hit
+    }
+
+    // Recorded with the nearest hit
+    pub fn overlap_circle(&mut self, caller: &'static str, center: Vec2, radius: f32, mask: QueryMask) -> Vec<OverlapHit> {
+        let hits = self.world.overlap_circle(&self.spatial_hash, center, radius, mask);
+        if let Some(history) = self.history.as_mut() {
+            history.record(CastRecord::circle(caller, center, radius, hits.first()));
+        }
+        hits
+    }
+}
+
+// Static solids are left to the spatial hash
+pub fn update_physics_world(
+    mut world: ResMut<PhysicsWorld>,
+    cache: Res<SolidColliderCache>,
+    collider_query: Query<(Entity, &Position, &AABB, &BodyType, Option<&OneWay>, Option<&Slope>, Option<&Sensor>)>
+) {
+    world.colliders.clear();
+    for (entity, position, collider, body_type, one_way, slope, sensor) in collider_query.iter() {
+        let kind = if sensor.is_some() {
+            QueryMask::SENSORS
+        } else if *body_type == BodyType::Actor {
+            QueryMask::ACTORS
+        } else {
+            QueryMask::SOLIDS
+        };
+        if kind == QueryMask::SOLIDS && cache.get(entity).is_some() && !cache.is_moving(entity) {
+            continue;
+        }
+
+        world.colliders.push(WorldCollider {
+            entity,
+            world_box: world_aabb(&position.0, collider),
+            kind,
+            one_way: one_way.is_some(),
+            slope: slope.copied()
+        });
+    }
+}
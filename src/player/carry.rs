@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+
+use crate::{animation::sockets::AnimationSockets, control::{PlayerControlLock, actions::PlayerActions, glyphs::InputAction}, physics::{activation::WakeBody, body::{Held, Position, Velocity}, collision::AABB, gravity::Gravity, world::{PhysicsQuery, QueryMask}}};
+
+use super::PlayerInput;
+
+// Light actors the player can pick up. `movement_scale` multiplies how fast
+// the player walks and how hard they jump while holding it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Carryable {
+    pub movement_scale: f32
+}
+
+impl Default for Carryable {
+    fn default() -> Self {
+        Carryable {
+            movement_scale: 0.75
+        }
+    }
+}
+
+// Press the carry key next to something Carryable to hold it over your head,
+// press it again to throw it or hold crouch as well to set it down. `reach` is
+// in pixels from the player's position, `throw_speed` in units on top of the
+// player's own velocity and `throw_angle` in radians above the ground in
+// front of them.
+#[derive(Component, Debug)]
+pub struct Carrier {
+    pub reach: f32,
+    pub throw_speed: f32,
+    pub throw_angle: f32,
+    carried: Option<Entity>,
+    movement_scale: f32
+}
+
+impl Default for Carrier {
+    fn default() -> Self {
+        Carrier {
+            reach: 12.0,
+            throw_speed: 12.0,
+            throw_angle: std::f32::consts::FRAC_PI_6,
+            carried: None,
+            movement_scale: 1.0
+        }
+    }
+}
+
+impl Carrier {
+    pub fn carried(&self) -> Option<Entity> {
+        self.carried
+    }
+
+    // 1 with empty hands
+    pub fn movement_scale(&self) -> f32 {
+        self.movement_scale
+    }
+
+    fn release(&mut self) -> Option<Entity> {
+        self.movement_scale = 1.0;
+        self.carried.take()
+    }
+}
+
+pub fn pick_up_and_throw(
+    mut commands: Commands,
+    actions: PlayerActions,
+    control_lock: Res<PlayerControlLock>,
+    mut world: PhysicsQuery,
+    mut wake_events: EventWriter<WakeBody>,
+    carryable_query: Query<&Carryable, Without<Held>>,
+    mut item_query: Query<&mut Velocity, (With<Held>, Without<Carrier>)>,
+    mut carrier_query: Query<(Entity, &PlayerInput, &mut Carrier, &Position, &Velocity, &Gravity, Option<&TextureAtlasSprite>)>
+) {
+    // Whatever is held stays held until control comes back
+    if control_lock.is_locked() {
+        return;
+    }
+
+    for (entity, p_input, mut carrier, position, velocity, gravity, sprite) in carrier_query.iter_mut() {
+        if !actions.just_pressed(p_input, InputAction::Carry) {
+            continue;
+        }
+
+        if carrier.carried.is_none() {
+            let pick = world.overlap_circle("pick_up_and_throw", position.0, carrier.reach, QueryMask::ACTORS).into_iter()
+                .filter(|hit| hit.entity != entity)
+                .find_map(|hit| carryable_query.get(hit.entity).ok().map(|carryable| (hit.entity, carryable)));
+            if let Some((item, carryable)) = pick {
+                carrier.carried = Some(item);
+                carrier.movement_scale = carryable.movement_scale;
+                commands.entity(item).insert(Held);
+            }
+            continue;
+        }
+
+        let item = match carrier.release() {
+            Some(item) => item,
+            None => continue,
+        };
+        // Thrown things keep the player's momentum, set down ones only that
+        let throw = if actions.pressed(p_input, InputAction::Crouch) {
+            Vec2::ZERO
+        } else {
+            let facing = if sprite.is_some_and(|sprite| sprite.flip_x) { -1.0 } else { 1.0 };
+            gravity.frame().to_world(Vec2::new(carrier.throw_angle.cos() * facing, carrier.throw_angle.sin()) * carrier.throw_speed)
+        };
+        if let Ok(mut item_velocity) = item_query.get_mut(item) {
+            item_velocity.0 = velocity.0 + throw;
+        }
+        commands.entity(item).remove::<Held>();
+        wake_events.send(WakeBody(item));
+    }
+}
+
+// Keeps carried things on the player's "carry" socket, or sitting on top of
+// them along whichever way is up without one. Runs after the step so it
+// follows where the player ended up, the transform gets set here as well so
+// it doesn't trail a frame behind.
+pub fn hold_carried(
+    mut carrier_query: Query<(&mut Carrier, &Position, &AABB, &Gravity, Option<&TextureAtlasSprite>, Option<&AnimationSockets>)>,
+    mut item_query: Query<(&mut Position, &mut Velocity, &AABB, Option<&mut Transform>, Option<&mut TextureAtlasSprite>), (With<Held>, Without<Carrier>)>
+) {
+    for (mut carrier, position, collider, gravity, sprite, sockets) in carrier_query.iter_mut() {
+        let item = match carrier.carried {
+            Some(item) => item,
+            None => continue,
+        };
+        // Whatever was carried is gone, like a bomb going off in the player's hands
+        let (mut item_position, mut item_velocity, item_collider, transform, item_sprite) = match item_query.get_mut(item) {
+            Ok(item) => item,
+            Err(_) => {
+                carrier.release();
+                continue;
+            }
+        };
+
+        let frame = gravity.frame();
+        let height = |collider: &AABB| frame.to_local(collider.half_size.as_vec2()).y.abs();
+        let offset = sockets.and_then(|sockets| sockets.get("carry"))
+            .unwrap_or_else(|| frame.up().as_vec2() * (height(collider) + height(item_collider)));
+        let socket = position.0 + offset;
+        item_position.0 = socket;
+        item_velocity.0 = Vec2::ZERO;
+        if let Some(mut transform) = transform {
+            transform.translation.x = socket.x;
+            transform.translation.y = socket.y;
+        }
+        if let (Some(sprite), Some(mut item_sprite)) = (sprite, item_sprite) {
+            item_sprite.flip_x = sprite.flip_x;
+        }
+    }
+}
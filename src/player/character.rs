@@ -0,0 +1,161 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{animation::{AnimationDefinition, SpriteSheetDefinition, TransitionTable}, mods::resolve_path};
+
+use super::player_physics::{PlayerJumpParams, PlayerWalkParams};
+
+// Hero the player spawns as unless something picks another one
+pub const DEFAULT_CHARACTER: &str = "characters/hero.character.ron";
+
+// How a character handles, in physics units like the params it fills in
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct CharacterMovement {
+    pub walk_accel: f32,
+    pub max_walk_speed: f32,
+    pub jump_acceleration: f32,
+    pub max_jump_duration: f32,
+    pub max_fall_speed: f32,
+    // Seconds after walking off a ledge the player can still jump
+    pub coyote_time: f32
+}
+
+impl CharacterMovement {
+    pub fn walk_params(&self) -> PlayerWalkParams {
+        PlayerWalkParams {
+            walk_accel: self.walk_accel,
+            max_walk_speed: self.max_walk_speed
+        }
+    }
+
+    pub fn jump_params(&self) -> PlayerJumpParams {
+        PlayerJumpParams {
+            jump_acceleration: self.jump_acceleration,
+            max_jump_duration: self.max_jump_duration,
+            max_fall_speed: self.max_fall_speed,
+            coyote_time: self.coyote_time,
+            jump_timer: Timer::from_seconds(self.max_jump_duration, TimerMode::Once),
+            ..Default::default()
+        }
+    }
+}
+
+// A playable character, written as RON in `*.character.ron` files. The
+// spritesheet path is relative to the assets folder, or the pack's folder for
+// characters from a mod, and gets loaded along with the character.
+// Animations are the sheet's rows from the top, the player animations look
+// them up by name ("idle", "run", "jumping", "falling").
+// Transitions between them are optional. Instead of listing the rows,
+// columns, animations and transitions inline `sheet` can point at an
+// `*.anim.ron` file with them, which is found like the spritesheet.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct CharacterDef {
+    pub name: String,
+    pub spritesheet: Handle<Image>,
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub animations: Vec<AnimationDefinition>,
+    pub transitions: TransitionTable,
+    pub movement: CharacterMovement
+}
+
+impl CharacterDef {
+    pub fn animation_row(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+struct CharacterDefFile {
+    name: String,
+    spritesheet: String,
+    tile_size: (f32, f32),
+    #[serde(default)]
+    sheet: Option<String>,
+    #[serde(default)]
+    columns: usize,
+    #[serde(default)]
+    rows: usize,
+    #[serde(default)]
+    animations: Vec<AnimationDefinition>,
+    #[serde(default)]
+    transitions: TransitionTable,
+    movement: CharacterMovement
+}
+
+#[derive(Default)]
+pub struct CharacterDefLoader;
+
+impl AssetLoader for CharacterDefLoader {
+    type Asset = CharacterDef;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let file = ron::de::from_bytes::<CharacterDefFile>(&bytes)?;
+            // Loaded as a dependency so editing the sheet reloads the character
+            let sheet = match &file.sheet {
+                Some(path) => {
+                    let path = resolve_path(load_context.asset_path(), path);
+                    load_context.load_direct(path.clone()).await?.take::<SpriteSheetDefinition>()
+                        .ok_or_else(|| anyhow::anyhow!("{} is not a sprite sheet definition", path))?
+                },
+                None => SpriteSheetDefinition {
+                    animation_definitions: file.animations,
+                    rows: file.rows,
+                    columns: file.columns,
+                    transitions: file.transitions
+                }
+            };
+            Ok(CharacterDef {
+                name: file.name,
+                spritesheet: load_context.load(resolve_path(load_context.asset_path(), &file.spritesheet)),
+                tile_size: Vec2::new(file.tile_size.0, file.tile_size.1),
+                columns: sheet.columns,
+                rows: sheet.rows,
+                animations: sheet.animation_definitions,
+                transitions: sheet.transitions,
+                movement: file.movement
+            })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["character.ron"]
+    }
+}
+
+// Path of the character def each local player spawns as, by player index.
+// The character select screen fills it in before the level spawns.
+#[derive(Resource, Debug, Clone)]
+pub struct CharacterSelection {
+    pub players: Vec<String>
+}
+
+impl Default for CharacterSelection {
+    fn default() -> Self {
+        CharacterSelection {
+            players: vec![String::from(DEFAULT_CHARACTER)]
+        }
+    }
+}
+
+impl CharacterSelection {
+    // Players nobody picked for get the default hero
+    pub fn character(&self, player: usize) -> &str {
+        self.players.get(player).map_or(DEFAULT_CHARACTER, |path| &path[..])
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::{prelude::*, time::Fixed};
+use bevy_egui::{EguiContexts, egui};
+
+use crate::physics::{PhysicsConfig, body::{GravityScale, Position}, integrator::{IntegrationScheme, integrate}};
+
+use super::player_physics::{PlayerJumpParams, PlayerWalkParams};
+
+// Upper bound on simulated steps so a zero gravity setup can't hang the preview
+const MAX_STEPS: usize = 600;
+
+pub struct JumpArc {
+    pub points: Vec<Vec2>,
+    pub max_height: f32,
+    pub distance: f32
+}
+
+// Replays move_player, apply_gravity and integrate_movement for a jump started from
+// the ground while running right, holding jump for `hold_steps` fixed steps.
+// Points are in pixels relative to where the jump started. Gravity zones and
+// flipped gravity aren't taken into account.
+pub fn predict_jump_arc(
+    config: &PhysicsConfig,
+    walk_params: &PlayerWalkParams,
+    jump_params: &PlayerJumpParams,
+    gravity_scale: f32,
+    timestep: f32,
+    hold_steps: usize
+) -> JumpArc {
+    let mut position = Vec2::ZERO;
+    let mut velocity = Vec2::ZERO;
+    let mut jump_timer = Timer::from_seconds(jump_params.max_jump_duration, TimerMode::Once);
+    let mut points = vec![position];
+    let mut max_height: f32 = 0.0;
+
+    for step in 0..MAX_STEPS {
+        velocity.x = walk_params.walk_accel;
+        if step < hold_steps && !jump_timer.finished() {
+            velocity.y += jump_params.jump_acceleration;
+            jump_timer.tick(std::time::Duration::from_secs_f32(timestep));
+        }
+
+        let acceleration = integrate(&mut velocity, config.gravity * gravity_scale, timestep, |velocity| Vec2::new(
+            velocity.x.clamp(-walk_params.max_walk_speed, walk_params.max_walk_speed),
+            velocity.y.max(jump_params.max_fall_speed)
+        ));
+        position += config.step_pixels(config.integration.step_velocity(velocity, acceleration, timestep), timestep);
+        points.push(position);
+        max_height = max_height.max(position.y);
+
+        if position.y <= 0.0 {
+            break;
+        }
+    }
+
+    JumpArc {
+        distance: position.x,
+        max_height,
+        points
+    }
+}
+
+pub fn debug_jump_arc(
+    mut gizmos: Gizmos,
+    mut egui_ctx: EguiContexts,
+    fixed_time: Res<Time<Fixed>>,
+    mut config: ResMut<PhysicsConfig>,
+    player_query: Query<(&Position, &PlayerWalkParams, &PlayerJumpParams, &GravityScale, Option<&TextureAtlasSprite>)>
+) {
+    let timestep = fixed_time.timestep().as_secs_f32();
+
+    egui::Window::new("Jump Arc").show(egui_ctx.ctx_mut(), |ui| {
+        let mut integration = config.integration;
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut integration, IntegrationScheme::SemiImplicitEuler, "Semi-implicit Euler");
+            ui.radio_value(&mut integration, IntegrationScheme::VelocityVerlet, "Velocity Verlet");
+        });
+        if integration != config.integration {
+            config.integration = integration;
+        }
+        ui.separator();
+
+        for (i, (position, walk_params, jump_params, gravity_scale, sprite)) in player_query.iter().enumerate() {
+            let full_steps = (jump_params.max_jump_duration / timestep).ceil() as usize;
+            let full = predict_jump_arc(&config, walk_params, jump_params, gravity_scale.0, timestep, full_steps);
+            let short = predict_jump_arc(&config, walk_params, jump_params, gravity_scale.0, timestep, 1);
+
+            // Draw in front of the player, sprites face right unless flipped
+            let facing = if sprite.is_some_and(|sprite| sprite.flip_x) { -1.0 } else { 1.0 };
+            for (arc, color) in [(&full, Color::LIME_GREEN), (&short, Color::YELLOW)] {
+                gizmos.linestrip_2d(arc.points.iter().map(|point| position.0 + Vec2::new(point.x * facing, point.y)), color);
+            }
+
+            ui.label(format!("Player {}", i));
+            egui::Grid::new(format!("Player {} jump arc", i)).show(ui, |ui| {
+                ui.label("");
+                ui.label("Height");
+                ui.label("Distance");
+                ui.end_row();
+                for (name, arc) in [("Full hold", &full), ("Tap", &short)] {
+                    ui.label(name);
+                    ui.label(format!("{:.0}", arc.max_height));
+                    ui.label(format!("{:.0}", arc.distance));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+        }
+    });
+}
@@ -1,29 +1,33 @@
-use bevy::{ecs::schedule::GraphNode, prelude::*, sprite::collide_aabb::Collision};
+use bevy::prelude::*;
 
-#[cfg(target_arch = "x86_64")]
-use bevy_canvas::{
-    common_shapes::{self, Rectangle},
-    Canvas, DrawMode,
-};
+use bevy_egui::{EguiContexts, egui::{self, Window}};
 
-use bevy_egui::{EguiContext, egui::{self, Window}};
-
-use crate::{animation::{AnimatedSpriteBundle, Col, Row, SpriteSheetDefinition}, physics::{PhysicsStages, StepSystemLabels, body::{Acceleration, BodyBundle, Velocity}, collision::{AABB, CollisionResult}}};
-use macros::animation_graph;
+use crate::{animation::{AnimatedSpriteBundle, play_requested_animations}, control::{PlayerControlLock, actions::PlayerActions, device::{ActiveInputDevice, InputSmoothing}, glyphs::InputAction}, level::AppState, physics::{PhysicsStages, PhysicsStep, StepSystemLabels, body::{Acceleration, BodyBundle, CustomIntegration, GravityScale, Velocity}, controller::{Grounded, TouchingFlags}, collision::{AABB, DropThrough, OneWay, PhysicsMaterial}, gravity::Gravity, solid::{OnSquished, Squished}}};
 
+pub mod carry;
+pub mod character;
+pub mod jump_arc;
 pub mod player_animation;
 pub mod player_physics;
+pub mod rewind;
+pub mod throw;
 
-use self::{player_animation::{update_player_animation, Player::{PlayerAnimationUpdate, player_animation_update}}, player_physics::{PlayerJumpParams, PlayerWalkParams, collision_check, gravity, integrate_movement}};
+use self::{carry::{Carrier, hold_carried, pick_up_and_throw}, character::{CharacterDef, CharacterDefLoader, CharacterSelection}, jump_arc::debug_jump_arc, player_animation::{PlayerAnimationUpdate, player_animation_update}, player_physics::{PlayerGlideParams, PlayerJumpParams, PlayerWalkParams, collision_check, integrate_movement}, rewind::rewind_player, throw::{detonate_bombs, setup_bomb_assets, throw_bomb}};
 
-#[derive(Default)]
+#[derive(Component, Default)]
 pub struct Health(pub u32);
 
+#[derive(Component, Debug, Clone, Copy)]
 pub struct PlayerInput {
     pub left: KeyCode,
     pub right: KeyCode,
     pub jump: KeyCode,
     pub crouch: KeyCode,
+    pub rewind: KeyCode,
+    pub throw: KeyCode,
+    pub carry: KeyCode,
+    // Which connected gamepad this player uses, in the order they were connected
+    pub gamepad: usize,
 }
 
 impl Default for PlayerInput {
@@ -33,64 +37,168 @@ impl Default for PlayerInput {
             right: KeyCode::D,
             jump: KeyCode::Space,
             crouch: KeyCode::S,
+            rewind: KeyCode::R,
+            throw: KeyCode::E,
+            carry: KeyCode::F,
+            gamepad: 0,
         }
     }
 }
 
+impl PlayerInput {
+    // Keys for each local player, the second one is on the arrow keys
+    pub fn for_local_player(player: usize) -> Self {
+        match player {
+            1 => PlayerInput {
+                left: KeyCode::Left,
+                right: KeyCode::Right,
+                jump: KeyCode::Up,
+                crouch: KeyCode::Down,
+                rewind: KeyCode::ShiftRight,
+                throw: KeyCode::ControlRight,
+                carry: KeyCode::AltRight,
+                gamepad: 1,
+            },
+            _ => PlayerInput::default()
+        }
+    }
+}
+
+// Presses and releases seen by the frame loop, held until the next fixed step
+// consumes them so a quick tap can't fall between two physics steps
+#[derive(Component, Default, Debug)]
+pub struct PlayerInputBuffer {
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+}
+
 #[derive(Bundle, Default)]
 pub struct PlayerBundle {
     pub health: Health,
-    #[bundle]
     pub body_bundle: BodyBundle,
     pub collider: AABB,
-    #[bundle]
     pub animation: AnimatedSpriteBundle,
     pub input: PlayerInput,
+    pub input_buffer: PlayerInputBuffer,
     pub action: PlayerAnimationUpdate,
     pub player_walk_params: PlayerWalkParams,
     pub player_jump_params: PlayerJumpParams,
-    pub acceleration: Acceleration
+    pub player_glide_params: PlayerGlideParams,
+    pub acceleration: Acceleration,
+    pub gravity_scale: GravityScale,
+    pub gravity: Gravity,
+    pub touching: TouchingFlags,
+    pub grounded: Grounded,
+    // Player movement goes through integrate_movement instead
+    pub custom_integration: CustomIntegration
 }
 
+fn buffer_player_input(
+    actions: PlayerActions,
+    mut player_query: Query<(&PlayerInput, &mut PlayerInputBuffer)>,
+) {
+    for (p_input, mut buffer) in player_query.iter_mut() {
+        buffer.jump_pressed |= actions.just_pressed(p_input, InputAction::Jump);
+        buffer.jump_released |= actions.just_released(p_input, InputAction::Jump);
+    }
+}
 
+// How long the player ignores a one way platform after dropping through it
+const DROP_THROUGH_TIME: f32 = 0.25;
 
 fn move_player(
+    mut commands: Commands,
     time: Res<Time>,
-    keys: Res<Input<KeyCode>>,
+    actions: PlayerActions,
+    control_lock: Res<PlayerControlLock>,
+    device: Option<Res<ActiveInputDevice>>,
+    smoothing: Option<Res<InputSmoothing>>,
+    one_way_query: Query<(), With<OneWay>>,
+    material_query: Query<&PhysicsMaterial>,
     mut player_query: Query<(
+        Entity,
         &PlayerInput,
+        &mut PlayerInputBuffer,
         &PlayerWalkParams,
         &mut PlayerJumpParams,
+        &mut PlayerGlideParams,
         &mut Velocity,
-        &mut Acceleration
+        &Gravity,
+        &Grounded,
+        Option<&Carrier>
     )>,
 ) {
-    for (p_input, player_walk_params, mut player_jump_params, mut vel, mut accel) in
+    for (entity, p_input, mut buffer, player_walk_params, mut player_jump_params, mut glide_params, mut vel, gravity, grounded, carrier) in
         player_query.iter_mut()
     {
-        if vel.0.y != 0.0 {
-            player_jump_params.grounded = false;
+        let jump_pressed = std::mem::take(&mut buffer.jump_pressed);
+        let jump_released = std::mem::take(&mut buffer.jump_released);
+
+        // Jumping is still allowed for a moment after walking off a ledge
+        if grounded.is_grounded {
+            player_jump_params.grounded = true;
+            let max_duration = glide_params.max_duration;
+            glide_params.glide_timer = Timer::from_seconds(max_duration, TimerMode::Once);
+        } else {
+            player_jump_params.ground = None;
+            if grounded.time_since_grounded(&time) > player_jump_params.coyote_time {
+                player_jump_params.grounded = false;
+            }
         }
 
-        if (!keys.pressed(p_input.left) && !keys.pressed(p_input.right))
-            || (keys.pressed(p_input.left) && keys.pressed(p_input.right))
-        {
-            vel.0.x = 0.0;
-        } else if keys.pressed(p_input.left) {
-            vel.0.x = -player_walk_params.walk_accel;
-        } else if keys.pressed(p_input.right) {
-            vel.0.x = player_walk_params.walk_accel;
+        // Walking and jumping follow gravity around walls and ceilings, x runs
+        // along the ground and y points away from it
+        let frame = gravity.frame();
+        let mut local = frame.to_local(vel.0);
+
+        // Something else has control of the player, drop any input in progress
+        if control_lock.is_locked() {
+            local.x = 0.0;
+            vel.0 = frame.to_world(local);
+            player_jump_params.is_jumping = false;
+            glide_params.is_gliding = false;
+            continue;
         }
 
-        if player_jump_params.grounded && keys.just_pressed(p_input.jump) {
+        // Carrying something heavy slows walking and jumping down
+        let carry_scale = carrier.map_or(1.0, Carrier::movement_scale);
+        let (left, right) = (actions.pressed(p_input, InputAction::Left), actions.pressed(p_input, InputAction::Right));
+        let target_speed = if left == right {
+            0.0
+        } else if left {
+            -player_walk_params.walk_accel
+        } else {
+            player_walk_params.walk_accel
+        };
+
+        // Slippery ground only lets the player change speed gradually
+        let traction = player_jump_params.ground
+            .and_then(|ground| material_query.get(ground).ok())
+            .map_or(1.0, |material| material.friction.clamp(0.0, 1.0));
+        let smoothing = match (&device, &smoothing) {
+            (Some(device), Some(smoothing)) => smoothing.for_device(device.0).clamp(0.0, 1.0),
+            _ => 1.0
+        };
+        local.x += (target_speed * carry_scale - local.x) * traction * smoothing;
+
+        // Down + jump on a one way platform drops through it instead of jumping
+        let on_one_way = player_jump_params.ground.is_some_and(|ground| one_way_query.contains(ground));
+        if player_jump_params.grounded && jump_pressed && on_one_way && actions.pressed(p_input, InputAction::Crouch) {
+            commands.entity(entity).insert(DropThrough {
+                platform: player_jump_params.ground.unwrap(),
+                timer: Timer::from_seconds(DROP_THROUGH_TIME, TimerMode::Once)
+            });
+            player_jump_params.grounded = false;
+            player_jump_params.ground = None;
+        } else if player_jump_params.grounded && jump_pressed {
             player_jump_params.is_jumping = true;
             player_jump_params.grounded = false;
-            player_jump_params.jump_timer = Timer::from_seconds(player_jump_params.max_jump_duration, false);
+            player_jump_params.jump_timer = Timer::from_seconds(player_jump_params.max_jump_duration, TimerMode::Once);
         }
 
-        if keys.pressed(p_input.jump) && player_jump_params.is_jumping {
+        if actions.pressed(p_input, InputAction::Jump) && player_jump_params.is_jumping {
             if !player_jump_params.jump_timer.finished() {
-                vel.0.y += player_jump_params.jump_acceleration;
+                local.y += player_jump_params.jump_acceleration * carry_scale;
                 // println!("Vel.0 {:?}", vel.0);
                 player_jump_params.jump_timer.tick(time.delta());
             } else {
@@ -98,19 +206,37 @@ fn move_player(
             }
         }
 
-        if keys.just_released(p_input.jump) {
+        if jump_released {
             player_jump_params.is_jumping = false;
         }
+
+        let falling = local.y < 0.0;
+        glide_params.is_gliding = glide_params.enabled && falling && actions.pressed(p_input, InputAction::Jump) && !glide_params.glide_timer.finished();
+        if glide_params.is_gliding {
+            glide_params.glide_timer.tick(time.delta());
+        }
+        vel.0 = frame.to_world(local);
+    }
+}
+
+// Anything with health that is set to die when squished loses all of it
+fn kill_squished(
+    mut squished_events: EventReader<Squished>,
+    mut health_query: Query<(&mut Health, &OnSquished)>
+) {
+    for Squished(entity) in squished_events.read().copied() {
+        if let Ok((mut health, OnSquished::Kill)) = health_query.get_mut(entity) {
+            health.0 = 0;
+        }
     }
 }
 
 fn debug_player_params(
-    mut egui_ctx: ResMut<EguiContext>,
-    mut player_params_query: Query<(&mut PlayerJumpParams, &mut PlayerWalkParams)>,
+    mut egui_ctx: EguiContexts,
+    mut player_params_query: Query<(&mut PlayerJumpParams, &mut PlayerWalkParams, &mut GravityScale)>,
 ) {
-    Window::new("Bodies").scroll(true).show(egui_ctx.ctx(), |ui| {
-        let mut i = 0u32;
-        for (mut jump_params, mut walk_params) in player_params_query.iter_mut() {
+    Window::new("Bodies").vscroll(true).show(egui_ctx.ctx_mut(), |ui| {
+        for (i, (mut jump_params, mut walk_params, mut gravity_scale)) in player_params_query.iter_mut().enumerate() {
             ui.collapsing(format!("Player {}", i), |ui| {
                 egui::Grid::new(format!("Player {} prams", i)).show(ui, |ui|{
                     ui.label("Walk Accel");
@@ -121,9 +247,8 @@ fn debug_player_params(
                     ui.end_row();
                     ui.separator();
                     ui.end_row();
-                    ui.label("Gravity");
-                    ui.add_sized([40.0, 20.0], egui::DragValue::new(&mut jump_params.gravity.x));
-                    ui.add_sized([40.0, 20.0], egui::DragValue::new(&mut jump_params.gravity.y));
+                    ui.label("Gravity Scale");
+                    ui.add_sized([40.0, 20.0], egui::DragValue::new(&mut gravity_scale.0).speed(0.05));
                     ui.end_row();
                     ui.label("Jump Acceleration");
                     ui.add_sized([40.0, 20.0], egui::DragValue::new(&mut jump_params.jump_acceleration));
@@ -142,7 +267,6 @@ fn debug_player_params(
                 });
             });
             ui.separator();
-            i += 1;
         }
     });
 }
@@ -150,22 +274,26 @@ fn debug_player_params(
 pub struct PlayerDebugPlugin;
 
 impl Plugin for PlayerDebugPlugin {
-    fn build(&self, app: &mut AppBuilder) {
-        app.add_system(debug_player_params.system());
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (debug_player_params, debug_jump_arc));
     }
 }
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
-    fn build(&self, app: &mut AppBuilder) {
+    fn build(&self, app: &mut App) {
         app
-            .add_system_to_stage(PhysicsStages::PreStep, move_player.system().label("MOVE_PLAYER"))
-            .add_system_to_stage(PhysicsStages::PreStep, gravity.system().after("MOVE_PLAYER"))
-            .add_system_to_stage(PhysicsStages::Step, integrate_movement.system().label("INTEGRATE_PLAYER").before(StepSystemLabels::MoveActors))
-            .add_system_to_stage(PhysicsStages::PostStep, collision_check.system().label("COLLISION_CHECK"))
+            .init_asset::<CharacterDef>()
+            .init_asset_loader::<CharacterDefLoader>()
+            .init_resource::<CharacterSelection>()
+            .init_resource::<PlayerControlLock>()
+            .add_systems(OnExit(AppState::Loading), setup_bomb_assets)
+            .add_systems(Update, (buffer_player_input, throw_bomb, pick_up_and_throw))
+            .add_systems(FixedUpdate, move_player.in_set(PhysicsStages::PreStep))
+            .add_systems(PhysicsStep, integrate_movement.in_set(StepSystemLabels::Integrate))
+            .add_systems(FixedUpdate, (collision_check, kill_squished, rewind_player, detonate_bombs, hold_carried).in_set(PhysicsStages::PostStep))
 
-            .add_system(update_player_animation.system().after("player_animation_update"))
-            .add_system(player_animation_update.system().label("player_animation_update"));
+            .add_systems(Update, player_animation_update.before(play_requested_animations));
     }
-}
\ No newline at end of file
+}
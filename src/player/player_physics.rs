@@ -1,56 +1,84 @@
-use bevy::{core::{Time, Timer}, math::Vec2, prelude::{Added, Commands, Entity, Query, Res}};
-use crate::physics::{body::{Acceleration, Velocity}, collision::CollisionResult};
+use bevy::prelude::{Component, Entity, EventReader, Query, Res, Time, Timer, TimerMode, Vec2};
+use crate::physics::{body::{Acceleration, Velocity}, collision::CollisionEvent, gravity::Gravity, integrator::{clamp_fall, integrate_acceleration}};
 
-#[derive(Debug, Default)]
+// All speeds and accelerations are in physics units, see PhysicsConfig
+#[derive(Component, Debug, Default)]
 pub struct PlayerWalkParams {
     pub walk_accel: f32,
     pub max_walk_speed: f32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Component, Debug, Default)]
 pub struct PlayerJumpParams {
-    pub gravity: Vec2,
     pub jump_acceleration: f32,
     pub max_jump_duration: f32,
+    // Negative, it is a velocity pointing down when the player is the right way up
     pub max_fall_speed: f32,
+    // Seconds after leaving the ground a jump still works
+    pub coyote_time: f32,
     pub jump_timer: Timer,
+    // Can jump, set while on the ground and for the coyote time after
     pub grounded: bool,
+    // What the player landed on last, cleared once they leave the ground
+    pub ground: Option<Entity>,
     pub is_jumping: bool
 }
 
-pub fn integrate_movement(
-    time: Res<Time>,
-    mut body_query: Query<(&mut Velocity, &mut Acceleration, &PlayerWalkParams, &PlayerJumpParams)>
-) {
-    for (mut velocity, mut acceleration, player_walk_params, player_jump_params) in body_query.iter_mut() {
-        let added_velocity = acceleration.0 * time.delta_seconds();
-        let temp_velocity = added_velocity + velocity.0;
+// Holding jump on the way down glides once the power up has been picked up.
+// Gliding falls no faster than `max_fall_speed` and lasts `max_duration`
+// seconds per time in the air, landing fills it back up.
+#[derive(Component, Debug)]
+pub struct PlayerGlideParams {
+    pub enabled: bool,
+    // Negative like PlayerJumpParams::max_fall_speed
+    pub max_fall_speed: f32,
+    pub max_duration: f32,
+    pub glide_timer: Timer,
+    pub is_gliding: bool
+}
 
-        // Clamp the player speed
-        velocity.0 = Vec2::new(
-            temp_velocity.x.clamp(-player_walk_params.max_walk_speed, player_walk_params.max_walk_speed), 
-            temp_velocity.y.max(player_jump_params.max_fall_speed)
-        );
-        acceleration.0 = Vec2::ZERO;
+impl Default for PlayerGlideParams {
+    fn default() -> Self {
+        PlayerGlideParams {
+            enabled: false,
+            max_fall_speed: -2.0,
+            max_duration: 2.0,
+            glide_timer: Timer::from_seconds(2.0, TimerMode::Once),
+            is_gliding: false
+        }
     }
 }
 
-pub fn gravity(
-    mut body_query: Query<(&mut Acceleration, &PlayerJumpParams)>
+pub fn integrate_movement(
+    time: Res<Time>,
+    mut body_query: Query<(&mut Velocity, &mut Acceleration, &PlayerWalkParams, &PlayerJumpParams, &PlayerGlideParams, &Gravity)>
 ) {
-    for (mut accel, player_jump_params) in body_query.iter_mut() {
-        accel.0 += player_jump_params.gravity;
+    for (mut velocity, mut acceleration, player_walk_params, player_jump_params, glide_params, gravity) in body_query.iter_mut() {
+        let max_fall_speed = if glide_params.is_gliding { glide_params.max_fall_speed } else { player_jump_params.max_fall_speed };
+        // Clamp the player speed, walking along the ground and falling away from it
+        let frame = gravity.frame();
+        integrate_acceleration(&mut velocity, &mut acceleration, time.delta_seconds(), |velocity| {
+            let local = frame.to_local(velocity);
+            frame.to_world(Vec2::new(
+                local.x.clamp(-player_walk_params.max_walk_speed, player_walk_params.max_walk_speed),
+                clamp_fall(local.y, -max_fall_speed, 1.0)
+            ))
+        });
     }
 }
 
 pub fn collision_check(
-    mut commands: Commands,
-    mut jump_state_query: Query<(Entity, &mut PlayerJumpParams, &CollisionResult), Added<CollisionResult>>
+    mut collision_events: EventReader<CollisionEvent>,
+    mut jump_state_query: Query<(&mut PlayerJumpParams, &Gravity)>
 ) {
-    for (entity, mut jump_params, collision_result) in jump_state_query.iter_mut() {
-        if !jump_params.grounded && collision_result.y_collision_body.is_some(){
-            jump_params.grounded = true;
+    for event in collision_events.read() {
+        if let Ok((mut jump_params, gravity)) = jump_state_query.get_mut(event.entity) {
+            // Only landing on top of something counts as ground, the ceiling
+            // or a wall when gravity pulls that way. Grounded says whether
+            // they are still on it.
+            if event.normal.dot(gravity.frame().up().as_vec2()) > 0.0 {
+                jump_params.ground = Some(event.other);
+            }
         }
-        commands.entity(entity).remove::<CollisionResult>();
     }
-}
\ No newline at end of file
+}
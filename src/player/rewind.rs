@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{animation::{AnimationController, AnimationTimer, Col, Row, SpriteSheetDefinition}, control::{PlayerControlLock, actions::PlayerActions, glyphs::InputAction}, physics::body::{Position, Remainder, Velocity}};
+
+use super::{PlayerInput, player_physics::PlayerJumpParams};
+
+// Where the player was on one fixed step
+#[derive(Debug, Clone, Copy)]
+pub struct RewindFrame {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub row: usize,
+    pub col: usize,
+    pub flip_x: bool
+}
+
+// Lets the player hold the rewind key to play back the last `seconds` of
+// movement. Once they let go, or run out of history, the ability goes on
+// cooldown.
+#[derive(Component, Debug)]
+pub struct Rewind {
+    pub seconds: f32,
+    pub cooldown: Timer,
+    // Tint while rewinding
+    pub ghost_color: Color,
+    history: VecDeque<RewindFrame>,
+    rewinding: bool
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(2.0, TimerMode::Once);
+        // Ready right away
+        cooldown.tick(cooldown.duration());
+        Rewind {
+            seconds: 3.0,
+            cooldown,
+            ghost_color: Color::rgba(0.6, 0.8, 1.0, 0.6),
+            history: VecDeque::new(),
+            rewinding: false
+        }
+    }
+}
+
+impl Rewind {
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+// Runs after the physics step so the rewound position is the one that gets drawn
+pub fn rewind_player(
+    time: Res<Time>,
+    fixed_time: Res<Time<Fixed>>,
+    actions: PlayerActions,
+    control_lock: Res<PlayerControlLock>,
+    mut player_query: Query<(
+        &PlayerInput,
+        &mut Rewind,
+        &mut Position,
+        &mut Velocity,
+        &mut Remainder,
+        &mut PlayerJumpParams,
+        &mut Row,
+        &mut Col,
+        &mut AnimationTimer,
+        &mut AnimationController,
+        &mut TextureAtlasSprite,
+        &SpriteSheetDefinition,
+        &mut Transform
+    )>
+) {
+    for (input, mut rewind, mut position, mut velocity, mut remainder, mut jump_params, mut row, mut col, mut timer, mut controller, mut sprite, sheet_def, mut transform) in player_query.iter_mut() {
+        rewind.cooldown.tick(time.delta());
+
+        let held = actions.pressed(input, InputAction::Rewind) && !control_lock.is_locked();
+        let frame = if held && (rewind.rewinding || rewind.cooldown.finished()) {
+            rewind.history.pop_back()
+        } else {
+            None
+        };
+
+        let frame = match frame {
+            Some(frame) => frame,
+            None => {
+                if rewind.rewinding {
+                    rewind.rewinding = false;
+                    rewind.cooldown.reset();
+                    sprite.color = Color::WHITE;
+                }
+
+                let capacity = (rewind.seconds / fixed_time.timestep().as_secs_f32()).ceil() as usize;
+                rewind.history.push_back(RewindFrame {
+                    position: position.0,
+                    velocity: velocity.0,
+                    row: row.0,
+                    col: col.0,
+                    flip_x: sprite.flip_x
+                });
+                while rewind.history.len() > capacity {
+                    rewind.history.pop_front();
+                }
+                continue;
+            }
+        };
+
+        rewind.rewinding = true;
+        position.0 = frame.position;
+        velocity.0 = frame.velocity;
+        remainder.0 = Vec2::ZERO;
+        jump_params.is_jumping = false;
+        transform.translation.x = frame.position.x;
+        transform.translation.y = frame.position.y;
+
+        // Played back frames aren't reached again, changing the row without
+        // change detection keeps their AnimationEvents from firing and the
+        // held timer keeps the animation from stepping past them
+        if row.0 != frame.row {
+            row.bypass_change_detection().0 = frame.row;
+        }
+        if col.0 != frame.col {
+            col.0 = frame.col;
+        }
+        timer.reset();
+        if let Some(animation) = sheet_def.animation_definitions.get(frame.row) {
+            controller.set_current(&animation.name);
+        }
+        let index = frame.col + sheet_def.columns * frame.row;
+        if sprite.index != index {
+            sprite.index = index;
+        }
+        sprite.flip_x = frame.flip_x;
+        sprite.color = rewind.ghost_color;
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::{prelude::*, time::Fixed};
+
+use crate::{animation::{AnimatedSpriteBundle, AnimationController, AnimationDefinition, Col, Row, SpriteSheetDefinition, SpriteSheetGrids}, control::{PlayerControlLock, actions::PlayerActions, glyphs::InputAction}, explosion::Explosion, physics::{PhysicsConfig, body::{Acceleration, BodyBundle, BodyType, GravityScale, Position, Velocity}, collision::AABB, gravity::Gravity, integrator::integrate, world::{PhysicsQuery, QueryMask}}, preload::GameAssets};
+
+use super::{PlayerInput, carry::Carryable};
+
+const BOMB_TEXTURE: &str = "miscellaneous sprites/bomb_thrown_anim_strip_3.png";
+// Upper bound on simulated steps for the preview, about three seconds
+const MAX_PREVIEW_STEPS: usize = 180;
+// Steps between the dots of the preview
+const PREVIEW_DOT_SPACING: usize = 3;
+
+// Hold the throw key to aim along a dotted arc, let go to throw a bomb.
+// `speed` is in units and `angle` in radians above the ground in front of the
+// player, following gravity onto walls and ceilings. Bombs go off
+// `fuse_time` seconds after being thrown with `explosion`, its position is
+// filled in when they do.
+#[derive(Component, Debug)]
+pub struct BombThrower {
+    pub speed: f32,
+    pub angle: f32,
+    pub fuse_time: f32,
+    pub cooldown: Timer,
+    pub explosion: Explosion,
+    aiming: bool
+}
+
+impl Default for BombThrower {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(1.0, TimerMode::Once);
+        // Ready right away
+        cooldown.tick(cooldown.duration());
+        BombThrower {
+            speed: 16.0,
+            angle: std::f32::consts::FRAC_PI_4,
+            fuse_time: 2.0,
+            cooldown,
+            explosion: Explosion {
+                position: Vec2::ZERO,
+                radius: 48.0,
+                impulse: 20.0,
+                damage: 3,
+                shake: 0.6
+            },
+            aiming: false
+        }
+    }
+}
+
+impl BombThrower {
+    pub fn is_aiming(&self) -> bool {
+        self.aiming
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Bomb {
+    pub fuse: Timer,
+    pub explosion: Explosion
+}
+
+#[derive(Resource)]
+pub struct BombAssets {
+    pub atlas: Handle<TextureAtlas>
+}
+
+// Leaving Loading can happen more than once, through the error screen, and
+// before the assets are there when loading is what failed
+pub fn setup_bomb_assets(
+    mut commands: Commands,
+    game_assets: Option<Res<GameAssets>>,
+    bomb_assets: Option<Res<BombAssets>>,
+    mut sprite_sheet_grids: ResMut<SpriteSheetGrids>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>
+) {
+    let game_assets = match game_assets {
+        Some(game_assets) if bomb_assets.is_none() => game_assets,
+        _ => return,
+    };
+    let atlas = sprite_sheet_grids.from_grid(&mut texture_atlases, game_assets.texture(BOMB_TEXTURE), Vec2::splat(8.0), 3, 1);
+    commands.insert_resource(BombAssets { atlas });
+}
+
+// Velocity a bomb leaves the thrower with, in units
+fn throw_velocity(thrower: &BombThrower, gravity: &Gravity, flip_x: bool) -> Vec2 {
+    let facing = if flip_x { -1.0 } else { 1.0 };
+    let local = Vec2::new(thrower.angle.cos() * facing, thrower.angle.sin()) * thrower.speed;
+    gravity.frame().to_world(local)
+}
+
+// Replays apply_gravity, integrate_bodies and move_actor for a thrown bomb
+// until it would hit a solid. Points are in pixels. Gravity zones along the
+// way aren't taken into account, the thrower's current gravity is used
+// throughout.
+pub fn predict_throw_arc(
+    config: &PhysicsConfig,
+    world: &mut PhysicsQuery,
+    start: Vec2,
+    mut velocity: Vec2,
+    gravity: Vec2,
+    timestep: f32
+) -> Vec<Vec2> {
+    let mut position = start;
+    let mut points = vec![position];
+
+    for _ in 0..MAX_PREVIEW_STEPS {
+        let acceleration = integrate(&mut velocity, gravity, timestep, |velocity| velocity);
+        let delta = config.step_pixels(config.integration.step_velocity(velocity, acceleration, timestep), timestep);
+        if let Some(hit) = world.raycast("predict_throw_arc", position, delta, delta.length(), QueryMask::SOLIDS) {
+            points.push(hit.point);
+            break;
+        }
+        position += delta;
+        points.push(position);
+    }
+
+    points
+}
+
+pub fn throw_bomb(
+    mut commands: Commands,
+    time: Res<Time>,
+    fixed_time: Res<Time<Fixed>>,
+    actions: PlayerActions,
+    control_lock: Res<PlayerControlLock>,
+    config: Res<PhysicsConfig>,
+    mut world: PhysicsQuery,
+    bomb_assets: Option<Res<BombAssets>>,
+    mut gizmos: Gizmos,
+    mut thrower_query: Query<(&PlayerInput, &mut BombThrower, &Position, &Gravity, &GravityScale, Option<&TextureAtlasSprite>)>
+) {
+    for (p_input, mut thrower, position, gravity, gravity_scale, sprite) in thrower_query.iter_mut() {
+        thrower.cooldown.tick(time.delta());
+        // Cutscenes and the like put the aim away, the throw is lost as well
+        if !thrower.cooldown.finished() || control_lock.is_locked() {
+            thrower.aiming = false;
+            continue;
+        }
+
+        thrower.aiming = actions.pressed(p_input, InputAction::Throw);
+        let velocity = throw_velocity(&thrower, gravity, sprite.is_some_and(|sprite| sprite.flip_x));
+        if thrower.aiming {
+            let points = predict_throw_arc(&config, &mut world, position.0, velocity, gravity.0, fixed_time.timestep().as_secs_f32());
+            for point in points.iter().step_by(PREVIEW_DOT_SPACING) {
+                gizmos.circle_2d(*point, 1.0, Color::WHITE);
+            }
+        }
+
+        if !actions.just_released(p_input, InputAction::Throw) {
+            continue;
+        }
+        thrower.cooldown.reset();
+
+        let mut bomb = commands.spawn((
+            BodyBundle {
+                body_type: BodyType::Actor,
+                position: *position,
+                velocity: Velocity(velocity),
+                ..Default::default()
+            },
+            AABB {
+                position: IVec2::ZERO,
+                half_size: IVec2::splat(3)
+            },
+            Acceleration::default(),
+            *gravity_scale,
+            Gravity::default(),
+            Bomb {
+                fuse: Timer::from_seconds(thrower.fuse_time, TimerMode::Once),
+                explosion: thrower.explosion
+            },
+            Carryable::default()
+        ));
+        if let Some(bomb_assets) = &bomb_assets {
+            bomb.insert(AnimatedSpriteBundle {
+                sprite_sheet: SpriteSheetBundle {
+                    texture_atlas: bomb_assets.atlas.clone(),
+                    transform: Transform::from_translation(position.0.extend(500.0)),
+                    ..Default::default()
+                },
+                sprite_sheet_definitions: SpriteSheetDefinition {
+                    animation_definitions: vec![AnimationDefinition {
+                        name: "thrown".to_string(),
+                        number_of_frames: 3,
+                        frame_time: 0.1,
+                        repeating: true,
+                        ..Default::default()
+                    }],
+                    rows: 1,
+                    columns: 3,
+                    ..Default::default()
+                },
+                current_row: Row(0),
+                current_col: Col(0),
+                controller: AnimationController::new("thrown"),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+pub fn detonate_bombs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut explosion_events: EventWriter<Explosion>,
+    mut bomb_query: Query<(Entity, &mut Bomb, &Position)>
+) {
+    for (entity, mut bomb, position) in bomb_query.iter_mut() {
+        if bomb.fuse.tick(time.delta()).just_finished() {
+            explosion_events.send(Explosion {
+                position: position.0,
+                ..bomb.explosion
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
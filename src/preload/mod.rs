@@ -0,0 +1,236 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState, LoadedUntypedAsset, UntypedAssetId, io::Reader},
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{animation::SpriteSheetDefinition, credits::Credits, ldtk::ldtk_json::Project, mods::{ModPacks, pack_path}, player::character::CharacterDef, timeline::Timeline};
+
+// Everything the game loads up front, listed in `assets/manifest.ron` as paths
+// relative to the assets folder
+#[derive(Asset, TypePath, Deserialize, Debug, Clone, Default)]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub textures: Vec<String>,
+    #[serde(default)]
+    pub maps: Vec<String>,
+    #[serde(default)]
+    pub timelines: Vec<String>,
+    #[serde(default)]
+    pub credits: Vec<String>,
+    #[serde(default)]
+    pub characters: Vec<String>,
+    #[serde(default)]
+    pub sounds: Vec<String>,
+    #[serde(default)]
+    pub animations: Vec<String>
+}
+
+#[derive(Default)]
+pub struct AssetManifestLoader;
+
+impl AssetLoader for AssetManifestLoader {
+    type Asset = AssetManifest;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let manifest = ron::de::from_bytes::<AssetManifest>(&bytes)?;
+            Ok(manifest)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["manifest.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ManifestHandle(Handle<AssetManifest>);
+
+// Manifest of each mod pack, by pack name
+#[derive(Resource)]
+struct ModManifestHandles(Vec<(String, Handle<AssetManifest>)>);
+
+// Handles for everything in the manifest, keyed by the path they were listed
+// under. Gets inserted once the manifest itself has loaded. Sounds have no
+// typed loader yet so they are kept untyped.
+#[derive(Resource, Default)]
+pub struct GameAssets {
+    pub textures: HashMap<String, Handle<Image>>,
+    pub maps: HashMap<String, Handle<Project>>,
+    pub timelines: HashMap<String, Handle<Timeline>>,
+    pub credits: HashMap<String, Handle<Credits>>,
+    pub characters: HashMap<String, Handle<CharacterDef>>,
+    pub sounds: HashMap<String, Handle<LoadedUntypedAsset>>,
+    pub animations: HashMap<String, Handle<SpriteSheetDefinition>>
+}
+
+impl GameAssets {
+    pub fn texture(&self, path: &str) -> Handle<Image> {
+        self.textures.get(path).cloned().unwrap_or_else(|| {
+            warn!("Texture {} is not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    pub fn map(&self, path: &str) -> Handle<Project> {
+        self.maps.get(path).cloned().unwrap_or_else(|| {
+            warn!("Map {} is not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    pub fn timeline(&self, path: &str) -> Handle<Timeline> {
+        self.timelines.get(path).cloned().unwrap_or_else(|| {
+            warn!("Timeline {} is not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    pub fn credits(&self, path: &str) -> Handle<Credits> {
+        self.credits.get(path).cloned().unwrap_or_else(|| {
+            warn!("Credits {} are not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    pub fn character(&self, path: &str) -> Handle<CharacterDef> {
+        self.characters.get(path).cloned().unwrap_or_else(|| {
+            warn!("Character {} is not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    pub fn animation(&self, path: &str) -> Handle<SpriteSheetDefinition> {
+        self.animations.get(path).cloned().unwrap_or_else(|| {
+            warn!("Animations {} are not in the asset manifest", path);
+            Handle::default()
+        })
+    }
+
+    fn ids(&self) -> impl Iterator<Item = (&String, UntypedAssetId)> + '_ {
+        self.textures.iter().map(|(path, handle)| (path, handle.id().untyped()))
+            .chain(self.maps.iter().map(|(path, handle)| (path, handle.id().untyped())))
+            .chain(self.timelines.iter().map(|(path, handle)| (path, handle.id().untyped())))
+            .chain(self.credits.iter().map(|(path, handle)| (path, handle.id().untyped())))
+            .chain(self.characters.iter().map(|(path, handle)| (path, handle.id().untyped())))
+            .chain(self.sounds.iter().map(|(path, handle)| (path, handle.id().untyped())))
+            .chain(self.animations.iter().map(|(path, handle)| (path, handle.id().untyped())))
+    }
+
+    // Loaded and total number of assets, for a loading screen
+    pub fn progress(&self, asset_server: &AssetServer) -> (usize, usize) {
+        self.ids().fold((0, 0), |(loaded, total), (_, id)| {
+            (loaded + asset_server.is_loaded_with_dependencies(id) as usize, total + 1)
+        })
+    }
+
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        let (loaded, total) = self.progress(asset_server);
+        loaded == total
+    }
+
+    // Paths of everything that failed to load, these never count as loaded
+    pub fn failed(&self, asset_server: &AssetServer) -> Vec<String> {
+        self.ids()
+            .filter(|(_, id)| asset_server.get_load_state(*id) == Some(LoadState::Failed))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+fn load_manifest(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mod_packs: Option<Res<ModPacks>>
+) {
+    commands.insert_resource(ManifestHandle(asset_server.load("manifest.ron")));
+    let packs = mod_packs.map(|mod_packs| mod_packs.0.clone()).unwrap_or_default();
+    commands.insert_resource(ModManifestHandles(packs.into_iter().map(|pack| {
+        let handle = asset_server.load(pack_path(&pack, "manifest.ron"));
+        (pack, handle)
+    }).collect()));
+}
+
+// `key` turns a listed path into the one loaded, which is also what it's found under
+fn load_all<A: Asset>(asset_server: &AssetServer, paths: &[String], key: impl Fn(&str) -> String) -> HashMap<String, Handle<A>> {
+    paths.iter().map(|path| {
+        let path = key(path);
+        (path.clone(), asset_server.load(path))
+    }).collect()
+}
+
+fn load_untyped(asset_server: &AssetServer, paths: &[String], key: impl Fn(&str) -> String) -> HashMap<String, Handle<LoadedUntypedAsset>> {
+    paths.iter().map(|path| {
+        let path = key(path);
+        (path.clone(), asset_server.load_untyped(path))
+    }).collect()
+}
+
+impl GameAssets {
+    fn load_manifest(&mut self, asset_server: &AssetServer, manifest: &AssetManifest, key: impl Fn(&str) -> String + Copy) {
+        self.textures.extend(load_all(asset_server, &manifest.textures, key));
+        self.maps.extend(load_all(asset_server, &manifest.maps, key));
+        self.timelines.extend(load_all(asset_server, &manifest.timelines, key));
+        self.credits.extend(load_all(asset_server, &manifest.credits, key));
+        self.characters.extend(load_all(asset_server, &manifest.characters, key));
+        self.sounds.extend(load_untyped(asset_server, &manifest.sounds, key));
+        self.animations.extend(load_all(asset_server, &manifest.animations, key));
+    }
+}
+
+fn preload_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    manifests: Res<Assets<AssetManifest>>,
+    manifest_handle: Res<ManifestHandle>,
+    mod_manifest_handles: Res<ModManifestHandles>,
+    game_assets: Option<Res<GameAssets>>
+) {
+    if game_assets.is_some() {
+        return;
+    }
+
+    let manifest = match manifests.get(&manifest_handle.0) {
+        Some(manifest) => manifest,
+        None => return,
+    };
+    // A broken mod manifest only loses that mod
+    let mods_done = mod_manifest_handles.0.iter().all(|(_, handle)| {
+        manifests.contains(handle) || asset_server.get_load_state(handle) == Some(LoadState::Failed)
+    });
+    if !mods_done {
+        return;
+    }
+
+    let mut game_assets = GameAssets::default();
+    game_assets.load_manifest(&asset_server, manifest, |path| path.to_string());
+    for (pack, handle) in mod_manifest_handles.0.iter() {
+        match manifests.get(handle) {
+            Some(mod_manifest) => game_assets.load_manifest(&asset_server, mod_manifest, |path| pack_path(pack, path)),
+            None => warn!("Mod {} has a broken manifest, skipping it", pack),
+        }
+    }
+    commands.insert_resource(game_assets);
+}
+
+pub struct PreloadPlugin;
+
+impl Plugin for PreloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AssetManifest>()
+            .init_asset_loader::<AssetManifestLoader>()
+            .add_systems(Startup, load_manifest)
+            .add_systems(Update, preload_assets);
+    }
+}
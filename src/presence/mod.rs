@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{camera::MainCamera, level::{AppState, mood::LevelMood}};
+
+// What the game is up to, for Steam or Discord to show as "Playing Level_3"
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presence {
+    // LDtk identifier of the level the camera is in, None outside of a level
+    pub level: Option<String>,
+    pub mode: &'static str,
+    // Only counts time in game, menus and credits don't add to it
+    pub play_time: Duration
+}
+
+// Whatever shows the presence. Distribution builds put their SDK behind this
+// so none of it ends up in the game itself, see PresenceBackends.
+pub trait PresenceBackend: Send + Sync + 'static {
+    // Called whenever the level or mode changes and every `refresh` otherwise,
+    // so backends that only take an elapsed time can skip the play time updates
+    fn update(&mut self, presence: &Presence);
+
+    // Last chance to clear the presence before the game closes
+    fn shutdown(&mut self) {}
+}
+
+// Used when nothing else is set up
+pub struct NoPresence;
+
+impl PresenceBackend for NoPresence {
+    fn update(&mut self, _presence: &Presence) {}
+}
+
+// `app.insert_resource(PresenceBackends::new(SteamPresence::new()))` after
+// PresencePlugin swaps out the no-op backend
+#[derive(Resource)]
+pub struct PresenceBackends {
+    pub backend: Box<dyn PresenceBackend>,
+    pub refresh: Timer
+}
+
+impl PresenceBackends {
+    pub fn new(backend: impl PresenceBackend) -> Self {
+        PresenceBackends {
+            backend: Box::new(backend),
+            refresh: Timer::from_seconds(15.0, TimerMode::Repeating)
+        }
+    }
+}
+
+impl Default for PresenceBackends {
+    fn default() -> Self {
+        PresenceBackends::new(NoPresence)
+    }
+}
+
+pub fn presence_mode(state: &AppState) -> &'static str {
+    match state {
+        AppState::Loading => "Loading",
+        AppState::CharacterSelect => "Picking a character",
+        AppState::InGame => "Playing",
+        AppState::Credits => "Watching the credits",
+        AppState::Error => "Stuck on an error",
+    }
+}
+
+// Same camera check the level tint uses, so the level is whichever one is on screen
+fn update_presence(
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    mut presence: ResMut<Presence>,
+    mood_query: Query<&LevelMood>,
+    camera_query: Query<&Transform, With<MainCamera>>
+) {
+    let mode = presence_mode(state.get());
+    if presence.mode != mode {
+        presence.mode = mode;
+    }
+
+    if *state.get() != AppState::InGame {
+        return;
+    }
+    presence.play_time += time.delta();
+
+    if let Ok(camera) = camera_query.get_single() {
+        let camera = camera.translation.truncate();
+        let level = mood_query.iter().find(|mood| mood.bounds.contains(camera)).map(|mood| &mood.level);
+        if level.is_some() && presence.level.as_ref() != level {
+            presence.level = level.cloned();
+        }
+    }
+}
+
+fn send_presence(
+    time: Res<Time>,
+    presence: Res<Presence>,
+    mut backends: ResMut<PresenceBackends>,
+    mut sent: Local<Presence>
+) {
+    let refresh = backends.refresh.tick(time.delta()).just_finished();
+    // Play time alone changes every frame, only the level and mode are worth sending right away
+    if refresh || presence.level != sent.level || presence.mode != sent.mode {
+        backends.backend.update(&presence);
+        *sent = presence.clone();
+        backends.refresh.reset();
+    }
+}
+
+fn shutdown_presence(
+    mut exit_events: EventReader<bevy::app::AppExit>,
+    mut backends: ResMut<PresenceBackends>
+) {
+    if exit_events.read().next().is_some() {
+        backends.backend.shutdown();
+    }
+}
+
+pub struct PresencePlugin;
+
+impl Plugin for PresencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Presence>()
+            .init_resource::<PresenceBackends>()
+            .add_systems(Update, (update_presence, send_presence).chain().run_if(resource_exists::<State<AppState>>()))
+            .add_systems(Last, shutdown_presence);
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::{
+    prelude::*,
+    reflect::TypePath,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+    transform::TransformSystem,
+};
+
+use crate::camera::parallax::ParallaxLayer;
+
+// Put on a sprite sheet entity to draw it as a flat colored silhouette
+// wherever a foreground layer covers it, so it can't get lost behind the
+// decoration. Parallax layers in front of the sprite count as foreground.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Silhouette {
+    pub color: Color
+}
+
+impl Default for Silhouette {
+    fn default() -> Self {
+        Silhouette {
+            color: Color::rgba(0.45, 0.8, 1.0, 0.7)
+        }
+    }
+}
+
+// Masks the sprite's current frame by the foreground layer's alpha, see
+// assets/shaders/silhouette.wgsl
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct SilhouetteMaterial {
+    #[uniform(0)]
+    pub color: Color,
+    #[uniform(0)]
+    pub frame: Vec4,
+    #[uniform(0)]
+    pub foreground: Vec4,
+    #[texture(1)]
+    #[sampler(2)]
+    pub sprite_texture: Handle<Image>,
+    #[texture(3)]
+    #[sampler(4)]
+    pub foreground_texture: Handle<Image>
+}
+
+impl Material2d for SilhouetteMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/silhouette.wgsl".into()
+    }
+}
+
+// Drawn just in front of one foreground layer, following one silhouetted sprite
+#[derive(Component)]
+struct SilhouetteOverlay {
+    target: Entity,
+    layer: Entity
+}
+
+#[derive(Resource)]
+struct SilhouetteMesh(Mesh2dHandle);
+
+fn setup_silhouette_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>
+) {
+    // Unit sized, overlays get scaled up to the frame size
+    commands.insert_resource(SilhouetteMesh(meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE))).into()));
+}
+
+fn spawn_silhouette_overlays(
+    mut commands: Commands,
+    mesh: Res<SilhouetteMesh>,
+    mut materials: ResMut<Assets<SilhouetteMaterial>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    target_query: Query<(Entity, &Transform, &Handle<TextureAtlas>), With<Silhouette>>,
+    layer_query: Query<(Entity, &Transform, &Handle<Image>), (With<ParallaxLayer>, Without<Silhouette>)>,
+    overlay_query: Query<&SilhouetteOverlay>
+) {
+    for (target, target_transform, atlas_handle) in target_query.iter() {
+        let atlas = match texture_atlases.get(atlas_handle) {
+            Some(atlas) => atlas,
+            None => continue,
+        };
+
+        for (layer, layer_transform, layer_texture) in layer_query.iter() {
+            let in_front = layer_transform.translation.z > target_transform.translation.z;
+            if !in_front || overlay_query.iter().any(|overlay| overlay.target == target && overlay.layer == layer) {
+                continue;
+            }
+
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: mesh.0.clone(),
+                    material: materials.add(SilhouetteMaterial {
+                        color: Color::NONE,
+                        frame: Vec4::ZERO,
+                        foreground: Vec4::ZERO,
+                        sprite_texture: atlas.texture.clone(),
+                        foreground_texture: layer_texture.clone()
+                    }),
+                    ..Default::default()
+                },
+                SilhouetteOverlay { target, layer }
+            ));
+        }
+    }
+}
+
+fn update_silhouette_overlays(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut materials: ResMut<Assets<SilhouetteMaterial>>,
+    target_query: Query<(&Transform, &TextureAtlasSprite, &Handle<TextureAtlas>, &Silhouette, &InheritedVisibility), Without<SilhouetteOverlay>>,
+    layer_query: Query<(&Transform, &Handle<Image>), (With<ParallaxLayer>, Without<SilhouetteOverlay>)>,
+    mut overlay_query: Query<(Entity, &SilhouetteOverlay, &Handle<SilhouetteMaterial>, &mut Transform, &mut Visibility)>
+) {
+    for (entity, overlay, material_handle, mut transform, mut visibility) in overlay_query.iter_mut() {
+        let (target, layer) = match (target_query.get(overlay.target), layer_query.get(overlay.layer)) {
+            (Ok(target), Ok(layer)) => (target, layer),
+            _ => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+        let (target_transform, sprite, atlas_handle, silhouette, target_visibility) = target;
+        let (layer_transform, layer_texture) = layer;
+
+        let frame = texture_atlases.get(atlas_handle).and_then(|atlas| atlas.textures.get(sprite.index).map(|rect| (atlas.size, *rect)));
+        let (layer_image, material) = (images.get(layer_texture), materials.get_mut(material_handle));
+        let ((atlas_size, rect), layer_image, material) = match (frame, layer_image, material) {
+            (Some(frame), Some(layer_image), Some(material)) if target_visibility.get() => (frame, layer_image, material),
+            _ => {
+                *visibility = Visibility::Hidden;
+                continue;
+            }
+        };
+        *visibility = Visibility::Inherited;
+
+        // Sit on the sprite, just in front of the layer. Both are top level
+        // entities so their transforms are already in world space. The
+        // sprite's anchor shifts it off its transform, the overlay follows.
+        let anchor_offset = target_transform.rotation * (-sprite.anchor.as_vec() * rect.size() * target_transform.scale.truncate()).extend(0.0);
+        transform.translation = (target_transform.translation + anchor_offset).truncate().extend(layer_transform.translation.z + 0.5);
+        // Turned with sprites walking on walls and ceilings
+        transform.rotation = target_transform.rotation;
+        transform.scale = (rect.size() * target_transform.scale.truncate()).extend(1.0);
+
+        let (mut min, mut max) = (rect.min / atlas_size, rect.max / atlas_size);
+        if sprite.flip_x {
+            std::mem::swap(&mut min.x, &mut max.x);
+        }
+        if sprite.flip_y {
+            std::mem::swap(&mut min.y, &mut max.y);
+        }
+
+        let layer_size = layer_image.size_f32() * layer_transform.scale.truncate();
+        let layer_min = layer_transform.translation.truncate() - layer_size / 2.0;
+
+        material.color = silhouette.color;
+        material.frame = Vec4::new(min.x, min.y, max.x, max.y);
+        material.foreground = Vec4::new(layer_min.x, layer_min.y, layer_size.x, layer_size.y);
+    }
+}
+
+pub struct SilhouettePlugin;
+
+impl Plugin for SilhouettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<SilhouetteMaterial>::default())
+            .add_systems(Startup, setup_silhouette_mesh)
+            .add_systems(PostUpdate, (spawn_silhouette_overlays, update_silhouette_overlays).chain().before(TransformSystem::TransformPropagate));
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+
+// How long everything takes to appear and to go away, kept here so every
+// enemy and pickup feels the same
+pub const MATERIALIZE_TIME: f32 = 0.4;
+pub const POOF_TIME: f32 = 0.25;
+// How much bigger things get as they poof away
+const POOF_GROWTH: f32 = 0.5;
+
+// Spawn enemies and pickups with this to have them grow and fade in. While
+// it's on they can't be hurt or collected, it removes itself once done.
+#[derive(Component, Debug)]
+pub struct SpawnEffect {
+    pub timer: Timer,
+    scale: Option<Vec3>
+}
+
+impl Default for SpawnEffect {
+    fn default() -> Self {
+        SpawnEffect {
+            timer: Timer::from_seconds(MATERIALIZE_TIME, TimerMode::Once),
+            scale: None
+        }
+    }
+}
+
+// Insert instead of despawning to poof the entity away first. Like
+// SpawnEffect it can't be hurt or collected while this plays, the entity and
+// its children are despawned at the end.
+#[derive(Component, Debug)]
+pub struct DespawnEffect {
+    pub timer: Timer,
+    scale: Option<Vec3>
+}
+
+impl Default for DespawnEffect {
+    fn default() -> Self {
+        DespawnEffect {
+            timer: Timer::from_seconds(POOF_TIME, TimerMode::Once),
+            scale: None
+        }
+    }
+}
+
+fn set_alpha(sprite: Option<Mut<Sprite>>, atlas_sprite: Option<Mut<TextureAtlasSprite>>, alpha: f32) {
+    if let Some(mut sprite) = sprite {
+        sprite.color.set_a(alpha);
+    }
+    if let Some(mut sprite) = atlas_sprite {
+        sprite.color.set_a(alpha);
+    }
+}
+
+fn materialize(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effect_query: Query<(Entity, &mut SpawnEffect, &mut Transform, Option<&mut Sprite>, Option<&mut TextureAtlasSprite>), Without<DespawnEffect>>
+) {
+    for (entity, mut effect, mut transform, sprite, atlas_sprite) in effect_query.iter_mut() {
+        // Whatever scale it was spawned with is where it grows to
+        let scale = *effect.scale.get_or_insert(transform.scale);
+        effect.timer.tick(time.delta());
+        let t = effect.timer.percent();
+        // Eases out so it pops in rather than crawling up to size
+        transform.scale = scale * (1.0 - (1.0 - t) * (1.0 - t));
+        set_alpha(sprite, atlas_sprite, t);
+
+        if effect.timer.finished() {
+            transform.scale = scale;
+            commands.entity(entity).remove::<SpawnEffect>();
+        }
+    }
+}
+
+fn poof(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effect_query: Query<(Entity, &mut DespawnEffect, &mut Transform, Option<&mut Sprite>, Option<&mut TextureAtlasSprite>)>
+) {
+    for (entity, mut effect, mut transform, sprite, atlas_sprite) in effect_query.iter_mut() {
+        let scale = *effect.scale.get_or_insert(transform.scale);
+        effect.timer.tick(time.delta());
+        let t = effect.timer.percent();
+        transform.scale = scale * (1.0 + POOF_GROWTH * t);
+        set_alpha(sprite, atlas_sprite, 1.0 - t);
+
+        if effect.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// A poof partway through materializing takes over from it
+fn cancel_materialize(
+    mut commands: Commands,
+    effect_query: Query<Entity, (With<SpawnEffect>, Added<DespawnEffect>)>
+) {
+    for entity in effect_query.iter() {
+        commands.entity(entity).remove::<SpawnEffect>();
+    }
+}
+
+pub struct SpawnEffectPlugin;
+
+impl Plugin for SpawnEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (cancel_materialize, materialize, poof).chain());
+    }
+}
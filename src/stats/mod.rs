@@ -0,0 +1,100 @@
+use bevy::{ecs::{archetype::Archetypes, component::Components}, prelude::*, utils::get_short_name};
+use bevy_egui::{EguiContexts, egui};
+
+use crate::physics::{body::BodyType, collision::AABB};
+
+// Snapshot of what is alive in the world, refreshed on a timer so the panel is
+// readable and counting doesn't happen every frame
+#[derive(Resource)]
+pub struct EntityStats {
+    pub refresh: Timer,
+    pub total: usize,
+    pub categories: Vec<(&'static str, usize)>,
+    // Component list and entity count of the biggest archetypes
+    pub archetypes: Vec<(String, usize)>,
+    pub max_archetypes: usize
+}
+
+impl Default for EntityStats {
+    fn default() -> Self {
+        EntityStats {
+            refresh: Timer::from_seconds(1.0, TimerMode::Repeating),
+            total: 0,
+            categories: Vec::new(),
+            archetypes: Vec::new(),
+            max_archetypes: 20
+        }
+    }
+}
+
+fn collect_entity_stats(
+    time: Res<Time>,
+    mut stats: ResMut<EntityStats>,
+    archetypes: &Archetypes,
+    components: &Components,
+    all_query: Query<Entity>,
+    tile_query: Query<Entity, (With<TextureAtlasSprite>, Without<AABB>)>,
+    body_query: Query<&BodyType, With<AABB>>,
+    sprite_query: Query<Entity, With<Sprite>>,
+    ui_query: Query<Entity, With<Node>>
+) {
+    // Fill in right away instead of showing zeros until the first refresh
+    if !stats.refresh.tick(time.delta()).just_finished() && stats.total != 0 {
+        return;
+    }
+
+    let colliders = body_query.iter().filter(|body_type| **body_type == BodyType::Solid).count();
+    let actors = body_query.iter().filter(|body_type| **body_type == BodyType::Actor).count();
+
+    stats.total = all_query.iter().count();
+    stats.categories = vec![
+        ("Tiles", tile_query.iter().count()),
+        ("Colliders", colliders),
+        ("Actors", actors),
+        ("Sprites", sprite_query.iter().count()),
+        ("UI nodes", ui_query.iter().count()),
+    ];
+
+    let mut archetype_counts: Vec<(String, usize)> = archetypes.iter().filter(|archetype| !archetype.is_empty()).map(|archetype| {
+        let names: Vec<String> = archetype.components().filter_map(|id| {
+            components.get_info(id).map(|info| get_short_name(info.name()))
+        }).collect();
+        (names.join(", "), archetype.len())
+    }).collect();
+    archetype_counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    archetype_counts.truncate(stats.max_archetypes);
+    stats.archetypes = archetype_counts;
+}
+
+fn debug_entity_stats(
+    mut egui_ctx: EguiContexts,
+    stats: Res<EntityStats>
+) {
+    egui::Window::new("Entities").vscroll(true).show(egui_ctx.ctx_mut(), |ui| {
+        ui.label(format!("Total: {}", stats.total));
+        egui::Grid::new("Entity categories").show(ui, |ui| {
+            for (name, count) in stats.categories.iter() {
+                ui.label(*name);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+
+        ui.collapsing("Archetypes", |ui| {
+            for (components, count) in stats.archetypes.iter() {
+                ui.label(format!("{}: {}", count, components));
+            }
+        });
+    });
+}
+
+pub struct EntityStatsPlugin;
+
+impl Plugin for EntityStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntityStats>()
+            .add_systems(Update, (collect_entity_stats, debug_entity_stats).chain());
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader},
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{animation::AnimationController, camera::{CameraFocus, MainCamera}, control::{ControlLockReason, ControlLockToken, PlayerControlLock}, physics::body::Position};
+
+// A scripted sequence of events, written as RON in `*.timeline.ron` files.
+// Keyframes fire once the timeline clock passes their time (in seconds). A
+// `Wait` stops the clock for its duration so everything after it gets pushed
+// back without having to retime the rest of the file.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Timeline {
+    #[serde(default)]
+    pub lock_player: bool,
+    pub keyframes: Vec<Keyframe>
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub event: TimelineEvent
+}
+
+// Entities are referenced by their `Name`
+#[derive(Deserialize, Debug, Clone)]
+pub enum TimelineEvent {
+    MoveEntity { target: String, to: (f32, f32), duration: f32 },
+    PlayAnimation { target: String, animation: String },
+    Wait(f32),
+    CameraCut { to: (f32, f32) },
+    CameraRelease,
+    Spawn { name: String, texture: String, position: (f32, f32) },
+    SetFlag { flag: String, value: bool }
+}
+
+// Flags set by timelines so gameplay code can react to how a sequence played out
+#[derive(Resource, Default, Debug)]
+pub struct TimelineFlags(pub HashMap<String, bool>);
+
+impl TimelineFlags {
+    pub fn get(&self, flag: &str) -> bool {
+        *self.0.get(flag).unwrap_or(&false)
+    }
+}
+
+// Control locks held by playing timelines. Kept out of TimelinePlayer so a
+// lock still gets released when its player is despawned before the end.
+#[derive(Resource, Default, Debug)]
+struct TimelineControlLocks(HashMap<Entity, ControlLockToken>);
+
+#[derive(Event)]
+pub struct TimelineFinished(pub Handle<Timeline>);
+
+struct ActiveMove {
+    target: Entity,
+    from: Vec2,
+    to: Vec2,
+    duration: f32,
+    elapsed: f32
+}
+
+// Spawn an entity with this to start playing a timeline, it despawns itself
+// once every keyframe has fired and all moves are done
+#[derive(Component)]
+pub struct TimelinePlayer {
+    pub timeline: Handle<Timeline>,
+    pub elapsed: f32,
+    next_keyframe: usize,
+    wait_remaining: f32,
+    moves: Vec<ActiveMove>
+}
+
+impl TimelinePlayer {
+    pub fn new(timeline: Handle<Timeline>) -> Self {
+        TimelinePlayer {
+            timeline,
+            elapsed: 0.0,
+            next_keyframe: 0,
+            wait_remaining: 0.0,
+            moves: Vec::new()
+        }
+    }
+
+    fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.next_keyframe = 0;
+        self.wait_remaining = 0.0;
+        self.moves.clear();
+    }
+}
+
+#[derive(Default)]
+pub struct TimelineAssetLoader;
+
+impl AssetLoader for TimelineAssetLoader {
+    type Asset = Timeline;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let timeline = ron::de::from_bytes::<Timeline>(&bytes)?;
+            Ok(timeline)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["timeline.ron"]
+    }
+}
+
+fn play_timelines(
+    mut commands: Commands,
+    time: Res<Time>,
+    timelines: Res<Assets<Timeline>>,
+    asset_server: Res<AssetServer>,
+    mut flags: ResMut<TimelineFlags>,
+    mut camera_focus: ResMut<CameraFocus>,
+    mut control_lock: ResMut<PlayerControlLock>,
+    mut control_locks: ResMut<TimelineControlLocks>,
+    mut finished_events: EventWriter<TimelineFinished>,
+    mut player_query: Query<(Entity, &mut TimelinePlayer)>,
+    name_query: Query<(Entity, &Name)>,
+    mut animation_query: Query<&mut AnimationController>,
+    mut transform_queries: ParamSet<(
+        Query<(Option<&mut Position>, &mut Transform)>,
+        Query<&mut Transform, With<MainCamera>>
+    )>
+) {
+    let find_named = |name: &str| {
+        name_query.iter().find(|(_, entity_name)| entity_name.as_str() == name).map(|(entity, _)| entity)
+    };
+
+    for (entity, mut player) in player_query.iter_mut() {
+        let timeline = match timelines.get(&player.timeline) {
+            Some(timeline) => timeline,
+            None => continue,
+        };
+
+        if timeline.lock_player && !control_locks.0.contains_key(&entity) {
+            control_locks.0.insert(entity, control_lock.acquire(ControlLockReason::Cutscene));
+        }
+
+        let mut delta = time.delta_seconds();
+        if player.wait_remaining > 0.0 {
+            let waited = delta.min(player.wait_remaining);
+            player.wait_remaining -= waited;
+            delta -= waited;
+        }
+        player.elapsed += delta;
+
+        // Fire everything that is due, a Wait stops the rest from firing this frame
+        while player.wait_remaining <= 0.0 && player.next_keyframe < timeline.keyframes.len()
+            && timeline.keyframes[player.next_keyframe].time <= player.elapsed {
+            let keyframe = &timeline.keyframes[player.next_keyframe];
+            player.next_keyframe += 1;
+
+            match &keyframe.event {
+                TimelineEvent::MoveEntity { target, to, duration } => {
+                    if let Some(target) = find_named(target) {
+                        if let Ok((position, transform)) = transform_queries.p0().get_mut(target) {
+                            let from = position.map_or(transform.translation.truncate(), |position| position.0);
+                            player.moves.push(ActiveMove {
+                                target,
+                                from,
+                                to: Vec2::new(to.0, to.1),
+                                duration: *duration,
+                                elapsed: 0.0
+                            });
+                        }
+                    }
+                }
+                TimelineEvent::PlayAnimation { target, animation } => {
+                    if let Some(target) = find_named(target) {
+                        if let Ok(mut controller) = animation_query.get_mut(target) {
+                            controller.play(animation);
+                        }
+                    }
+                }
+                TimelineEvent::Wait(duration) => {
+                    player.wait_remaining += duration;
+                }
+                TimelineEvent::CameraCut { to } => {
+                    let focus = Vec2::new(to.0, to.1);
+                    camera_focus.0 = Some(focus);
+                    for mut transform in transform_queries.p1().iter_mut() {
+                        transform.translation.x = focus.x;
+                        transform.translation.y = focus.y;
+                    }
+                }
+                TimelineEvent::CameraRelease => {
+                    camera_focus.0 = None;
+                }
+                TimelineEvent::Spawn { name, texture, position } => {
+                    commands.spawn(SpriteBundle {
+                        texture: asset_server.load(texture.clone()),
+                        transform: Transform::from_translation(Vec3::new(position.0, position.1, 500.0)),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(name.clone()));
+                }
+                TimelineEvent::SetFlag { flag, value } => {
+                    flags.0.insert(flag.clone(), *value);
+                }
+            }
+        }
+
+        for active_move in player.moves.iter_mut() {
+            active_move.elapsed += time.delta_seconds();
+            let t = if active_move.duration > 0.0 { (active_move.elapsed / active_move.duration).min(1.0) } else { 1.0 };
+            let new_position = active_move.from.lerp(active_move.to, t);
+            if let Ok((position, mut transform)) = transform_queries.p0().get_mut(active_move.target) {
+                match position {
+                    Some(mut position) => position.0 = new_position,
+                    None => {
+                        transform.translation.x = new_position.x;
+                        transform.translation.y = new_position.y;
+                    }
+                }
+            }
+        }
+        player.moves.retain(|active_move| active_move.elapsed < active_move.duration);
+
+        if player.next_keyframe >= timeline.keyframes.len() && player.moves.is_empty() && player.wait_remaining <= 0.0 {
+            if let Some(token) = control_locks.0.remove(&entity) {
+                control_lock.release(token);
+            }
+            // Hand the camera back in case the timeline forgot to
+            camera_focus.0 = None;
+            finished_events.send(TimelineFinished(player.timeline.clone()));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Timelines despawned part way through, by a level change or anything else,
+// give control back like finished ones do
+fn release_removed_timelines(
+    mut removed: RemovedComponents<TimelinePlayer>,
+    mut control_lock: ResMut<PlayerControlLock>,
+    mut control_locks: ResMut<TimelineControlLocks>
+) {
+    for entity in removed.read() {
+        if let Some(token) = control_locks.0.remove(&entity) {
+            control_lock.release(token);
+        }
+    }
+}
+
+// Editing a timeline on disk restarts anything currently playing it
+fn reload_timelines(
+    mut timeline_events: EventReader<AssetEvent<Timeline>>,
+    mut player_query: Query<&mut TimelinePlayer>
+) {
+    for event in timeline_events.read() {
+        if let AssetEvent::Modified { id } = event {
+            for mut player in player_query.iter_mut() {
+                if player.timeline.id() == *id {
+                    player.restart();
+                }
+            }
+        }
+    }
+}
+
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Timeline>()
+            .init_asset_loader::<TimelineAssetLoader>()
+            .add_event::<TimelineFinished>()
+            .init_resource::<TimelineFlags>()
+            .init_resource::<CameraFocus>()
+            .init_resource::<PlayerControlLock>()
+            .init_resource::<TimelineControlLocks>()
+            .add_systems(Update, (reload_timelines, play_timelines, release_removed_timelines).chain());
+    }
+}